@@ -1,17 +1,14 @@
-use crate::{Config, ErrorKind};
-use mongodb::bson::doc;
+use chrono::{DateTime, FixedOffset, Utc};
 use mongodb::bson::Bson;
-use mongodb::{Client, Collection, Cursor, Database};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::fmt::{Display, Formatter};
-use tracing::{info, trace};
 
-use mongodb::options::ClientOptions;
-
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Venue {
     pub venue_id: String,
     pub name: String,
+    pub address: Option<String>,
 }
 
 impl Display for Venue {
@@ -20,108 +17,167 @@ impl Display for Venue {
     }
 }
 
-#[derive(Debug, Deserialize, Serialize, Clone)]
+/// Whether an agenda item is still on the listing of the venue.
+#[derive(Debug, Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum AgendaStatus {
+    #[default]
+    Active,
+    /// Upcoming, but no longer on the listing.
+    Removed,
+    /// On the listing, marked as cancelled by the venue.
+    Cancelled,
+    /// No longer on the listing after it took place.
+    Past,
+}
+
+/// Words venues use on the listing for a cancelled event.
+const CANCELLED_WORDS: [&str; 5] = [
+    "geannuleerd",
+    "afgelast",
+    "gecanceld",
+    "cancelled",
+    "canceled",
+];
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
 pub struct Agenda {
     #[serde(skip_serializing)]
     pub _id: Option<Bson>,
 
     pub url: String,
+    /// The link to the details page as the listing has it, resolved against the listing page. The
+    /// url is the canonical form of it.
+    #[serde(default)]
+    pub details_url: Option<String>,
     pub title: String,
     pub description: Option<String>,
 
     pub venue_id: String,
+    /// The listing page the agenda item was last found on.
+    #[serde(default)]
+    pub listing_url: Option<String>,
 
     pub needs_details: bool,
-}
-
-impl Display for Agenda {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("Agenda")
-            .field("_id", &self._id)
-            .field("url", &self.url)
-            .field("title", &self.title)
-            .field("description", &self.description)
-            .finish()
-    }
-}
 
-/// Creates the agenda collection for the database.
-fn agenda_collection(db: &Database) -> Collection<Agenda> {
-    db.collection::<Agenda>("agenda")
+    /// Fields filled from the details page of the agenda item.
+    #[serde(default)]
+    pub start_text: Option<String>,
+    #[serde(default)]
+    pub doors_open_text: Option<String>,
+    #[serde(default)]
+    pub price: Option<String>,
+    #[serde(default)]
+    pub ticket_url: Option<String>,
+    #[serde(default)]
+    pub image_url: Option<String>,
+    #[serde(default)]
+    pub long_description: Option<String>,
+    #[serde(default)]
+    pub support_acts: Vec<String>,
+
+    /// Dates parsed from the start and doors open texts, in the Europe/Amsterdam timezone.
+    #[serde(default)]
+    pub starts_at: Option<DateTime<FixedOffset>>,
+    #[serde(default)]
+    pub ends_at: Option<DateTime<FixedOffset>>,
+    #[serde(default)]
+    pub doors_open_at: Option<DateTime<FixedOffset>>,
+    #[serde(default)]
+    pub all_day: bool,
+
+    /// Hash of the content of the item on the listing page, to detect changes between syncs.
+    #[serde(default)]
+    pub listing_hash: Option<String>,
+    /// When the item was inserted, or last changed on the listing page.
+    #[serde(default)]
+    pub updated_at: Option<DateTime<Utc>>,
+
+    #[serde(default)]
+    pub status: AgendaStatus,
+    /// The start of the last full sync that found the item on the listing.
+    #[serde(default)]
+    pub last_seen_at: Option<DateTime<Utc>>,
 }
 
-/// Retrieve an Agenda by the url.
-pub async fn get_agenda_by_url(url: &str, db: &Database) -> Result<Option<Agenda>, ErrorKind> {
-    let agenda_collection = agenda_collection(&db);
-    let optional_agenda = agenda_collection.find_one(doc! {"url": &url}, None).await?;
-    match optional_agenda {
-        Some(agenda) => Ok(Some(agenda)),
-        None => Ok(None),
+impl Agenda {
+    /// Stable id of the agenda item, see stable_id_for_url.
+    pub fn stable_id(&self) -> String {
+        stable_id_for_url(&self.url)
     }
-}
 
-pub struct UpsertAgendaResult {
-    pub agenda: Agenda,
-    pub inserted: bool,
-}
+    /// The url the details page is fetched at.
+    pub fn details_page_url(&self) -> &str {
+        self.details_url.as_deref().unwrap_or(&self.url)
+    }
 
-pub async fn insert_or_get_agenda(
-    agenda: &Agenda,
-    db: &Database,
-) -> Result<UpsertAgendaResult, ErrorKind> {
-    let agenda_collection = agenda_collection(&db);
-    match get_agenda_by_url(&agenda.url, &db).await? {
-        Some(agenda) => Ok(UpsertAgendaResult {
-            agenda,
-            inserted: false,
-        }),
-        None => {
-            let new_agenda = Agenda {
-                _id: None,
-                venue_id: agenda.venue_id.clone(),
-                url: agenda.url.clone(),
-                description: agenda.description.clone(),
-                title: agenda.title.clone(),
-                needs_details: true,
-            };
-            let _insert_result = agenda_collection.insert_one(&new_agenda, None).await?;
-
-            Ok(UpsertAgendaResult {
-                agenda: new_agenda,
-                inserted: true,
-            })
+    /// Take over the listing fields of the freshly scraped agenda item. The details are fetched
+    /// again, the details page likely changed along with the listing.
+    pub fn apply_listing_changes(&mut self, scraped: &Agenda, now: DateTime<Utc>) {
+        self.title = scraped.title.clone();
+        self.description = scraped.description.clone();
+        if scraped.start_text.is_some() || scraped.starts_at.is_some() {
+            self.start_text = scraped.start_text.clone();
+            self.starts_at = scraped.starts_at;
+            self.ends_at = scraped.ends_at;
+            self.doors_open_at = scraped.doors_open_at;
+            self.all_day = scraped.all_day;
+        }
+        // Listings from JSON or structured data can have the details fields too.
+        if scraped.price.is_some() {
+            self.price = scraped.price.clone();
+        }
+        if scraped.ticket_url.is_some() {
+            self.ticket_url = scraped.ticket_url.clone();
+        }
+        if scraped.image_url.is_some() {
+            self.image_url = scraped.image_url.clone();
+        }
+        if !scraped.support_acts.is_empty() {
+            self.support_acts = scraped.support_acts.clone();
         }
+        self.listing_hash = scraped.listing_hash.clone();
+        self.listing_url = scraped.listing_url.clone();
+        self.details_url = scraped.details_url.clone();
+        self.needs_details = true;
+        self.updated_at = Some(now);
     }
-}
 
-pub async fn update_agenda(agenda: &Agenda, db: &Database) -> Result<(), ErrorKind> {
-    let collection = agenda_collection(&db);
-    let update_results = collection
-        .replace_one(doc! { "url": &agenda.url }, agenda, None)
-        .await?;
+    /// The venue marked the event as cancelled in the title or description.
+    pub fn is_marked_cancelled(&self) -> bool {
+        let text = format!(
+            "{} {}",
+            self.title,
+            self.description.as_deref().unwrap_or_default()
+        )
+        .to_lowercase();
+        CANCELLED_WORDS.iter().any(|word| text.contains(word))
+    }
 
-    if update_results.matched_count != 1 || update_results.modified_count != 1 {
-        Err(ErrorKind::GenericError)
-    } else {
-        Ok(())
+    /// The event took place before now, by its end, or otherwise its start.
+    pub fn took_place_before(&self, now: DateTime<Utc>) -> bool {
+        match self.ends_at.or(self.starts_at) {
+            Some(ends_at) => ends_at < now,
+            None => false,
+        }
     }
 }
 
-pub async fn create_mongo_connection(config: &Config) -> Result<Database, ErrorKind> {
-    trace!("Connecting mongodb, config {}", config);
-    let client_options = ClientOptions::parse(&config.mongo_url).await?;
-    let client = Client::with_options(client_options)?;
-    info!("Mongo db client connected with config {}", config);
-    let db = client.database(&config.mongo_db);
-    Ok(db)
+/// Stable id of the agenda item with the url, the first 128 bits of the sha256 of the url, in hex.
+pub fn stable_id_for_url(url: &str) -> String {
+    let hash = format!("{:x}", Sha256::digest(url.as_bytes()));
+    hash[..32].to_string()
 }
 
-pub async fn execute_on_agenda_items(
-    venue_id: &String,
-    db: &Database,
-) -> Result<Cursor<Agenda>, ErrorKind> {
-    let collection = agenda_collection(db);
-    let filter = doc! { "venue_id": venue_id };
-    let cursor = collection.find(filter, None).await?;
-    Ok(cursor)
+impl Display for Agenda {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Agenda")
+            .field("_id", &self._id)
+            .field("url", &self.url)
+            .field("title", &self.title)
+            .field("description", &self.description)
+            .field("starts_at", &self.starts_at)
+            .field("status", &self.status)
+            .finish()
+    }
 }
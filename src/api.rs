@@ -0,0 +1,427 @@
+use crate::agenda::{Agenda, AgendaStatus};
+use crate::health::{SelectorHealth, HEALTH_HISTORY_SIZE};
+use crate::store::AgendaStore;
+use crate::venue_definition::VenueDefinition;
+use crate::ErrorKind;
+use axum::extract::rejection::QueryRejection;
+use axum::extract::{Path, Query, State};
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use chrono::{DateTime, FixedOffset, NaiveDate, TimeZone, Utc};
+use chrono_tz::Europe::Amsterdam;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::fmt::Display;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tracing::info;
+
+/// The OpenAPI description of the api, served at `/openapi.json`.
+pub const OPENAPI_DESCRIPTION: &str = include_str!("openapi.json");
+
+const DEFAULT_PAGE_SIZE: usize = 50;
+const MAX_PAGE_SIZE: usize = 200;
+
+#[derive(Clone)]
+struct ApiState {
+    store: Arc<dyn AgendaStore>,
+    venue_definitions: Arc<Vec<VenueDefinition>>,
+}
+
+/// The read-only routes of the api over the agenda items in the store.
+pub fn router(store: Arc<dyn AgendaStore>, venue_definitions: Vec<VenueDefinition>) -> Router {
+    let state = ApiState {
+        store,
+        venue_definitions: Arc::new(venue_definitions),
+    };
+    Router::new()
+        .route("/venues", get(list_venues))
+        .route("/agenda", get(list_agenda))
+        .route("/agenda/:id", get(get_agenda))
+        .route("/health", get(list_health))
+        .route("/venues/:id/health", get(venue_health))
+        .route("/openapi.json", get(openapi_description))
+        .with_state(state)
+}
+
+/// Serve the api on the address until the process stops.
+pub async fn serve(
+    address: SocketAddr,
+    store: Arc<dyn AgendaStore>,
+    venue_definitions: Vec<VenueDefinition>,
+) -> Result<(), ErrorKind> {
+    info!("Serving the api on {}", address);
+    axum::Server::try_bind(&address)
+        .map_err(server_error)?
+        .serve(router(store, venue_definitions).into_make_service())
+        .await
+        .map_err(server_error)
+}
+
+fn server_error(error: impl Display) -> ErrorKind {
+    ErrorKind::ServerError {
+        message: error.to_string(),
+    }
+}
+
+#[derive(Serialize)]
+struct VenueResponse {
+    id: String,
+    name: String,
+    address: Option<String>,
+    agenda_url: String,
+    tags: Vec<String>,
+}
+
+#[derive(Serialize)]
+struct AgendaResponse {
+    id: String,
+    url: String,
+    venue_id: String,
+    title: String,
+    description: Option<String>,
+    long_description: Option<String>,
+    support_acts: Vec<String>,
+    starts_at: Option<DateTime<FixedOffset>>,
+    ends_at: Option<DateTime<FixedOffset>>,
+    doors_open_at: Option<DateTime<FixedOffset>>,
+    all_day: bool,
+    price: Option<String>,
+    ticket_url: Option<String>,
+    image_url: Option<String>,
+    status: AgendaStatus,
+    last_seen_at: Option<DateTime<Utc>>,
+    updated_at: Option<DateTime<Utc>>,
+}
+
+impl From<Agenda> for AgendaResponse {
+    fn from(agenda: Agenda) -> Self {
+        AgendaResponse {
+            id: agenda.stable_id(),
+            url: agenda.url,
+            venue_id: agenda.venue_id,
+            title: agenda.title,
+            description: agenda.description,
+            long_description: agenda.long_description,
+            support_acts: agenda.support_acts,
+            starts_at: agenda.starts_at,
+            ends_at: agenda.ends_at,
+            doors_open_at: agenda.doors_open_at,
+            all_day: agenda.all_day,
+            price: agenda.price,
+            ticket_url: agenda.ticket_url,
+            image_url: agenda.image_url,
+            status: agenda.status,
+            last_seen_at: agenda.last_seen_at,
+            updated_at: agenda.updated_at,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct AgendaPage {
+    items: Vec<AgendaResponse>,
+    page: usize,
+    page_size: usize,
+    total_items: usize,
+    total_pages: usize,
+}
+
+#[derive(Deserialize)]
+struct AgendaQuery {
+    venue_id: Option<String>,
+    /// Only items starting at or after, a date or an RFC 3339 date-time.
+    from: Option<String>,
+    /// Only items starting before the end of the date, or before the RFC 3339 date-time.
+    to: Option<String>,
+    /// Free text to find in the title, descriptions and support acts.
+    q: Option<String>,
+    /// Only items with the status, active items by default.
+    status: Option<AgendaStatus>,
+    page: Option<usize>,
+    page_size: Option<usize>,
+}
+
+/// The last selector health of a venue, none before its first full sync.
+#[derive(Serialize)]
+struct VenueHealthResponse {
+    venue_id: String,
+    layout_change_suspected: bool,
+    selector_health: Option<SelectorHealth>,
+}
+
+#[derive(Deserialize)]
+struct HealthQuery {
+    limit: Option<usize>,
+}
+
+/// An error response, the message as json with the status code.
+struct ApiError {
+    status: StatusCode,
+    message: String,
+}
+
+impl ApiError {
+    fn bad_request(message: String) -> ApiError {
+        ApiError {
+            status: StatusCode::BAD_REQUEST,
+            message,
+        }
+    }
+}
+
+impl From<ErrorKind> for ApiError {
+    fn from(error: ErrorKind) -> Self {
+        let status = match error {
+            ErrorKind::UnknownVenue { .. } | ErrorKind::AgendaNotFound { .. } => {
+                StatusCode::NOT_FOUND
+            }
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        ApiError {
+            status,
+            message: error.to_string(),
+        }
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        ApiError::bad_request(rejection.body_text())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(json!({ "error": self.message }))).into_response()
+    }
+}
+
+async fn list_venues(State(state): State<ApiState>) -> Json<Vec<VenueResponse>> {
+    Json(
+        state
+            .venue_definitions
+            .iter()
+            .map(|venue_definition| VenueResponse {
+                id: venue_definition.id.clone(),
+                name: venue_definition.name.clone(),
+                address: venue_definition.address.clone(),
+                agenda_url: venue_definition.agenda_url.clone(),
+                tags: venue_definition.tags.clone(),
+            })
+            .collect(),
+    )
+}
+
+async fn list_agenda(
+    State(state): State<ApiState>,
+    query: Result<Query<AgendaQuery>, QueryRejection>,
+) -> Result<Json<AgendaPage>, ApiError> {
+    let Query(query) = query?;
+    let from = query
+        .from
+        .as_deref()
+        .map(|from| parse_bound(from, false))
+        .transpose()?;
+    let to = query
+        .to
+        .as_deref()
+        .map(|to| parse_bound(to, true))
+        .transpose()?;
+    let search_text = query.q.map(|q| q.to_lowercase());
+    let page = query.page.unwrap_or(1).max(1);
+    let page_size = query
+        .page_size
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+
+    let status = query.status.unwrap_or_default();
+    let mut agenda_items: Vec<Agenda> = all_agenda_items(&state, query.venue_id.as_deref(), status)
+        .await?
+        .into_iter()
+        .filter(|agenda| match (from, agenda.starts_at) {
+            (Some(from), Some(starts_at)) => starts_at >= from,
+            (Some(_), None) => false,
+            (None, _) => true,
+        })
+        .filter(|agenda| match (to, agenda.starts_at) {
+            (Some(to), Some(starts_at)) => starts_at < to,
+            (Some(_), None) => false,
+            (None, _) => true,
+        })
+        .filter(|agenda| match &search_text {
+            Some(search_text) => matches_text(agenda, search_text),
+            None => true,
+        })
+        .collect();
+    // Items without a start come last.
+    agenda_items.sort_by(|agenda, other| {
+        (agenda.starts_at.is_none(), agenda.starts_at, &agenda.url).cmp(&(
+            other.starts_at.is_none(),
+            other.starts_at,
+            &other.url,
+        ))
+    });
+
+    let total_items = agenda_items.len();
+    let items = agenda_items
+        .into_iter()
+        .skip((page - 1) * page_size)
+        .take(page_size)
+        .map(AgendaResponse::from)
+        .collect();
+    Ok(Json(AgendaPage {
+        items,
+        page,
+        page_size,
+        total_items,
+        total_pages: total_items.div_ceil(page_size),
+    }))
+}
+
+async fn get_agenda(
+    State(state): State<ApiState>,
+    Path(id): Path<String>,
+) -> Result<Json<AgendaResponse>, ApiError> {
+    state
+        .store
+        .get_agenda_by_stable_id(&id)
+        .await?
+        .filter(|agenda| {
+            state
+                .venue_definitions
+                .iter()
+                .any(|venue_definition| venue_definition.id == agenda.venue_id)
+        })
+        .map(|agenda| Json(AgendaResponse::from(agenda)))
+        .ok_or(ApiError {
+            status: StatusCode::NOT_FOUND,
+            message: format!("No agenda item with id '{}'", id),
+        })
+}
+
+async fn list_health(
+    State(state): State<ApiState>,
+) -> Result<Json<Vec<VenueHealthResponse>>, ApiError> {
+    let mut venue_health = Vec::new();
+    for venue_definition in state.venue_definitions.iter() {
+        let selector_health = state
+            .store
+            .selector_health_for_venue(&venue_definition.id, 1)
+            .await?
+            .pop();
+        venue_health.push(VenueHealthResponse {
+            venue_id: venue_definition.id.clone(),
+            layout_change_suspected: selector_health
+                .as_ref()
+                .is_some_and(|health| health.layout_change_suspected.is_some()),
+            selector_health,
+        });
+    }
+    Ok(Json(venue_health))
+}
+
+async fn venue_health(
+    State(state): State<ApiState>,
+    Path(venue_id): Path<String>,
+    query: Result<Query<HealthQuery>, QueryRejection>,
+) -> Result<Json<Vec<SelectorHealth>>, ApiError> {
+    let Query(query) = query?;
+    if !state
+        .venue_definitions
+        .iter()
+        .any(|venue_definition| venue_definition.id == venue_id)
+    {
+        return Err(ErrorKind::UnknownVenue { venue_id }.into());
+    }
+    let limit = query
+        .limit
+        .unwrap_or(HEALTH_HISTORY_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    Ok(Json(
+        state
+            .store
+            .selector_health_for_venue(&venue_id, limit)
+            .await?,
+    ))
+}
+
+async fn openapi_description() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "application/json")],
+        OPENAPI_DESCRIPTION,
+    )
+}
+
+/// The agenda items with the status of the venue, or of all venues.
+async fn all_agenda_items(
+    state: &ApiState,
+    venue_id: Option<&str>,
+    status: AgendaStatus,
+) -> Result<Vec<Agenda>, ErrorKind> {
+    let venue_ids: Vec<&str> = match venue_id {
+        Some(venue_id) => {
+            if !state
+                .venue_definitions
+                .iter()
+                .any(|venue_definition| venue_definition.id == venue_id)
+            {
+                return Err(ErrorKind::UnknownVenue {
+                    venue_id: venue_id.to_string(),
+                });
+            }
+            vec![venue_id]
+        }
+        None => state
+            .venue_definitions
+            .iter()
+            .map(|venue_definition| venue_definition.id.as_str())
+            .collect(),
+    };
+
+    let mut agenda_items = Vec::new();
+    for venue_id in venue_ids {
+        agenda_items.extend(
+            state
+                .store
+                .agenda_items_for_venue_with_status(venue_id, status)
+                .await?,
+        );
+    }
+    Ok(agenda_items)
+}
+
+fn matches_text(agenda: &Agenda, search_text: &str) -> bool {
+    [&agenda.description, &agenda.long_description]
+        .into_iter()
+        .flatten()
+        .chain(std::iter::once(&agenda.title))
+        .chain(agenda.support_acts.iter())
+        .any(|text| text.to_lowercase().contains(search_text))
+}
+
+/// A date-time, or a date at the start of the day in Amsterdam. The end bound of a date is the start
+/// of the next day, so the whole day is included.
+fn parse_bound(text: &str, end: bool) -> Result<DateTime<FixedOffset>, ApiError> {
+    if let Ok(date_time) = DateTime::parse_from_rfc3339(text) {
+        return Ok(date_time);
+    }
+    let date = NaiveDate::parse_from_str(text, "%Y-%m-%d").map_err(|_| {
+        ApiError::bad_request(format!(
+            "'{}' is not a date (YYYY-MM-DD) or an RFC 3339 date-time",
+            text
+        ))
+    })?;
+    let date = if end {
+        date.succ_opt().unwrap_or(date)
+    } else {
+        date
+    };
+    Amsterdam
+        .from_local_datetime(&date.and_hms_opt(0, 0, 0).unwrap())
+        .earliest()
+        .map(|date_time| date_time.fixed_offset())
+        .ok_or_else(|| ApiError::bad_request(format!("'{}' is not a valid date", text)))
+}
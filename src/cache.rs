@@ -0,0 +1,201 @@
+use crate::http_sender::HttpSender;
+use crate::ErrorKind;
+use async_trait::async_trait;
+use reqwest::header::{
+    HeaderMap, HeaderValue, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH, LAST_MODIFIED,
+};
+use reqwest::{Method, RequestBuilder, Response, ResponseBuilderExt, StatusCode};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+use tokio::fs;
+use tracing::{trace, warn};
+use url::Url;
+
+/// Marks a response served from the cache after a `304 Not Modified`, stored in the extensions of
+/// the response with the size of the cached body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CacheHit {
+    pub body_size: u64,
+}
+
+/// The cached body size when the response came from the cache.
+pub fn cache_hit_of(response: &Response) -> Option<CacheHit> {
+    response.extensions().get::<CacheHit>().copied()
+}
+
+/// The validators of a cached response, the body is stored next to it.
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    url: String,
+    etag: Option<String>,
+    last_modified: Option<String>,
+    content_type: Option<String>,
+}
+
+/// Sender caching the bodies of GET responses with an `ETag` or `Last-Modified` in a directory.
+/// Requests for cached urls are sent with `If-None-Match` and `If-Modified-Since`, and a `304 Not
+/// Modified` is answered with the cached body, marked with CacheHit.
+pub struct CachingHttpSender<S> {
+    sender: S,
+    cache_dir: PathBuf,
+    /// Use the cached responses without storing new ones.
+    read_only: bool,
+}
+
+impl<S: HttpSender> CachingHttpSender<S> {
+    pub fn new(sender: S, cache_dir: PathBuf) -> CachingHttpSender<S> {
+        CachingHttpSender {
+            sender,
+            cache_dir,
+            read_only: false,
+        }
+    }
+
+    /// A read only cache leaves the cache as it is, for runs that store nothing, like dry runs. A
+    /// later real run then still gets the changed pages it has not stored yet.
+    pub fn with_read_only(mut self, read_only: bool) -> CachingHttpSender<S> {
+        self.read_only = read_only;
+        self
+    }
+
+    /// The paths of the entry and the body of the url.
+    fn paths_for(&self, url: &Url) -> (PathBuf, PathBuf) {
+        let key = format!("{:x}", Sha256::digest(url.as_str().as_bytes()));
+        (
+            self.cache_dir.join(format!("{}.json", key)),
+            self.cache_dir.join(format!("{}.body", key)),
+        )
+    }
+
+    async fn read_entry(&self, url: &Url) -> Option<CacheEntry> {
+        let (entry_path, _) = self.paths_for(url);
+        let contents = fs::read(&entry_path).await.ok()?;
+        serde_json::from_slice::<CacheEntry>(&contents)
+            .ok()
+            .filter(|entry| entry.url == url.as_str())
+    }
+
+    async fn write_entry(
+        &self,
+        url: &Url,
+        entry: &CacheEntry,
+        body: &[u8],
+    ) -> Result<(), ErrorKind> {
+        let (entry_path, body_path) = self.paths_for(url);
+        fs::create_dir_all(&self.cache_dir).await?;
+        fs::write(&body_path, body).await?;
+        fs::write(&entry_path, serde_json::to_vec(entry)?).await?;
+        Ok(())
+    }
+
+    /// Answer a 304 with the cached body.
+    async fn cached_response(
+        &self,
+        url: &Url,
+        entry: &CacheEntry,
+        mut response: Response,
+    ) -> Result<Response, ErrorKind> {
+        let (_, body_path) = self.paths_for(url);
+        let body = fs::read(&body_path).await?;
+        let mut headers = response.headers().clone();
+        if let Some(content_type) = entry
+            .content_type
+            .as_deref()
+            .and_then(|value| HeaderValue::from_str(value).ok())
+        {
+            headers.insert(CONTENT_TYPE, content_type);
+        }
+        let mut extensions = std::mem::take(response.extensions_mut());
+        extensions.insert(CacheHit {
+            body_size: body.len() as u64,
+        });
+        Ok(response_from_parts(
+            response.url().clone(),
+            StatusCode::OK,
+            headers,
+            extensions,
+            body,
+        ))
+    }
+}
+
+fn header_text(headers: &HeaderMap, name: reqwest::header::HeaderName) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| value.to_string())
+}
+
+fn response_from_parts(
+    url: Url,
+    status: StatusCode,
+    headers: HeaderMap,
+    extensions: http::Extensions,
+    body: Vec<u8>,
+) -> Response {
+    let mut builder = http::response::Builder::new().status(status).url(url);
+    *builder.headers_mut().unwrap() = headers;
+    let mut http_response = builder.body(body).unwrap();
+    *http_response.extensions_mut() = extensions;
+    http_response.into()
+}
+
+#[async_trait]
+impl<S: HttpSender> HttpSender for CachingHttpSender<S> {
+    async fn send(&self, request: RequestBuilder) -> Result<Response, ErrorKind> {
+        let url = match request.try_clone().and_then(|request| request.build().ok()) {
+            Some(built_request) if built_request.method() == Method::GET => {
+                built_request.url().clone()
+            }
+            _ => return self.sender.send(request).await,
+        };
+
+        let entry = self.read_entry(&url).await;
+        let mut request = request;
+        if let Some(entry) = &entry {
+            if let Some(etag) = &entry.etag {
+                request = request.header(IF_NONE_MATCH, etag);
+            }
+            if let Some(last_modified) = &entry.last_modified {
+                request = request.header(IF_MODIFIED_SINCE, last_modified);
+            }
+        }
+
+        let response = self.sender.send(request).await?;
+        match (response.status(), &entry) {
+            (StatusCode::NOT_MODIFIED, Some(entry)) => {
+                trace!("Cache hit for {}", url);
+                self.cached_response(&url, entry, response).await
+            }
+            (StatusCode::OK, _) => {
+                let headers = response.headers().clone();
+                let entry = CacheEntry {
+                    url: url.to_string(),
+                    etag: header_text(&headers, ETAG),
+                    last_modified: header_text(&headers, LAST_MODIFIED),
+                    content_type: header_text(&headers, CONTENT_TYPE),
+                };
+                if self.read_only || (entry.etag.is_none() && entry.last_modified.is_none()) {
+                    return Ok(response);
+                }
+                // The body is read to store it, the response gets it back.
+                let mut response = response;
+                let extensions = std::mem::take(response.extensions_mut());
+                let response_url = response.url().clone();
+                let body = response.bytes().await?.to_vec();
+                if let Err(err) = self.write_entry(&url, &entry, &body).await {
+                    warn!("Cannot cache {}: {}", url, err);
+                }
+                Ok(response_from_parts(
+                    response_url,
+                    StatusCode::OK,
+                    headers,
+                    extensions,
+                    body,
+                ))
+            }
+            _ => Ok(response),
+        }
+    }
+}
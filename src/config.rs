@@ -1,5 +1,10 @@
+use crate::politeness::PolitenessPolicy;
+use crate::registry::DEFAULT_MAX_CONCURRENT_VENUES;
+use crate::retry::RetryPolicy;
 use std::env;
 use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::time::Duration;
 
 #[derive(Clone)]
 pub struct Config {
@@ -8,6 +13,20 @@ pub struct Config {
     pub masked_mongo_url: String,
     pub environment: String,
     pub application_name: String,
+    pub venues_config: String,
+    /// The agenda store to use: mongo, sqlite or memory.
+    pub store_type: String,
+    pub sqlite_path: String,
+    pub retry_policy: RetryPolicy,
+    /// The user agent of the requests.
+    pub user_agent: String,
+    pub politeness_policy: PolitenessPolicy,
+    /// Directory of the http response cache, no cache when empty.
+    pub http_cache_dir: Option<String>,
+    /// The number of venues synced at the same time.
+    pub max_concurrent_venues: usize,
+    /// Webhook the suspected layout changes of venues are posted to, only logged when empty.
+    pub layout_change_webhook_url: Option<String>,
 }
 
 fn os_var_as_string(var: &str) -> String {
@@ -22,23 +41,89 @@ fn os_var_as_string(var: &str) -> String {
         .unwrap()
 }
 
+fn os_var_as_string_or(var: &str, default_value: &str) -> String {
+    env::var_os(var)
+        .map(|value| value.into_string().unwrap())
+        .unwrap_or_else(|| default_value.to_string())
+}
+
+fn os_var_parsed_or<T: FromStr>(var: &str, default_value: T) -> T {
+    env::var(var)
+        .ok()
+        .map(|value| {
+            value
+                .parse()
+                .unwrap_or_else(|_| panic!("Environment {} cannot be parsed: {}", var, value))
+        })
+        .unwrap_or(default_value)
+}
+
 impl Config {
     pub fn from_environment() -> Self {
-        let mongo_db = os_var_as_string("MONGO_DB");
-        let mongo_host = os_var_as_string("MONGO_HOST");
-        let mongo_port = os_var_as_string("MONGO_PORT");
-        let mongo_user = os_var_as_string("MONGO_USER");
-        let mongo_pass = os_var_as_string("MONGO_PASS");
-
         let environment = os_var_as_string("ENVIRONMENT");
-        let mongo_url = format!(
-            "mongodb://{}:{}@{}:{}/{}",
-            mongo_user, mongo_pass, mongo_host, mongo_port, mongo_db
-        );
-        let masked_mongo_url = format!(
-            "mongodb://{}:******@{}:{}/{}",
-            mongo_user, mongo_host, mongo_port, mongo_db
-        );
+        let venues_config = os_var_as_string_or("VENUES_CONFIG", "venues.toml");
+        let store_type = os_var_as_string_or("STORE_TYPE", "mongo");
+        let sqlite_path = os_var_as_string_or("SQLITE_PATH", "venue-scraper.sqlite");
+        let default_retry_policy = RetryPolicy::default();
+        let retry_policy = RetryPolicy {
+            max_attempts: os_var_parsed_or("HTTP_MAX_ATTEMPTS", default_retry_policy.max_attempts),
+            initial_backoff: Duration::from_millis(os_var_parsed_or(
+                "HTTP_INITIAL_BACKOFF_MS",
+                default_retry_policy.initial_backoff.as_millis() as u64,
+            )),
+            max_backoff: Duration::from_millis(os_var_parsed_or(
+                "HTTP_MAX_BACKOFF_MS",
+                default_retry_policy.max_backoff.as_millis() as u64,
+            )),
+            ..default_retry_policy
+        };
+        let http_cache_dir = Some(os_var_as_string_or("HTTP_CACHE_DIR", "http-cache"))
+            .filter(|http_cache_dir| !http_cache_dir.is_empty());
+        let max_concurrent_venues =
+            os_var_parsed_or("MAX_CONCURRENT_VENUES", DEFAULT_MAX_CONCURRENT_VENUES);
+        let layout_change_webhook_url = Some(os_var_as_string_or("LAYOUT_CHANGE_WEBHOOK_URL", ""))
+            .filter(|layout_change_webhook_url| !layout_change_webhook_url.is_empty());
+        let user_agent = os_var_as_string_or("HTTP_USER_AGENT", "venue-scraper");
+        let default_politeness_policy = PolitenessPolicy::default();
+        let politeness_policy = PolitenessPolicy {
+            requests_per_second: os_var_parsed_or(
+                "HTTP_REQUESTS_PER_SECOND",
+                default_politeness_policy.requests_per_second,
+            ),
+            min_delay: Duration::from_millis(os_var_parsed_or(
+                "HTTP_MIN_DELAY_MS",
+                default_politeness_policy.min_delay.as_millis() as u64,
+            )),
+            max_concurrent_per_host: os_var_parsed_or(
+                "HTTP_MAX_CONCURRENT_PER_HOST",
+                default_politeness_policy.max_concurrent_per_host,
+            ),
+            user_agent: user_agent.clone(),
+            obey_robots_txt: os_var_parsed_or(
+                "HTTP_OBEY_ROBOTS_TXT",
+                default_politeness_policy.obey_robots_txt,
+            ),
+        };
+
+        // The mongo settings are only required when mongo is the store.
+        let (mongo_db, mongo_url, masked_mongo_url) = if store_type == "mongo" {
+            let mongo_db = os_var_as_string("MONGO_DB");
+            let mongo_host = os_var_as_string("MONGO_HOST");
+            let mongo_port = os_var_as_string("MONGO_PORT");
+            let mongo_user = os_var_as_string("MONGO_USER");
+            let mongo_pass = os_var_as_string("MONGO_PASS");
+            let mongo_url = format!(
+                "mongodb://{}:{}@{}:{}/{}",
+                mongo_user, mongo_pass, mongo_host, mongo_port, mongo_db
+            );
+            let masked_mongo_url = format!(
+                "mongodb://{}:******@{}:{}/{}",
+                mongo_user, mongo_host, mongo_port, mongo_db
+            );
+            (mongo_db, mongo_url, masked_mongo_url)
+        } else {
+            (String::new(), String::new(), String::new())
+        };
 
         Config {
             environment,
@@ -46,6 +131,15 @@ impl Config {
             masked_mongo_url,
             mongo_db,
             application_name: "venue-scraper".to_string(),
+            venues_config,
+            store_type,
+            sqlite_path,
+            retry_policy,
+            user_agent,
+            politeness_policy,
+            http_cache_dir,
+            max_concurrent_venues,
+            layout_change_webhook_url,
         }
     }
 }
@@ -58,6 +152,15 @@ impl Display for Config {
             .field("environment", &self.environment)
             .field("mongo_url", &self.masked_mongo_url)
             .field("application_name", &self.application_name)
+            .field("venues_config", &self.venues_config)
+            .field("store_type", &self.store_type)
+            .field("sqlite_path", &self.sqlite_path)
+            .field("retry_policy", &self.retry_policy)
+            .field("user_agent", &self.user_agent)
+            .field("politeness_policy", &self.politeness_policy)
+            .field("http_cache_dir", &self.http_cache_dir)
+            .field("max_concurrent_venues", &self.max_concurrent_venues)
+            .field("layout_change_webhook_url", &self.layout_change_webhook_url)
             .finish()
     }
 }
@@ -0,0 +1,395 @@
+use crate::agenda::Agenda;
+use crate::ErrorKind;
+use chrono::offset::LocalResult;
+use chrono::{
+    DateTime, Datelike, Duration, FixedOffset, NaiveDate, NaiveDateTime, NaiveTime, Offset,
+    TimeZone, Utc,
+};
+use chrono_tz::Europe::Amsterdam;
+use chrono_tz::Tz;
+use regex::{Captures, Regex};
+use std::sync::OnceLock;
+
+/// Month names and abbreviations, Dutch and English.
+const MONTHS: [(&str, u32); 34] = [
+    ("januari", 1),
+    ("january", 1),
+    ("jan", 1),
+    ("februari", 2),
+    ("february", 2),
+    ("feb", 2),
+    ("maart", 3),
+    ("march", 3),
+    ("mrt", 3),
+    ("mar", 3),
+    ("april", 4),
+    ("apr", 4),
+    ("mei", 5),
+    ("may", 5),
+    ("juni", 6),
+    ("june", 6),
+    ("jun", 6),
+    ("juli", 7),
+    ("july", 7),
+    ("jul", 7),
+    ("augustus", 8),
+    ("august", 8),
+    ("aug", 8),
+    ("september", 9),
+    ("sept", 9),
+    ("sep", 9),
+    ("oktober", 10),
+    ("october", 10),
+    ("okt", 10),
+    ("oct", 10),
+    ("november", 11),
+    ("nov", 11),
+    ("december", 12),
+    ("dec", 12),
+];
+
+/// Separators between the parts of a date text, "deuren 19:30 / aanvang 20:30".
+const SEGMENT_SEPARATORS: [char; 7] = ['/', '|', '·', '•', '\n', ';', ','];
+
+const DOORS_KEYWORDS: [&str; 5] = ["deuren", "deur open", "zaal open", "doors", "inloop"];
+const START_KEYWORDS: [&str; 3] = ["aanvang", "start", "begin"];
+const END_KEYWORDS: [&str; 2] = ["einde", "eind"];
+
+/// The dates of an event, in the Europe/Amsterdam timezone.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EventDates {
+    pub starts_at: DateTime<Tz>,
+    /// The end of the event. For multi-day events without an end time, the start of the last day.
+    pub ends_at: Option<DateTime<Tz>>,
+    pub doors_open_at: Option<DateTime<Tz>>,
+    /// No time was found, the event takes the whole day(s).
+    pub all_day: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum TimeLabel {
+    Doors,
+    Start,
+    End,
+    Unlabelled,
+}
+
+/// A date as found in the text, the year may be missing.
+struct PartialDate {
+    day: u32,
+    month: u32,
+    year: Option<i32>,
+}
+
+fn month_pattern() -> String {
+    let mut months: Vec<&str> = MONTHS.iter().map(|(name, _)| *name).collect();
+    months.sort_by_key(|name| std::cmp::Reverse(name.len()));
+    months.join("|")
+}
+
+fn month_number(name: &str) -> Option<u32> {
+    MONTHS
+        .iter()
+        .find(|(month_name, _)| *month_name == name)
+        .map(|(_, number)| *number)
+}
+
+fn month_name(number: u32) -> &'static str {
+    MONTHS
+        .iter()
+        .find(|(_, month_number)| *month_number == number)
+        .map(|(name, _)| *name)
+        .unwrap_or("jan")
+}
+
+/// `12 okt`, `12 okt 2024`, or a range within one month `12 tm 14 okt 2024` or
+/// `vr 12 tm zo 14 okt`.
+fn textual_date_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| {
+        Regex::new(&format!(
+            r"\b(\d{{1,2}})(?:\s*(?:-|–|tm)\s*(?:[a-z]+\.?\s+)?(\d{{1,2}}))?\.?\s+({})\b\.?(?:\s+(\d{{4}}))?",
+            month_pattern()
+        ))
+        .unwrap()
+    })
+}
+
+/// `12-10-2024`, `12/10/2024` or `12.10.2024`.
+fn numeric_date_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| Regex::new(r"\b(\d{1,2})[-/.](\d{1,2})[-/.](\d{4})\b").unwrap())
+}
+
+/// `2024-10-12` optionally followed by a time, `2024-10-12t20:00`.
+fn iso_date_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| {
+        Regex::new(r"\b(\d{4})-(\d{2})-(\d{2})(?:[t ](\d{2}:\d{2})(?::\d{2})?)?\S*").unwrap()
+    })
+}
+
+/// `20:00`, `20.00` or `20:00 uur`.
+fn time_regex() -> &'static Regex {
+    static REGEX: OnceLock<Regex> = OnceLock::new();
+    REGEX.get_or_init(|| Regex::new(r"\b(\d{1,2})[:.](\d{2})\b").unwrap())
+}
+
+/// Lowercase the text and rewrite numeric and iso dates to textual dates, so only a single date
+/// notation remains.
+fn normalize(text: &str) -> String {
+    let text = text
+        .to_lowercase()
+        .replace("t/m", " tm ")
+        .replace("tot en met", " tm ");
+    let text = numeric_date_regex().replace_all(&text, |captures: &Captures| {
+        let month: u32 = captures[2].parse().unwrap_or(0);
+        if (1..=12).contains(&month) {
+            format!(" {} {} {} ", &captures[1], month_name(month), &captures[3])
+        } else {
+            captures[0].to_string()
+        }
+    });
+    let text = iso_date_regex().replace_all(&text, |captures: &Captures| {
+        let month: u32 = captures[2].parse().unwrap_or(0);
+        if !(1..=12).contains(&month) {
+            return captures[0].to_string();
+        }
+        let time = captures.get(4).map(|time| time.as_str()).unwrap_or("");
+        format!(
+            " {} {} {} / {} ",
+            &captures[3],
+            month_name(month),
+            &captures[1],
+            time
+        )
+    });
+    text.into_owned()
+}
+
+fn partial_dates(text: &str) -> Vec<PartialDate> {
+    let mut partial_dates = Vec::new();
+    for captures in textual_date_regex().captures_iter(text) {
+        let month = match month_number(&captures[3]) {
+            Some(month) => month,
+            None => continue,
+        };
+        let year = captures.get(4).and_then(|year| year.as_str().parse().ok());
+        let day = captures[1].parse().unwrap_or(0);
+        partial_dates.push(PartialDate { day, month, year });
+        if let Some(last_day) = captures.get(2) {
+            let day = last_day.as_str().parse().unwrap_or(0);
+            partial_dates.push(PartialDate { day, month, year });
+        }
+    }
+    partial_dates
+}
+
+/// The first date on or after the reference date with the day and month.
+fn next_occurrence(day: u32, month: u32, reference: NaiveDate) -> Option<NaiveDate> {
+    (reference.year()..reference.year() + 5)
+        .filter_map(|year| NaiveDate::from_ymd_opt(year, month, day))
+        .find(|date| *date >= reference)
+}
+
+/// Resolve the partial dates. A date without a year takes the year of the next date in the text
+/// ("12 okt - 14 okt 2024"), otherwise it is the next occurrence after the reference date.
+fn resolve_dates(partial_dates: &[PartialDate], reference: NaiveDate) -> Option<Vec<NaiveDate>> {
+    let mut dates = Vec::new();
+    let mut next_date: Option<NaiveDate> = None;
+    for partial_date in partial_dates.iter().rev() {
+        let date = match (partial_date.year, next_date) {
+            (Some(year), _) => NaiveDate::from_ymd_opt(year, partial_date.month, partial_date.day),
+            (None, Some(next_date)) => {
+                let year = next_date.year();
+                NaiveDate::from_ymd_opt(year, partial_date.month, partial_date.day)
+                    .filter(|date| *date <= next_date)
+                    .or_else(|| {
+                        NaiveDate::from_ymd_opt(year - 1, partial_date.month, partial_date.day)
+                    })
+            }
+            (None, None) => next_occurrence(partial_date.day, partial_date.month, reference),
+        }?;
+        dates.push(date);
+        next_date = Some(date);
+    }
+    dates.reverse();
+    Some(dates)
+}
+
+fn label_for_segment(segment: &str) -> TimeLabel {
+    if DOORS_KEYWORDS
+        .iter()
+        .any(|keyword| segment.contains(keyword))
+    {
+        TimeLabel::Doors
+    } else if START_KEYWORDS
+        .iter()
+        .any(|keyword| segment.contains(keyword))
+    {
+        TimeLabel::Start
+    } else if END_KEYWORDS.iter().any(|keyword| segment.contains(keyword)) {
+        TimeLabel::End
+    } else {
+        TimeLabel::Unlabelled
+    }
+}
+
+/// All times in the text with their label. The text is split in segments, like
+/// "deuren 19:30 / aanvang 20:30", a keyword labels the times in its segment. An unlabelled segment
+/// with two times, "20:00 - 23:00", holds a start and an end time.
+fn labelled_times(text: &str) -> Vec<(TimeLabel, NaiveTime)> {
+    let mut labelled_times = Vec::new();
+    for segment in text.split(SEGMENT_SEPARATORS) {
+        let times: Vec<NaiveTime> = time_regex()
+            .captures_iter(segment)
+            .filter_map(|captures| {
+                let hour = captures[1].parse().ok()?;
+                let minute = captures[2].parse().ok()?;
+                NaiveTime::from_hms_opt(hour, minute, 0)
+            })
+            .collect();
+        let label = label_for_segment(segment);
+        for (index, time) in times.iter().enumerate() {
+            if label == TimeLabel::Unlabelled && index == 1 {
+                labelled_times.push((TimeLabel::End, *time));
+            } else {
+                labelled_times.push((label, *time));
+            }
+        }
+    }
+    labelled_times
+}
+
+fn first_time_with_label(
+    labelled_times: &[(TimeLabel, NaiveTime)],
+    label: TimeLabel,
+) -> Option<NaiveTime> {
+    labelled_times
+        .iter()
+        .find(|(time_label, _)| *time_label == label)
+        .map(|(_, time)| *time)
+}
+
+/// The datetime in Amsterdam for the local date and time. Times in the gap of the switch to
+/// summer time are moved an hour forward, ambiguous times take the earliest.
+fn amsterdam_datetime(date: NaiveDate, time: NaiveTime) -> DateTime<Tz> {
+    match Amsterdam.from_local_datetime(&date.and_time(time)) {
+        LocalResult::Single(datetime) => datetime,
+        LocalResult::Ambiguous(earliest, _) => earliest,
+        LocalResult::None => amsterdam_datetime(date, time + Duration::hours(1)),
+    }
+}
+
+/// Parse an ISO 8601 date or date-time, as used by schema.org, like "2024-10-12",
+/// "2024-10-12T20:30" or "2024-10-12T20:30:00+02:00". Date-times without an offset are in
+/// Amsterdam.
+///
+/// # Returns:
+/// The date-time and whether it is a date without a time, None if the text is not ISO 8601.
+pub fn parse_iso_date_time(text: &str) -> Option<(DateTime<FixedOffset>, bool)> {
+    let text = text.trim();
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(text) {
+        return Some((datetime, false));
+    }
+    if let Ok(datetime) = DateTime::parse_from_str(text, "%Y-%m-%dT%H:%M%:z") {
+        return Some((datetime, false));
+    }
+    for format in [
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%d %H:%M",
+    ] {
+        if let Ok(datetime) = NaiveDateTime::parse_from_str(text, format) {
+            return Some((
+                fixed_offset(amsterdam_datetime(datetime.date(), datetime.time())),
+                false,
+            ));
+        }
+    }
+    let date = NaiveDate::parse_from_str(text, "%Y-%m-%d").ok()?;
+    Some((fixed_offset(amsterdam_datetime(date, NaiveTime::MIN)), true))
+}
+
+/// Parse the dates of an event from a venue text, like "za 12 okt 2024",
+/// "vrijdag 3 november · 20:00" or "Deuren 19:30 / Aanvang 20:30".
+///
+/// # Args:
+/// - text: The text holding the date and times of the event.
+/// - reference: Dates without a year are the next occurrence after the reference.
+///
+/// # Returns:
+/// The dates of the event or UnparseableDate if no date is found.
+pub fn parse_event_dates(text: &str, reference: DateTime<Utc>) -> Result<EventDates, ErrorKind> {
+    let unparseable = || ErrorKind::UnparseableDate {
+        text: text.to_string(),
+    };
+    if let Ok(datetime) = DateTime::parse_from_rfc3339(text.trim()) {
+        return Ok(EventDates {
+            starts_at: datetime.with_timezone(&Amsterdam),
+            ends_at: None,
+            doors_open_at: None,
+            all_day: false,
+        });
+    }
+
+    let text = normalize(text);
+    let reference_date = reference.with_timezone(&Amsterdam).naive_local().date();
+    let dates = resolve_dates(&partial_dates(&text), reference_date).ok_or_else(unparseable)?;
+    let start_date = *dates.first().ok_or_else(unparseable)?;
+    let end_date = dates.last().filter(|date| **date > start_date).copied();
+
+    let labelled_times = labelled_times(&text);
+    let doors_time = first_time_with_label(&labelled_times, TimeLabel::Doors);
+    let end_time = first_time_with_label(&labelled_times, TimeLabel::End);
+    let start_time = first_time_with_label(&labelled_times, TimeLabel::Start)
+        .or_else(|| first_time_with_label(&labelled_times, TimeLabel::Unlabelled))
+        .or(doors_time);
+
+    let all_day = start_time.is_none();
+    let starts_at = amsterdam_datetime(start_date, start_time.unwrap_or(NaiveTime::MIN));
+    let ends_at = match (end_date, end_time) {
+        (Some(end_date), Some(end_time)) => Some(amsterdam_datetime(end_date, end_time)),
+        (None, Some(end_time)) if Some(end_time) < start_time => {
+            Some(amsterdam_datetime(start_date + Duration::days(1), end_time))
+        }
+        (None, Some(end_time)) => Some(amsterdam_datetime(start_date, end_time)),
+        (Some(end_date), None) => Some(amsterdam_datetime(
+            end_date,
+            start_time.unwrap_or(NaiveTime::MIN),
+        )),
+        (None, None) => None,
+    };
+    let doors_open_at = doors_time.map(|doors_time| amsterdam_datetime(start_date, doors_time));
+
+    Ok(EventDates {
+        starts_at,
+        ends_at,
+        doors_open_at,
+        all_day,
+    })
+}
+
+fn fixed_offset(datetime: DateTime<Tz>) -> DateTime<FixedOffset> {
+    datetime.with_timezone(&datetime.offset().fix())
+}
+
+/// Fill the dates of the agenda item from its start and doors open texts. Items without date texts
+/// are left untouched.
+pub fn apply_event_dates(agenda: &mut Agenda, reference: DateTime<Utc>) -> Result<(), ErrorKind> {
+    let text = match (&agenda.start_text, &agenda.doors_open_text) {
+        (Some(start_text), Some(doors_open_text)) => {
+            format!("{} / {}", start_text, doors_open_text)
+        }
+        (Some(start_text), None) => start_text.clone(),
+        (None, Some(doors_open_text)) => doors_open_text.clone(),
+        (None, None) => return Ok(()),
+    };
+    let event_dates = parse_event_dates(&text, reference)?;
+    agenda.starts_at = Some(fixed_offset(event_dates.starts_at));
+    agenda.ends_at = event_dates.ends_at.map(fixed_offset);
+    agenda.doors_open_at = event_dates.doors_open_at.map(fixed_offset);
+    agenda.all_day = event_dates.all_day;
+    Ok(())
+}
@@ -29,25 +29,76 @@ pub enum ErrorKind {
     MongoDbError {
         mongodb_error: mongodb::error::Error,
     },
+
+    VenueConfigError {
+        message: String,
+    },
+    InvalidVenueDefinition {
+        venue_id: String,
+        message: String,
+    },
+    UnknownVenue {
+        venue_id: String,
+    },
+
+    UnparseableDate {
+        text: String,
+    },
+
+    SqliteError {
+        message: String,
+    },
+    JsonError {
+        message: String,
+    },
+    JsonPathError {
+        path: String,
+        message: String,
+    },
+    AgendaNotFound {
+        url: String,
+    },
+    UnknownStoreType {
+        store_type: String,
+    },
+    IoError {
+        message: String,
+    },
+    ServerError {
+        message: String,
+    },
+    /// The connection to the server failed or timed out, before there was a response.
+    ConnectionError {
+        url: String,
+        message: String,
+    },
+    RetriesExhausted {
+        url: String,
+        retries: u32,
+        message: String,
+    },
+    DisallowedByRobotsTxt {
+        url: String,
+    },
+    VenueSyncPanicked {
+        venue_id: String,
+        message: String,
+    },
 }
 
 impl std::error::Error for ErrorKind {}
 
 impl From<ParseError> for ErrorKind {
     fn from(parse_error: ParseError) -> Self {
-        match parse_error {
-            _ => ErrorKind::UrlCannotBeParsed {
-                message: parse_error.to_string(),
-            },
+        ErrorKind::UrlCannotBeParsed {
+            message: parse_error.to_string(),
         }
     }
 }
 
 impl From<reqwest::Error> for ErrorKind {
-    fn from(reqwest_error: Error) -> Self {
-        match reqwest_error {
-            _ => ErrorKind::GenericError,
-        }
+    fn from(_reqwest_error: Error) -> Self {
+        ErrorKind::GenericError
     }
 }
 
@@ -59,9 +110,33 @@ impl From<mongodb::error::Error> for ErrorKind {
     }
 }
 
+impl From<rusqlite::Error> for ErrorKind {
+    fn from(sqlite_error: rusqlite::Error) -> Self {
+        ErrorKind::SqliteError {
+            message: sqlite_error.to_string(),
+        }
+    }
+}
+
+impl From<serde_json::Error> for ErrorKind {
+    fn from(json_error: serde_json::Error) -> Self {
+        ErrorKind::JsonError {
+            message: json_error.to_string(),
+        }
+    }
+}
+
+impl From<std::io::Error> for ErrorKind {
+    fn from(io_error: std::io::Error) -> Self {
+        ErrorKind::IoError {
+            message: io_error.to_string(),
+        }
+    }
+}
+
 impl Display for ErrorKind {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        match &*self {
+        match self {
             ErrorKind::GenericError => write!(f, "Something is off"),
             ErrorKind::UrlCannotBeParsed { message } => {
                 write!(
@@ -91,6 +166,69 @@ impl Display for ErrorKind {
             ErrorKind::MongoDbError { mongodb_error } => {
                 write!(f, "MongoDbError: {:?}", mongodb_error)
             }
+            ErrorKind::VenueConfigError { message } => {
+                write!(f, "VenueConfigError: {}", message)
+            }
+            ErrorKind::InvalidVenueDefinition { venue_id, message } => {
+                write!(
+                    f,
+                    "InvalidVenueDefinition: venue '{}': {}",
+                    venue_id, message
+                )
+            }
+            ErrorKind::UnknownVenue { venue_id } => {
+                write!(f, "UnknownVenue: no venue with id '{}'", venue_id)
+            }
+            ErrorKind::UnparseableDate { text } => {
+                write!(f, "UnparseableDate: no date found in '{}'", text)
+            }
+            ErrorKind::SqliteError { message } => write!(f, "SqliteError: {}", message),
+            ErrorKind::JsonError { message } => write!(f, "JsonError: {}", message),
+            ErrorKind::JsonPathError { path, message } => {
+                write!(
+                    f,
+                    "JsonPathError: Error in json path '{}': {}",
+                    path, message
+                )
+            }
+            ErrorKind::AgendaNotFound { url } => {
+                write!(f, "AgendaNotFound: no agenda with url {}", url)
+            }
+            ErrorKind::UnknownStoreType { store_type } => {
+                write!(f, "UnknownStoreType: {}", store_type)
+            }
+            ErrorKind::IoError { message } => write!(f, "IoError: {}", message),
+            ErrorKind::ServerError { message } => write!(f, "ServerError: {}", message),
+            ErrorKind::ConnectionError { url, message } => {
+                write!(
+                    f,
+                    "ConnectionError: Cannot connect for {}: {}",
+                    url, message
+                )
+            }
+            ErrorKind::RetriesExhausted {
+                url,
+                retries,
+                message,
+            } => write!(
+                f,
+                "RetriesExhausted: {} failed after {} retries, last with {}",
+                url, retries, message
+            ),
+            ErrorKind::DisallowedByRobotsTxt { url } => {
+                write!(
+                    f,
+                    "DisallowedByRobotsTxt: {} is disallowed by robots.txt",
+                    url
+                )
+            }
+            ErrorKind::VenueSyncPanicked { venue_id, message } => {
+                write!(
+                    f,
+                    "VenueSyncPanicked: sync of venue '{}' panicked: {}",
+                    venue_id, message
+                )
+            }
         }
     }
 }
@@ -0,0 +1,199 @@
+use crate::agenda::{Agenda, AgendaStatus, Venue};
+use crate::store::AgendaStore;
+use crate::venue_definition::VenueDefinition;
+use crate::ErrorKind;
+use chrono::{DateTime, Duration, FixedOffset, Utc};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::info;
+
+const PRODUCT_ID: &str = "-//venue-scraper//venue-scraper//NL";
+/// Lines longer than this number of octets are folded, see RFC 5545 section 3.1.
+const MAX_LINE_OCTETS: usize = 75;
+/// The file name of the feed with the agenda items of all venues.
+pub const COMBINED_FEED_NAME: &str = "all";
+
+/// Write an iCalendar feed for every venue, `<venue_id>.ics`, and one with all venues combined,
+/// `all.ics`, into the output directory. Returns the paths of the written feeds.
+///
+/// The feeds only depend on the stored agenda items, exporting twice gives the same files.
+pub async fn export_icalendar(
+    store: &dyn AgendaStore,
+    venue_definitions: &[VenueDefinition],
+    output_dir: &Path,
+) -> Result<Vec<PathBuf>, ErrorKind> {
+    fs::create_dir_all(output_dir)?;
+    let mut written_paths = Vec::new();
+    let mut all_events = Vec::new();
+
+    for venue_definition in venue_definitions {
+        let venue = venue_definition.venue();
+        let agenda_items = store.agenda_items_for_venue(&venue.venue_id).await?;
+        let events: Vec<(Venue, Agenda)> = agenda_items
+            .into_iter()
+            .map(|agenda| (venue.clone(), agenda))
+            .collect();
+
+        let path = output_dir.join(format!("{}.ics", venue.venue_id));
+        fs::write(&path, icalendar_feed(&venue.name, &events))?;
+        info!(
+            "Exported {} agenda items to {}",
+            events.len(),
+            path.display()
+        );
+        written_paths.push(path);
+        all_events.extend(events);
+    }
+
+    let path = output_dir.join(format!("{}.ics", COMBINED_FEED_NAME));
+    fs::write(&path, icalendar_feed("All venues", &all_events))?;
+    info!(
+        "Exported {} agenda items to {}",
+        all_events.len(),
+        path.display()
+    );
+    written_paths.push(path);
+
+    Ok(written_paths)
+}
+
+/// An RFC 5545 calendar with an event for every agenda item with a start date. Events are ordered
+/// by start and url, so the feed is the same for the same agenda items.
+pub fn icalendar_feed(calendar_name: &str, events: &[(Venue, Agenda)]) -> String {
+    let mut events: Vec<&(Venue, Agenda)> = events
+        .iter()
+        .filter(|(_, agenda)| agenda.starts_at.is_some())
+        .collect();
+    events.sort_by(|(_, agenda), (_, other)| {
+        (agenda.starts_at, &agenda.url).cmp(&(other.starts_at, &other.url))
+    });
+
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        format!("PRODID:{}", PRODUCT_ID),
+        "CALSCALE:GREGORIAN".to_string(),
+        "METHOD:PUBLISH".to_string(),
+        format!("X-WR-CALNAME:{}", escape_text(calendar_name)),
+    ];
+    for (venue, agenda) in events {
+        lines.extend(event_lines(venue, agenda));
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    lines
+        .iter()
+        .map(|line| fold_line(line))
+        .collect::<Vec<String>>()
+        .join("")
+}
+
+/// Stable uid of the agenda item, derived from its url.
+pub fn event_uid(agenda: &Agenda) -> String {
+    format!("{}@venue-scraper", agenda.stable_id())
+}
+
+fn event_lines(venue: &Venue, agenda: &Agenda) -> Vec<String> {
+    let starts_at = agenda.starts_at.unwrap();
+    // The stamp comes from the agenda item instead of the clock, to keep the export reproducible.
+    let stamp = agenda
+        .updated_at
+        .unwrap_or_else(|| starts_at.with_timezone(&Utc));
+
+    let mut lines = vec![
+        "BEGIN:VEVENT".to_string(),
+        format!("UID:{}", event_uid(agenda)),
+        format!("DTSTAMP:{}", utc_date_time(&stamp)),
+    ];
+    if agenda.all_day {
+        lines.push(format!("DTSTART;VALUE=DATE:{}", date(&starts_at)));
+        let ends_at = agenda.ends_at.unwrap_or(starts_at);
+        // The end of an all day event is exclusive.
+        lines.push(format!(
+            "DTEND;VALUE=DATE:{}",
+            date(&(ends_at + Duration::days(1)))
+        ));
+    } else {
+        lines.push(format!(
+            "DTSTART:{}",
+            utc_date_time(&starts_at.with_timezone(&Utc))
+        ));
+        if let Some(ends_at) = agenda.ends_at {
+            lines.push(format!(
+                "DTEND:{}",
+                utc_date_time(&ends_at.with_timezone(&Utc))
+            ));
+        }
+    }
+    lines.push(format!("SUMMARY:{}", escape_text(&agenda.title)));
+    if let Some(description) = event_description(agenda) {
+        lines.push(format!("DESCRIPTION:{}", escape_text(&description)));
+    }
+    lines.push(format!("LOCATION:{}", escape_text(&location(venue))));
+    lines.push(format!("URL:{}", agenda.url));
+    lines.push(format!("STATUS:{}", event_status(agenda)));
+    lines.push("END:VEVENT".to_string());
+    lines
+}
+
+fn event_description(agenda: &Agenda) -> Option<String> {
+    let parts: Vec<&str> = [&agenda.description, &agenda.long_description]
+        .into_iter()
+        .flatten()
+        .map(|part| part.as_str())
+        .collect();
+    if parts.is_empty() {
+        None
+    } else {
+        Some(parts.join("\n\n"))
+    }
+}
+
+fn location(venue: &Venue) -> String {
+    match &venue.address {
+        Some(address) => format!("{}, {}", venue.name, address),
+        None => venue.name.clone(),
+    }
+}
+
+/// Events no longer on the listing are cancelled, so subscribed calendars drop them.
+fn event_status(agenda: &Agenda) -> &'static str {
+    match agenda.status {
+        AgendaStatus::Removed | AgendaStatus::Cancelled => "CANCELLED",
+        AgendaStatus::Active | AgendaStatus::Past => "CONFIRMED",
+    }
+}
+
+fn utc_date_time(date_time: &DateTime<Utc>) -> String {
+    date_time.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+fn date(date_time: &DateTime<FixedOffset>) -> String {
+    date_time.format("%Y%m%d").to_string()
+}
+
+/// Escape the characters with a meaning in text values.
+fn escape_text(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Fold the line into lines of at most 75 octets, continuation lines start with a space. Lines are
+/// only split between characters, never inside a multi-byte character.
+fn fold_line(line: &str) -> String {
+    let mut folded = String::with_capacity(line.len() + 3);
+    let mut line_octets = 0;
+    for character in line.chars() {
+        if line_octets + character.len_utf8() > MAX_LINE_OCTETS {
+            folded.push_str("\r\n ");
+            line_octets = 1;
+        }
+        folded.push(character);
+        line_octets += character.len_utf8();
+    }
+    folded.push_str("\r\n");
+    folded
+}
@@ -0,0 +1,268 @@
+use crate::http_sender::HttpSender;
+use crate::ErrorKind;
+use async_trait::async_trait;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use reqwest::{RequestBuilder, Response, ResponseBuilderExt};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use tokio::fs;
+use tokio::sync::Mutex;
+use tracing::info;
+use url::Url;
+
+/// Name of the manifest in the directory of a test case of a host.
+pub const MANIFEST_FILE: &str = "manifest.json";
+
+/// Headers that no longer match a body stored as plain file.
+const SKIPPED_HEADERS: [&str; 3] = ["content-encoding", "content-length", "transfer-encoding"];
+
+/// The recorded responses of a test case for a host.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    pub entries: Vec<ManifestEntry>,
+}
+
+/// A recorded response, the body is in the file relative to the test case directory.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    pub url: String,
+    pub path: String,
+    pub query: Option<String>,
+    pub status: u16,
+    pub headers: BTreeMap<String, String>,
+    pub file: String,
+}
+
+/// The directory with the files of the test case for the host of the url,
+/// `<fixtures_dir>/<host>/<test_case>`.
+pub fn test_case_dir(fixtures_dir: &Path, test_case: &str, url: &Url) -> PathBuf {
+    fixtures_dir
+        .join(url.host_str().unwrap_or_default())
+        .join(test_case)
+}
+
+/// The file of the url in the test case directory, the path of the url without the slashes around
+/// it, with `index` for paths ending in a slash. Urls with a query get the hash of the query
+/// appended, so they do not overwrite the url without one.
+pub fn fixture_file_for(url: &Url) -> String {
+    let path = url.path().trim_start_matches('/');
+    let file = if path.is_empty() || path.ends_with('/') {
+        format!("{}index", path)
+    } else {
+        path.to_string()
+    };
+    match url.query() {
+        Some(query) => {
+            let hash = format!("{:x}", Sha256::digest(query.as_bytes()));
+            format!("{}__{}", file, &hash[..12])
+        }
+        None => file,
+    }
+}
+
+/// The manifest of the test case directory, None when it has none.
+async fn read_manifest(test_case_dir: &Path) -> Option<Manifest> {
+    let contents = fs::read(test_case_dir.join(MANIFEST_FILE)).await.ok()?;
+    Some(serde_json::from_slice(&contents).unwrap_or_default())
+}
+
+fn url_of(request: &RequestBuilder) -> Option<Url> {
+    request
+        .try_clone()
+        .and_then(|request| request.build().ok())
+        .map(|request| request.url().clone())
+}
+
+/// Sender storing the responses of the sender as fixtures of the test case, in the layout of the
+/// test files, `<fixtures_dir>/<host>/<test_case>/<path>`, with a manifest of the status, headers
+/// and query string of every response.
+pub struct RecordingHttpSender<S> {
+    sender: S,
+    fixtures_dir: PathBuf,
+    test_case: String,
+    /// Serializes the updates of the manifests.
+    manifest_lock: Mutex<()>,
+}
+
+impl<S: HttpSender> RecordingHttpSender<S> {
+    pub fn new(sender: S, fixtures_dir: PathBuf, test_case: &str) -> RecordingHttpSender<S> {
+        RecordingHttpSender {
+            sender,
+            fixtures_dir,
+            test_case: test_case.to_string(),
+            manifest_lock: Mutex::new(()),
+        }
+    }
+
+    async fn record(&self, url: &Url, entry: ManifestEntry, body: &[u8]) -> Result<(), ErrorKind> {
+        let test_case_dir = test_case_dir(&self.fixtures_dir, &self.test_case, url);
+        let file_path = test_case_dir.join(&entry.file);
+        if let Some(parent) = file_path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+        fs::write(&file_path, body).await?;
+
+        let _manifest_lock = self.manifest_lock.lock().await;
+        let mut manifest = read_manifest(&test_case_dir).await.unwrap_or_default();
+        manifest
+            .entries
+            .retain(|recorded| recorded.url != entry.url);
+        manifest.entries.push(entry);
+        manifest
+            .entries
+            .sort_by(|entry, other| entry.url.cmp(&other.url));
+        fs::write(
+            test_case_dir.join(MANIFEST_FILE),
+            serde_json::to_vec_pretty(&manifest)?,
+        )
+        .await?;
+        info!("Recorded {} in {}", url, file_path.display());
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<S: HttpSender> HttpSender for RecordingHttpSender<S> {
+    async fn send(&self, request: RequestBuilder) -> Result<Response, ErrorKind> {
+        let url = match url_of(&request) {
+            Some(url) => url,
+            None => return self.sender.send(request).await,
+        };
+        let mut response = self.sender.send(request).await?;
+
+        let status = response.status();
+        let headers = response.headers().clone();
+        let extensions = std::mem::take(response.extensions_mut());
+        let response_url = response.url().clone();
+        let body = response.bytes().await?.to_vec();
+
+        let entry = ManifestEntry {
+            url: url.to_string(),
+            path: url.path().to_string(),
+            query: url.query().map(|query| query.to_string()),
+            status: status.as_u16(),
+            headers: headers
+                .iter()
+                .filter(|(name, _)| !SKIPPED_HEADERS.contains(&name.as_str()))
+                .filter_map(|(name, value)| {
+                    value
+                        .to_str()
+                        .ok()
+                        .map(|value| (name.to_string(), value.to_string()))
+                })
+                .collect(),
+            file: fixture_file_for(&url),
+        };
+        self.record(&url, entry, &body).await?;
+
+        let mut builder = http::response::Builder::new()
+            .status(status)
+            .url(response_url);
+        *builder.headers_mut().unwrap() = headers;
+        let mut http_response = builder.body(body).unwrap();
+        *http_response.extensions_mut() = extensions;
+        Ok(http_response.into())
+    }
+}
+
+/// Sender answering requests from the fixtures of a test case. Test cases with a manifest answer
+/// the recorded urls with the recorded status and headers, and other urls with a 404. Test cases
+/// made by hand, without a manifest, answer with the file at the path of the url, or a 404 when
+/// there is no such file.
+pub struct ReplayHttpSender {
+    fixtures_dir: PathBuf,
+    test_case: String,
+    /// The entries of the manifests by url, None for test case directories without a manifest.
+    manifests: Mutex<HashMap<PathBuf, Option<HashMap<String, ManifestEntry>>>>,
+}
+
+/// Where the replayed response comes from.
+enum Replay {
+    Recorded(ManifestEntry),
+    NotRecorded,
+    ByPath,
+}
+
+impl ReplayHttpSender {
+    pub fn new(fixtures_dir: PathBuf, test_case: &str) -> ReplayHttpSender {
+        ReplayHttpSender {
+            fixtures_dir,
+            test_case: test_case.to_string(),
+            manifests: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn replay_for(&self, test_case_dir: &Path, url: &Url) -> Replay {
+        let mut manifests = self.manifests.lock().await;
+        if !manifests.contains_key(test_case_dir) {
+            let entries = read_manifest(test_case_dir).await.map(|manifest| {
+                manifest
+                    .entries
+                    .into_iter()
+                    .map(|entry| (entry.url.clone(), entry))
+                    .collect()
+            });
+            manifests.insert(test_case_dir.to_path_buf(), entries);
+        }
+        match &manifests[test_case_dir] {
+            Some(entries) => match entries.get(url.as_str()) {
+                Some(entry) => Replay::Recorded(entry.clone()),
+                None => Replay::NotRecorded,
+            },
+            None => Replay::ByPath,
+        }
+    }
+}
+
+/// The file of a url in a test case made by hand, the path of the url, or the index file in the
+/// directory of the path.
+async fn file_for_path(test_case_dir: &Path, url: &Url) -> PathBuf {
+    let path = test_case_dir.join(url.path().trim_matches('/'));
+    match fs::metadata(&path).await {
+        Ok(metadata) if metadata.is_dir() => path.join("index"),
+        _ => path,
+    }
+}
+
+#[async_trait]
+impl HttpSender for ReplayHttpSender {
+    async fn send(&self, request: RequestBuilder) -> Result<Response, ErrorKind> {
+        let url = request.build()?.url().clone();
+        let test_case_dir = test_case_dir(&self.fixtures_dir, &self.test_case, &url);
+
+        let (status, headers, file_path) = match self.replay_for(&test_case_dir, &url).await {
+            Replay::Recorded(entry) => {
+                let mut headers = HeaderMap::new();
+                for (name, value) in entry.headers {
+                    if let (Ok(name), Ok(value)) = (
+                        HeaderName::from_bytes(name.as_bytes()),
+                        HeaderValue::from_str(&value),
+                    ) {
+                        headers.append(name, value);
+                    }
+                }
+                (entry.status, headers, Some(test_case_dir.join(entry.file)))
+            }
+            Replay::NotRecorded => (404, HeaderMap::new(), None),
+            Replay::ByPath => (
+                200,
+                HeaderMap::new(),
+                Some(file_for_path(&test_case_dir, &url).await),
+            ),
+        };
+
+        let body = match file_path {
+            Some(file_path) => fs::read(&file_path).await.ok(),
+            None => None,
+        };
+        let (status, body) = match body {
+            Some(body) => (status, body),
+            None => (404, Vec::new()),
+        };
+        let mut builder = http::response::Builder::new().status(status).url(url);
+        *builder.headers_mut().unwrap() = headers;
+        Ok(builder.body(body).unwrap().into())
+    }
+}
@@ -0,0 +1,231 @@
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+use tracing::{error, warn};
+
+/// The number of earlier full syncs the selector health of a sync is compared with.
+pub const HEALTH_HISTORY_SIZE: usize = 10;
+/// Counts are only compared once there are this many earlier full syncs to average.
+const MIN_HEALTH_HISTORY: usize = 3;
+/// Fewer agenda items than this part of the average is suspect.
+const MIN_ITEMS_RATIO: f64 = 0.5;
+/// A part of unparseable items this much above the average is suspect.
+const MAX_UNPARSEABLE_INCREASE: f64 = 0.25;
+/// A part of agenda items without description this much above the average is suspect.
+const MAX_MISSING_DESCRIPTION_INCREASE: f64 = 0.5;
+/// After this many consecutive suspect runs with about as many agenda items, their level is the
+/// new normal.
+const SUSPECT_RUNS_TO_ACCEPT: usize = 3;
+
+/// How well the selectors of a venue did in a full sync of its listing.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SelectorHealth {
+    pub venue_id: String,
+    /// The start of the sync.
+    pub checked_at: DateTime<Utc>,
+    pub number_of_items: u32,
+    pub number_of_unparseable_items: u32,
+    pub number_of_items_without_description: u32,
+    /// Set when the sync differs so much from the earlier ones that the site likely changed.
+    pub layout_change_suspected: Option<LayoutChangeSuspected>,
+}
+
+/// The reasons to suspect the layout of the agenda of a venue changed.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct LayoutChangeSuspected {
+    pub reasons: Vec<String>,
+}
+
+impl SelectorHealth {
+    fn number_of_parsed_items(&self) -> u32 {
+        self.number_of_items - self.number_of_unparseable_items
+    }
+
+    fn unparseable_ratio(&self) -> f64 {
+        ratio(self.number_of_unparseable_items, self.number_of_items)
+    }
+
+    fn missing_description_ratio(&self) -> f64 {
+        ratio(
+            self.number_of_items_without_description,
+            self.number_of_parsed_items(),
+        )
+    }
+
+    /// Compare with the earlier selector health of the venue, the most recent first. The runs that
+    /// were suspect themselves are left out, so a broken layout stays suspect until the selectors
+    /// are fixed, unless they are accepted as the new normal, see baseline.
+    pub fn compare_with_history(mut self, history: &[SelectorHealth]) -> SelectorHealth {
+        let history = baseline(history);
+        let average = |value: fn(&SelectorHealth) -> f64| {
+            history.iter().map(|health| value(health)).sum::<f64>() / history.len() as f64
+        };
+
+        let mut reasons = Vec::new();
+        let number_of_parsed_items = self.number_of_parsed_items();
+        let average_parsed_items = average(|health| health.number_of_parsed_items() as f64);
+        if self.number_of_items > 0 && number_of_parsed_items == 0 {
+            reasons.push(format!(
+                "all {} agenda items are unparseable",
+                self.number_of_items
+            ));
+        } else if self.number_of_items == 0 && !history.is_empty() && average_parsed_items >= 1.0 {
+            reasons.push(format!(
+                "no agenda items found, {:.1} on average",
+                average_parsed_items
+            ));
+        } else if history.len() >= MIN_HEALTH_HISTORY
+            && (number_of_parsed_items as f64) < average_parsed_items * MIN_ITEMS_RATIO
+        {
+            reasons.push(format!(
+                "{} agenda items found, {:.1} on average",
+                number_of_parsed_items, average_parsed_items
+            ));
+        }
+
+        if number_of_parsed_items > 0 && history.len() >= MIN_HEALTH_HISTORY {
+            let average_unparseable = average(SelectorHealth::unparseable_ratio);
+            if self.unparseable_ratio() > average_unparseable + MAX_UNPARSEABLE_INCREASE {
+                reasons.push(format!(
+                    "{:.0}% of the agenda items are unparseable, {:.0}% on average",
+                    self.unparseable_ratio() * 100.0,
+                    average_unparseable * 100.0
+                ));
+            }
+            let average_missing_description = average(SelectorHealth::missing_description_ratio);
+            if self.missing_description_ratio()
+                > average_missing_description + MAX_MISSING_DESCRIPTION_INCREASE
+            {
+                reasons.push(format!(
+                    "{:.0}% of the agenda items have no description, {:.0}% on average",
+                    self.missing_description_ratio() * 100.0,
+                    average_missing_description * 100.0
+                ));
+            }
+        }
+
+        self.layout_change_suspected = if reasons.is_empty() {
+            None
+        } else {
+            Some(LayoutChangeSuspected { reasons })
+        };
+        self
+    }
+}
+
+/// The earlier runs to compare with. A listing that really shrank is suspect at first, but once
+/// SUSPECT_RUNS_TO_ACCEPT consecutive suspect runs found about as many agenda items, those runs and
+/// the later ones that were not suspect are the baseline. The older runs are left out.
+fn baseline(history: &[SelectorHealth]) -> Vec<&SelectorHealth> {
+    let not_suspected = |health: &&SelectorHealth| health.layout_change_suspected.is_none();
+    match history
+        .windows(SUSPECT_RUNS_TO_ACCEPT)
+        .position(is_consistently_suspected)
+    {
+        Some(position) => history[..position]
+            .iter()
+            .filter(not_suspected)
+            .chain(&history[position..position + SUSPECT_RUNS_TO_ACCEPT])
+            .collect(),
+        None => history.iter().filter(not_suspected).collect(),
+    }
+}
+
+/// The runs were all suspect, but parsed agenda items and about as many each time.
+fn is_consistently_suspected(runs: &[SelectorHealth]) -> bool {
+    let parsed_items = runs.iter().map(SelectorHealth::number_of_parsed_items);
+    let (Some(min_parsed_items), Some(max_parsed_items)) =
+        (parsed_items.clone().min(), parsed_items.max())
+    else {
+        return false;
+    };
+    runs.iter()
+        .all(|health| health.layout_change_suspected.is_some())
+        && min_parsed_items > 0
+        && min_parsed_items as f64 >= max_parsed_items as f64 * MIN_ITEMS_RATIO
+}
+
+fn ratio(count: u32, total: u32) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        count as f64 / total as f64
+    }
+}
+
+impl Display for SelectorHealth {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} at {}: {} items, {} unparseable, {} without description",
+            self.venue_id,
+            self.checked_at.to_rfc3339(),
+            self.number_of_items,
+            self.number_of_unparseable_items,
+            self.number_of_items_without_description
+        )?;
+        match &self.layout_change_suspected {
+            Some(layout_change_suspected) => write!(f, ", {}", layout_change_suspected),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Display for LayoutChangeSuspected {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "layout change suspected: {}", self.reasons.join("; "))
+    }
+}
+
+/// Told about the syncs that suspect the layout of a venue changed.
+#[async_trait]
+pub trait LayoutChangeAlert: Send + Sync {
+    async fn alert(&self, selector_health: &SelectorHealth);
+}
+
+/// Logs the suspected layout changes as errors.
+pub struct LogLayoutChangeAlert;
+
+#[async_trait]
+impl LayoutChangeAlert for LogLayoutChangeAlert {
+    async fn alert(&self, selector_health: &SelectorHealth) {
+        error!("Check the selectors of {}", selector_health);
+    }
+}
+
+/// Posts the selector health of the suspected layout changes as json to a webhook, and logs them.
+pub struct WebhookLayoutChangeAlert {
+    client: Client,
+    url: String,
+}
+
+impl WebhookLayoutChangeAlert {
+    pub fn new(client: Client, url: &str) -> WebhookLayoutChangeAlert {
+        WebhookLayoutChangeAlert {
+            client,
+            url: url.to_string(),
+        }
+    }
+}
+
+#[async_trait]
+impl LayoutChangeAlert for WebhookLayoutChangeAlert {
+    async fn alert(&self, selector_health: &SelectorHealth) {
+        LogLayoutChangeAlert.alert(selector_health).await;
+        let response = self
+            .client
+            .post(&self.url)
+            .json(selector_health)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        if let Err(err) = response {
+            warn!(
+                "Cannot post the layout change alert to {}: {}",
+                self.url, err
+            );
+        }
+    }
+}
@@ -1,11 +1,14 @@
+use crate::cache::{cache_hit_of, CacheHit};
+use crate::retry::retries_of;
 use crate::ErrorKind;
 use async_trait::async_trait;
 use reqwest::{Client, RequestBuilder, Response};
-use std::rc::Rc;
+use std::sync::Arc;
 use url::Url;
 
+/// Sends the http requests of the scrapers. Senders are shared by the venues synced in parallel.
 #[async_trait]
-pub trait HttpSender {
+pub trait HttpSender: Send + Sync {
     async fn send(&self, request: RequestBuilder) -> Result<Response, ErrorKind>;
 }
 
@@ -14,18 +17,51 @@ pub struct DefaultHttpSender;
 #[async_trait]
 impl HttpSender for DefaultHttpSender {
     async fn send(&self, request: RequestBuilder) -> Result<Response, ErrorKind> {
-        Ok(request.send().await?)
+        request.send().await.map_err(|err| match err.url() {
+            Some(url) if err.is_connect() || err.is_timeout() => ErrorKind::ConnectionError {
+                url: url.to_string(),
+                message: err.to_string(),
+            },
+            _ => err.into(),
+        })
     }
 }
 
+/// The body of a fetched url, its status code, the number of retries it took and whether it came
+/// from the cache.
+pub struct FetchedBody {
+    pub body: String,
+    pub status_code: u16,
+    pub retries: u32,
+    pub cache_hit: Option<CacheHit>,
+}
+
 pub async fn get_body_for_url(
     client: &Client,
-    http_sender: &Rc<dyn HttpSender>,
+    http_sender: &Arc<dyn HttpSender>,
     url: &str,
 ) -> Result<String, ErrorKind> {
+    Ok(fetch_body_for_url(client, http_sender, url).await?.body)
+}
+
+/// Like get_body_for_url, with what the retrying and caching senders did.
+pub async fn fetch_body_for_url(
+    client: &Client,
+    http_sender: &Arc<dyn HttpSender>,
+    url: &str,
+) -> Result<FetchedBody, ErrorKind> {
     let request = build_request_for_url(client, url)?;
     let response = http_sender.send(request).await?;
-    body_for_response(response).await
+    let retries = retries_of(&response);
+    let cache_hit = cache_hit_of(&response);
+    let status_code = response.status().as_u16();
+    let body = body_for_response(response).await?;
+    Ok(FetchedBody {
+        body,
+        status_code,
+        retries,
+        cache_hit,
+    })
 }
 
 /// Build a GET request object for execution by the client.
@@ -0,0 +1,133 @@
+use crate::ErrorKind;
+use serde_json::Value;
+use std::fmt::{Display, Formatter};
+
+/// A JSONPath-style path into a JSON value, the subset needed to map embedded agenda data: `$`
+/// for the root, `.name` or `['name']` for a member, `[0]` for an array element, `[*]` or `.*`
+/// for all elements or members, and `..name` for the members with the name at any depth.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JsonPath {
+    path: String,
+    steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Step {
+    Member(String),
+    Index(usize),
+    Wildcard,
+    Descendants(String),
+}
+
+impl JsonPath {
+    pub fn parse(path: &str) -> Result<JsonPath, ErrorKind> {
+        let error = |message: &str| ErrorKind::JsonPathError {
+            path: path.to_string(),
+            message: message.to_string(),
+        };
+        let rest = path
+            .trim()
+            .strip_prefix('$')
+            .ok_or_else(|| error("must start with $"))?;
+        let mut chars = rest.chars().peekable();
+        let mut steps = Vec::new();
+        while let Some(char) = chars.next() {
+            match char {
+                '.' => {
+                    let recursive = chars.next_if_eq(&'.').is_some();
+                    let mut name = String::new();
+                    while let Some(char) = chars.next_if(|char| *char != '.' && *char != '[') {
+                        name.push(char);
+                    }
+                    steps.push(match (recursive, name.as_str()) {
+                        (_, "") => return Err(error("a member name is missing after .")),
+                        (false, "*") => Step::Wildcard,
+                        (false, _) => Step::Member(name),
+                        (true, _) => Step::Descendants(name),
+                    });
+                }
+                '[' => {
+                    let mut selector = String::new();
+                    loop {
+                        match chars.next() {
+                            Some(']') => break,
+                            Some(char) => selector.push(char),
+                            None => return Err(error("a [ is not closed")),
+                        }
+                    }
+                    let selector = selector.trim();
+                    let quoted = selector
+                        .strip_prefix('\'')
+                        .and_then(|it| it.strip_suffix('\''))
+                        .or_else(|| {
+                            selector
+                                .strip_prefix('"')
+                                .and_then(|it| it.strip_suffix('"'))
+                        });
+                    steps.push(match quoted {
+                        Some(name) => Step::Member(name.to_string()),
+                        None if selector == "*" => Step::Wildcard,
+                        None => Step::Index(
+                            selector
+                                .parse()
+                                .map_err(|_| error("an index must be a number, * or a name"))?,
+                        ),
+                    });
+                }
+                _ => return Err(error("expected . or [")),
+            }
+        }
+        Ok(JsonPath {
+            path: path.to_string(),
+            steps,
+        })
+    }
+
+    /// All values in the JSON at the path.
+    pub fn select<'a>(&self, json: &'a Value) -> Vec<&'a Value> {
+        self.steps.iter().fold(vec![json], |values, step| {
+            values
+                .into_iter()
+                .flat_map(|value| select_step(step, value))
+                .collect()
+        })
+    }
+}
+
+impl Display for JsonPath {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.path)
+    }
+}
+
+fn select_step<'a>(step: &Step, value: &'a Value) -> Vec<&'a Value> {
+    match (step, value) {
+        (Step::Member(name), Value::Object(object)) => object.get(name).into_iter().collect(),
+        (Step::Index(index), Value::Array(values)) => values.get(*index).into_iter().collect(),
+        (Step::Wildcard, Value::Array(values)) => values.iter().collect(),
+        (Step::Wildcard, Value::Object(object)) => object.values().collect(),
+        (Step::Descendants(name), _) => {
+            let mut descendants = Vec::new();
+            collect_descendants(name, value, &mut descendants);
+            descendants
+        }
+        _ => Vec::new(),
+    }
+}
+
+fn collect_descendants<'a>(name: &str, value: &'a Value, descendants: &mut Vec<&'a Value>) {
+    match value {
+        Value::Object(object) => {
+            if let Some(member) = object.get(name) {
+                descendants.push(member);
+            }
+            object
+                .values()
+                .for_each(|value| collect_descendants(name, value, descendants));
+        }
+        Value::Array(values) => values
+            .iter()
+            .for_each(|value| collect_descendants(name, value, descendants)),
+        _ => {}
+    }
+}
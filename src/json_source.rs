@@ -0,0 +1,166 @@
+use crate::agenda::Agenda;
+use crate::dates::parse_iso_date_time;
+use crate::json_path::JsonPath;
+use crate::structured_data::plain_text;
+use crate::urls::{absolute_url, canonical_url};
+use crate::ErrorKind;
+use scraper::{Html, Selector};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::sync::OnceLock;
+use url::Url;
+
+/// Agenda items from JSON, embedded in the page by client-side rendered sites or served by a JSON
+/// endpoint, compiled from the json definition of a venue.
+#[derive(Debug)]
+pub struct JsonSource {
+    pub embedded_in: EmbeddedIn,
+    /// The agenda items in the JSON, an array at the path is taken as its elements.
+    pub items: JsonPath,
+    pub fields: JsonFields,
+}
+
+/// Where the JSON is on the page.
+#[derive(Debug)]
+pub enum EmbeddedIn {
+    /// The page is the JSON, like the response of a wp-json endpoint.
+    Page,
+    /// The text of the script element found by the selector, like `script#__NEXT_DATA__`.
+    Script(Selector),
+    /// The value assigned to the variable in a script, like `window.__NUXT__`.
+    Variable(String),
+}
+
+/// The paths of the fields of an agenda item, relative to the item.
+#[derive(Debug)]
+pub struct JsonFields {
+    pub url: JsonPath,
+    pub title: JsonPath,
+    pub description: Option<JsonPath>,
+    /// An ISO date gives the exact start, other texts are parsed like the start texts of pages.
+    pub start: Option<JsonPath>,
+    pub end: Option<JsonPath>,
+    pub price: Option<JsonPath>,
+    pub ticket_url: Option<JsonPath>,
+    pub image: Option<JsonPath>,
+}
+
+impl JsonSource {
+    /// The JSON of the page, the document is the page parsed as html.
+    pub fn json_from_page(&self, page: &str, document: &Html) -> Result<Value, ErrorKind> {
+        match &self.embedded_in {
+            EmbeddedIn::Page => Ok(serde_json::from_str(page)?),
+            EmbeddedIn::Script(selector) => {
+                let script =
+                    document
+                        .select(selector)
+                        .next()
+                        .ok_or_else(|| ErrorKind::JsonError {
+                            message: "no script with the JSON on the page".to_string(),
+                        })?;
+                Ok(serde_json::from_str(&script.text().collect::<String>())?)
+            }
+            EmbeddedIn::Variable(variable) => document
+                .select(script_selector())
+                .find_map(|script| assigned_value(&script.text().collect::<String>(), variable))
+                .ok_or_else(|| ErrorKind::JsonError {
+                    message: format!("no JSON assigned to {} on the page", variable),
+                }),
+        }
+    }
+
+    /// The agenda items in the JSON, or the errors of the items that cannot be mapped.
+    pub fn agenda_items(
+        &self,
+        json: &Value,
+        venue_id: &str,
+        page_url: &Url,
+    ) -> Vec<Result<Agenda, ErrorKind>> {
+        let mut items = self.items.select(json);
+        if let [Value::Array(values)] = items[..] {
+            items = values.iter().collect();
+        }
+        items
+            .into_iter()
+            .map(|item| self.agenda_from_item(item, venue_id, page_url))
+            .collect()
+    }
+
+    fn agenda_from_item(
+        &self,
+        item: &Value,
+        venue_id: &str,
+        page_url: &Url,
+    ) -> Result<Agenda, ErrorKind> {
+        let fields = &self.fields;
+        let href = required_text(item, &fields.url)?;
+        let start_text = optional_text(item, &fields.start);
+        let start = start_text.as_deref().and_then(parse_iso_date_time);
+        let link = |path: &Option<JsonPath>| {
+            optional_text(item, path)
+                .and_then(|href| absolute_url(page_url, &href).ok())
+                .map(|url| url.to_string())
+        };
+
+        Ok(Agenda {
+            url: canonical_url(page_url, &href)?,
+            details_url: Some(absolute_url(page_url, &href)?.to_string()),
+            title: plain_text(&required_text(item, &fields.title)?),
+            description: optional_text(item, &fields.description).map(|text| plain_text(&text)),
+            venue_id: venue_id.to_string(),
+            needs_details: true,
+            // Exact dates need no parsing, other texts are parsed with the dates of the pages.
+            start_text: if start.is_none() { start_text } else { None },
+            starts_at: start.map(|(starts_at, _)| starts_at),
+            all_day: start.map(|(_, all_day)| all_day).unwrap_or_default(),
+            ends_at: optional_text(item, &fields.end)
+                .and_then(|text| parse_iso_date_time(&text))
+                .map(|(ends_at, _)| ends_at),
+            price: optional_text(item, &fields.price),
+            ticket_url: link(&fields.ticket_url),
+            image_url: link(&fields.image),
+            listing_hash: Some(format!("{:x}", Sha256::digest(item.to_string().as_bytes()))),
+            ..Default::default()
+        })
+    }
+}
+
+fn script_selector() -> &'static Selector {
+    static SELECTOR: OnceLock<Selector> = OnceLock::new();
+    SELECTOR.get_or_init(|| Selector::parse("script").unwrap())
+}
+
+/// The text of the first string or number at the path.
+fn text_at(item: &Value, path: &JsonPath) -> Option<String> {
+    path.select(item)
+        .into_iter()
+        .find_map(|value| match value {
+            Value::String(text) => Some(text.trim().to_string()),
+            Value::Number(number) => Some(number.to_string()),
+            _ => None,
+        })
+        .filter(|text| !text.is_empty())
+}
+
+fn required_text(item: &Value, path: &JsonPath) -> Result<String, ErrorKind> {
+    text_at(item, path).ok_or_else(|| ErrorKind::JsonPathError {
+        path: path.to_string(),
+        message: "no text in the agenda item".to_string(),
+    })
+}
+
+fn optional_text(item: &Value, path: &Option<JsonPath>) -> Option<String> {
+    path.as_ref().and_then(|path| text_at(item, path))
+}
+
+/// The JSON value assigned to the variable in the script, the script may go on after it.
+fn assigned_value(script: &str, variable: &str) -> Option<Value> {
+    script.match_indices(variable).find_map(|(start, _)| {
+        let after_variable = &script[start + variable.len()..];
+        let value = after_variable.trim_start().strip_prefix('=')?;
+        serde_json::Deserializer::from_str(value)
+            .into_iter::<Value>()
+            .next()?
+            .ok()
+    })
+}
@@ -1,28 +1,66 @@
-use futures::stream::TryStreamExt;
+use std::collections::HashSet;
 use std::fmt::{Debug, Display, Formatter};
-use std::rc::Rc;
-
-use agenda::Venue;
-use scraper::Html;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use agenda::{Agenda, AgendaStatus, Venue};
+use chrono::{DateTime, Duration, Utc};
+use futures::{stream, StreamExt};
+use scraper::{ElementRef, Html};
+use serde::{Deserialize, Serialize};
 use tracing::{error, info, trace, trace_span, warn};
 
-use crate::agenda::{execute_on_agenda_items, insert_or_get_agenda, update_agenda, Agenda};
 use crate::config::Config;
-use crate::http_sender::get_body_for_url;
+use crate::dates::apply_event_dates;
+use crate::health::{SelectorHealth, HEALTH_HISTORY_SIZE};
+use crate::http_sender::fetch_body_for_url;
+use crate::json_source::JsonSource;
+use crate::listing_page::ListingPage;
+use crate::pagination::{NextPage, Page, Pagination};
+use crate::registry::VenueRegistry;
+use crate::store::AgendaStore;
+use crate::venue_definition::{IncrementalDefinition, VenueDefinition};
 use errors::ErrorKind;
 use http_sender::HttpSender;
-use mongodb::Database;
-use parser::CssSelectors;
+use parser::{CssSelectors, DetailSelectors};
 use reqwest::Client;
-use tokio::join;
+use url::Url;
 
 pub mod agenda;
+pub mod api;
+pub mod cache;
 pub mod config;
+pub mod dates;
 pub mod errors;
+pub mod export;
+pub mod fixtures;
+pub mod health;
 pub mod http_sender;
+pub mod json_path;
+pub mod json_source;
+pub mod listing_page;
+pub mod pagination;
 mod parser;
-
-#[derive(Debug)]
+pub mod politeness;
+pub mod registry;
+pub mod retry;
+pub mod robots;
+pub mod stats;
+pub mod store;
+pub mod structured_data;
+pub mod sync_run;
+pub mod urls;
+pub mod venue_definition;
+
+/// The number of characters of the html of an unparseable element kept in its parse failure.
+const HTML_SNIPPET_LENGTH: usize = 500;
+
+/// Above this share of unparseable agenda items on the listing, the items that are not seen may
+/// well still be listed, so none are marked removed.
+const MAX_UNPARSEABLE_RATIO_TO_MARK_REMOVED: f64 = 0.1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SyncingResult {
     pub total_urls_fetched: u32,
     pub total_urls_unfetchable: u32,
@@ -30,6 +68,97 @@ pub struct SyncingResult {
     pub total_unparseable_items: u32,
     pub total_items_inserted: u32,
     pub total_items_updated: u32,
+    pub total_unparseable_dates: u32,
+    /// Agenda items no longer on the listing, marked removed or past.
+    pub total_items_removed: u32,
+    /// Retries of failed requests, by a retrying http sender.
+    pub total_retries: u32,
+    /// Pages that did not change since the last fetch, served from the http cache.
+    pub total_cache_hits: u32,
+    pub total_bytes_from_cache: u64,
+    /// The agenda items, and dates of agenda items, that cannot be parsed.
+    pub parse_failures: Vec<ParseFailure>,
+    /// The selector health of a full sync of the listing of a venue.
+    pub selector_health: Option<SelectorHealth>,
+    /// The urls requested, in the order their responses came in.
+    pub fetched_urls: Vec<FetchedUrl>,
+}
+
+/// A sync that failed, with the results of what it did until it failed.
+#[derive(Debug)]
+pub struct PartialSync {
+    pub error: ErrorKind,
+    pub result: SyncingResult,
+}
+
+/// A requested url, with the status code of the response if there was one.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct FetchedUrl {
+    pub url: String,
+    pub status_code: Option<u16>,
+    /// Why the url could not be fetched.
+    pub error: Option<String>,
+}
+
+impl FetchedUrl {
+    fn fetched(url: &str, status_code: u16) -> FetchedUrl {
+        FetchedUrl {
+            url: url.to_string(),
+            status_code: Some(status_code),
+            error: None,
+        }
+    }
+
+    fn failed(url: &str, err: &ErrorKind) -> FetchedUrl {
+        let status_code = match err {
+            ErrorKind::StatusCodeFromUrl { status_code, .. } => Some(*status_code),
+            _ => None,
+        };
+        FetchedUrl {
+            url: url.to_string(),
+            status_code,
+            error: Some(err.to_string()),
+        }
+    }
+}
+
+/// An agenda item that cannot be parsed, or whose dates cannot be parsed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParseFailure {
+    /// The page the agenda item is on.
+    pub page_url: String,
+    /// The url of the agenda item, if it got that far.
+    pub url: Option<String>,
+    pub reason: String,
+    /// The start of the html of the element the css selectors cannot parse.
+    pub html: Option<String>,
+}
+
+impl ParseFailure {
+    fn new(page_url: &str, url: Option<&str>, reason: impl Display) -> ParseFailure {
+        ParseFailure {
+            page_url: page_url.to_string(),
+            url: url.map(str::to_string),
+            reason: reason.to_string(),
+            html: None,
+        }
+    }
+
+    fn with_html(self, element: &ElementRef) -> ParseFailure {
+        ParseFailure {
+            html: Some(parser::html_snippet(element, HTML_SNIPPET_LENGTH)),
+            ..self
+        }
+    }
+}
+
+impl Display for ParseFailure {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.url {
+            Some(url) => write!(f, "{} on {}: {}", url, self.page_url, self.reason),
+            None => write!(f, "{}: {}", self.page_url, self.reason),
+        }
+    }
 }
 
 impl SyncingResult {
@@ -41,6 +170,14 @@ impl SyncingResult {
             total_urls_fetched: 0,
             total_urls_unfetchable: 0,
             total_unparseable_items: 0,
+            total_unparseable_dates: 0,
+            total_items_removed: 0,
+            total_retries: 0,
+            total_cache_hits: 0,
+            total_bytes_from_cache: 0,
+            parse_failures: Vec::new(),
+            selector_health: None,
+            fetched_urls: Vec::new(),
         }
     }
 
@@ -51,6 +188,14 @@ impl SyncingResult {
         self.total_urls_fetched += other.total_urls_fetched;
         self.total_items_updated += other.total_items_updated;
         self.total_urls_unfetchable += other.total_urls_unfetchable;
+        self.total_unparseable_dates += other.total_unparseable_dates;
+        self.total_items_removed += other.total_items_removed;
+        self.total_retries += other.total_retries;
+        self.total_cache_hits += other.total_cache_hits;
+        self.total_bytes_from_cache += other.total_bytes_from_cache;
+        self.parse_failures
+            .extend(other.parse_failures.iter().cloned());
+        self.fetched_urls.extend(other.fetched_urls.iter().cloned());
     }
 }
 
@@ -63,241 +208,737 @@ impl Display for SyncingResult {
             .field("total_unparseable_items", &self.total_unparseable_items)
             .field("total_items_inserted", &self.total_items_inserted)
             .field("total_items_updated", &self.total_items_updated)
+            .field("total_unparseable_dates", &self.total_unparseable_dates)
+            .field("total_items_removed", &self.total_items_removed)
+            .field("total_retries", &self.total_retries)
+            .field("total_cache_hits", &self.total_cache_hits)
+            .field("total_bytes_from_cache", &self.total_bytes_from_cache)
             .finish()
     }
 }
 
+/// The agenda items the selectors of a venue find on a page.
+#[derive(Debug, Serialize)]
+pub struct SelectorCheck {
+    pub venue_id: String,
+    pub page_url: String,
+    pub number_of_items: u32,
+    pub number_of_unparseable_items: u32,
+    pub agenda_items: Vec<Agenda>,
+    pub parse_failures: Vec<ParseFailure>,
+}
+
+/// How much of the listing a sync walks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncMode {
+    /// All pages, marking the agenda items no longer on the listing.
+    Full,
+    /// Stop after known_pages consecutive pages without new agenda items.
+    Incremental { known_pages: u32 },
+}
+
 pub struct VenueScraper {
     client: Client,
-    pub http_sender: Rc<dyn HttpSender>,
+    pub http_sender: Arc<dyn HttpSender>,
     venue: Venue,
-    agenda_urls: Vec<String>,
-    css_selectors: CssSelectors,
-    db: Database,
+    agenda_url: String,
+    pagination: Pagination,
+    incremental: Option<IncrementalDefinition>,
+    css_selectors: Option<CssSelectors>,
+    structured_data: bool,
+    json_source: Option<JsonSource>,
+    detail_selectors: DetailSelectors,
+    store: Arc<dyn AgendaStore>,
+    removal_grace_period: Duration,
+    max_concurrent_details: usize,
+    /// Unchanged listing pages are only skipped when they were parsed with the same definition.
+    definition_hash: String,
 }
 
 impl VenueScraper {
-    pub fn tivoli_with_sender_and_client(
-        http_sender: Rc<dyn HttpSender>,
+    /// Create a scraper for the venue described by the venue definition.
+    pub fn from_definition(
+        venue_definition: &VenueDefinition,
+        http_sender: Arc<dyn HttpSender>,
         client: Client,
-        db: Database,
+        store: Arc<dyn AgendaStore>,
     ) -> Result<VenueScraper, ErrorKind> {
-        let mut agenda_urls = Vec::new();
-        agenda_urls.push(String::from("https://www.tivolivredenburg.nl/agenda/"));
-        for count in 2..20 {
-            agenda_urls.push(format!(
-                "https://www.tivolivredenburg.nl/agenda/page/{}/",
-                count
-            ));
-        }
-        let agenda_item = parser::selector_for(r#"li.agenda-list-item"#)?;
-        let url = parser::selector_for(r#"a.agenda-list-item__title-link"#)?;
-        let title = parser::selector_for(r#"a.agenda-list-item__title-link"#)?;
-        let description = parser::selector_for(r#"p.agenda-list-item__text"#)?;
-
-        let css_selectors = CssSelectors {
-            agenda_item,
-            url,
-            title,
-            description,
-        };
-
-        let venue = Venue {
-            venue_id: "tivoli_utrecht".to_string(),
-            name: "Tivoli Utrecht".to_string(),
-        };
-
+        venue_definition.validate()?;
+        let css_selectors = venue_definition.css_selectors()?;
+        let detail_selectors = venue_definition.detail_selectors()?;
         Ok(VenueScraper {
             client,
             http_sender,
-            agenda_urls,
-            venue,
+            agenda_url: venue_definition.agenda_url.clone(),
+            pagination: venue_definition.pagination()?,
+            incremental: venue_definition.incremental.clone(),
+            venue: venue_definition.venue(),
             css_selectors,
-            db,
+            structured_data: venue_definition.structured_data,
+            json_source: venue_definition.json_source()?,
+            detail_selectors,
+            store,
+            removal_grace_period: Duration::hours(venue_definition.removal_grace_hours),
+            max_concurrent_details: venue_definition.max_concurrent_details,
+            definition_hash: venue_definition.definition_hash()?,
         })
     }
 
-    pub fn spot_groningen_with_sender_and_client(
-        http_sender: Rc<dyn HttpSender>,
-        client: Client,
-        db: Database,
-    ) -> Result<VenueScraper, ErrorKind> {
-        let mut agenda_urls = Vec::new();
-        agenda_urls.push(String::from("https://www.spotgroningen.nl/programma/"));
-
-        let agenda_item = parser::selector_for(r#"article.program__item"#)?;
-        let url = parser::selector_for(r#"a.program__link"#)?;
-        let title = parser::selector_for(r#"h1"#)?;
-        let description = parser::selector_for(r#"p"#)?;
-
-        let css_selectors = CssSelectors {
-            agenda_item,
-            url,
-            title,
-            description,
-        };
-
-        let venue = Venue {
-            venue_id: "spot_groningen".to_string(),
-            name: "Spot Groningen".to_string(),
-        };
-
-        Ok(VenueScraper {
-            client,
-            http_sender,
-            agenda_urls,
-            venue,
-            css_selectors,
-            db,
-        })
+    pub fn venue(&self) -> &Venue {
+        &self.venue
     }
 
+    /// Sync the agenda of the venue, incrementally when the venue syncs incrementally and its last
+    /// full sync is recent, otherwise in full.
     pub async fn sync(&self) -> Result<SyncingResult, ErrorKind> {
-        info!("Syncing venue {}", self.venue);
-        let mut number_of_agenda_last_iteration = 0;
-        let mut needs_next_page = true;
+        self.sync_with_partial_result()
+            .await
+            .map_err(|partial_sync| partial_sync.error)
+    }
+
+    /// Like sync, a failed sync also gives what it did until it failed, like the urls it fetched
+    /// and their retries.
+    pub async fn sync_with_partial_result(&self) -> Result<SyncingResult, PartialSync> {
         let mut sync_results = SyncingResult::with_zeroes();
+        let synced = match self.scheduled_sync_mode().await {
+            Ok(sync_mode) => self.sync_pages(sync_mode, &mut sync_results).await,
+            Err(err) => Err(err),
+        };
+        match synced {
+            Ok(()) => Ok(sync_results),
+            Err(error) => Err(PartialSync {
+                error,
+                result: sync_results,
+            }),
+        }
+    }
 
-        for agenda_url in self.agenda_urls.iter() {
-            if !needs_next_page {
-                continue;
+    /// The mode of the next sync. The start of the last full sync is the last time an agenda item
+    /// was seen, only full syncs mark the items they see.
+    pub async fn scheduled_sync_mode(&self) -> Result<SyncMode, ErrorKind> {
+        let incremental = match &self.incremental {
+            Some(incremental) => incremental,
+            None => return Ok(SyncMode::Full),
+        };
+        let last_full_sync_at = self
+            .store
+            .agenda_items_for_venue(&self.venue.venue_id)
+            .await?
+            .iter()
+            .filter_map(|agenda| agenda.last_seen_at)
+            .max();
+        match last_full_sync_at {
+            Some(last_full_sync_at)
+                if Utc::now() - last_full_sync_at
+                    < Duration::hours(incremental.full_sync_hours) =>
+            {
+                Ok(SyncMode::Incremental {
+                    known_pages: incremental.known_pages,
+                })
             }
-            let mut number_of_agenda_items = 0;
-            let mut number_of_unparseable_agenda_items = 0;
+            _ => Ok(SyncMode::Full),
+        }
+    }
 
-            let body = trace_span!("fetching_url", agenda_url=agenda_url, venue=?self.venue)
-                .in_scope(|| async {
-                    get_body_for_url(&self.client, &self.http_sender, &agenda_url).await
-                })
-                .await?;
+    /// Sync the agenda pages of the venue, following the pagination of the venue from the agenda
+    /// url until the last page. Incremental syncs stop early at pages with known items only.
+    pub async fn sync_with_mode(&self, sync_mode: SyncMode) -> Result<SyncingResult, ErrorKind> {
+        let mut sync_results = SyncingResult::with_zeroes();
+        self.sync_pages(sync_mode, &mut sync_results).await?;
+        Ok(sync_results)
+    }
 
-            let parsed_html =
-                trace_span!("parsing_document").in_scope(|| Html::parse_document(&body));
-
-            let agenda_res = trace_span!("doc_to_agenda_items").in_scope(|| {
-                parsed_html
-                    .select(&self.css_selectors.agenda_item)
-                    .map(|agenda_item_element| {
-                        parser::agenda_from_element(
-                            &agenda_item_element,
-                            &self.css_selectors,
-                            &self.venue.venue_id,
-                        )
+    /// Sync the agenda pages into the results, which keep what was done when the sync fails.
+    async fn sync_pages(
+        &self,
+        sync_mode: SyncMode,
+        sync_results: &mut SyncingResult,
+    ) -> Result<(), ErrorKind> {
+        info!("Syncing venue {} {:?}", self.venue, sync_mode);
+        let sync_started_at = Utc::now();
+        let mut seen_urls = HashSet::new();
+        let mut visited_page_urls = HashSet::new();
+        let mut page_number = 0;
+        let mut consecutive_known_pages = 0;
+        // The listing has more pages than the venue allows to fetch.
+        let mut truncated = false;
+        let mut next_page_url = Some(self.pagination.first_page_url(&self.agenda_url)?);
+
+        while let Some(agenda_url) = next_page_url.take() {
+            page_number += 1;
+            visited_page_urls.insert(agenda_url.clone());
+            let number_of_agenda_items;
+            let number_of_unparseable_agenda_items;
+            let number_of_seen_urls = seen_urls.len();
+            let number_of_inserted_items = sync_results.total_items_inserted;
+
+            let fetched_body =
+                trace_span!("fetching_url", agenda_url=agenda_url, venue=?self.venue)
+                    .in_scope(|| async {
+                        fetch_body_for_url(&self.client, &self.http_sender, &agenda_url).await
                     })
-                    .filter_map(|it| {
-                        number_of_agenda_items += 1;
-                        match it {
-                            Ok(agenda_item) => Some(agenda_item),
-                            Err(err) => {
-                                number_of_unparseable_agenda_items += 1;
-                                warn!("Cannot parse an item {}", err);
-                                None
+                    .await;
+            let fetched_body = match fetched_body {
+                Ok(fetched_body) => fetched_body,
+                Err(ErrorKind::StatusCodeFromUrl {
+                    status_code: 404, ..
+                }) if page_number > 1 => {
+                    info!("Page {} not found, the last page", agenda_url);
+                    sync_results
+                        .fetched_urls
+                        .push(FetchedUrl::fetched(&agenda_url, 404));
+                    break;
+                }
+                Err(err) => {
+                    if let ErrorKind::RetriesExhausted { retries, .. } = err {
+                        sync_results.total_retries += retries;
+                    }
+                    sync_results.total_urls_fetched += 1;
+                    sync_results.total_urls_unfetchable += 1;
+                    sync_results
+                        .fetched_urls
+                        .push(FetchedUrl::failed(&agenda_url, &err));
+                    return Err(err);
+                }
+            };
+            sync_results.total_retries += fetched_body.retries;
+            sync_results
+                .fetched_urls
+                .push(FetchedUrl::fetched(&agenda_url, fetched_body.status_code));
+            let unchanged_page = match fetched_body.cache_hit {
+                Some(cache_hit) => {
+                    sync_results.total_cache_hits += 1;
+                    sync_results.total_bytes_from_cache += cache_hit.body_size;
+                    self.store
+                        .get_listing_page(&agenda_url)
+                        .await?
+                        .filter(|listing_page| listing_page.definition_hash == self.definition_hash)
+                }
+                None => None,
+            };
+
+            let body = fetched_body.body;
+            let next_link = if let Some(listing_page) = unchanged_page {
+                // Neither the page nor the definition changed since it was parsed, its agenda
+                // items are stored.
+                trace!("Skip parsing unchanged page {}", agenda_url);
+                number_of_agenda_items = listing_page.agenda_urls.len() as u32
+                    + listing_page.number_of_unparseable_items;
+                number_of_unparseable_agenda_items = listing_page.number_of_unparseable_items;
+                sync_results.total_unparseable_dates += listing_page.number_of_unparseable_dates;
+                sync_results
+                    .parse_failures
+                    .extend(listing_page.parse_failures);
+                seen_urls.extend(listing_page.agenda_urls);
+                if self.pagination.follows_links() {
+                    self.pagination
+                        .find_next_link(&Html::parse_document(&body), &agenda_url)
+                } else {
+                    None
+                }
+            } else {
+                let page_html = self.pagination.page_html(page_number, &body)?;
+                let page_url = Url::parse(&agenda_url)?;
+
+                // The parsed document cannot be sent between threads, so it does not live
+                // across the awaits of storing the agenda items.
+                let (agenda_items, mut parse_failures, next_link) =
+                    trace_span!("doc_to_agenda_items").in_scope(|| {
+                        let parsed_html = trace_span!("parsing_document")
+                            .in_scope(|| Html::parse_document(&page_html));
+                        let (agenda_items, parse_failures) =
+                            self.agenda_items_from_page(&page_html, &parsed_html, &page_url)?;
+                        let next_link = self.pagination.find_next_link(&parsed_html, &agenda_url);
+                        Ok::<_, ErrorKind>((agenda_items, parse_failures, next_link))
+                    })?;
+                number_of_agenda_items = (agenda_items.len() + parse_failures.len()) as u32;
+                number_of_unparseable_agenda_items = parse_failures.len() as u32;
+                let mut number_of_unparseable_dates = 0;
+                let mut agenda_urls = Vec::new();
+
+                trace_span!("store_agenda_items")
+                    .in_scope(|| async {
+                        for mut agenda in agenda_items {
+                            // Structured data and JSON have exact dates, there is no text to parse.
+                            if agenda.starts_at.is_none() {
+                                if let Err(err) = apply_event_dates(&mut agenda, Utc::now()) {
+                                    number_of_unparseable_dates += 1;
+                                    warn!("Cannot parse the dates of {}: {}", agenda.url, err);
+                                    parse_failures.push(ParseFailure::new(
+                                        &agenda_url,
+                                        Some(&agenda.url),
+                                        err,
+                                    ));
+                                }
                             }
-                        }
-                    })
-                    .into_iter()
-            });
-
-            trace_span!("store_agenda_items")
-                .in_scope(|| async {
-                    for agenda in agenda_res {
-                        let nw_agenda = insert_or_get_agenda(&agenda, &self.db).await;
-                        match nw_agenda {
-                            Ok(nw_agenda_result) => {
-                                if nw_agenda_result.inserted {
+                            agenda.listing_url = Some(agenda_url.clone());
+                            seen_urls.insert(agenda.url.clone());
+                            agenda_urls.push(agenda.url.clone());
+                            match self.store.upsert_agenda(&agenda).await {
+                                Ok(upsert_result) if upsert_result.inserted => {
                                     sync_results.total_items_inserted += 1;
                                 }
+                                Ok(upsert_result) if upsert_result.updated => {
+                                    info!("Agenda item changed {}", upsert_result.agenda);
+                                    sync_results.total_items_updated += 1;
+                                }
+                                Ok(_) => {}
+                                Err(err) => warn!("Cannot store {}: {}", agenda.url, err),
                             }
-                            Err(_) => {}
                         }
-                    }
-                })
-                .await;
+                    })
+                    .await;
+
+                sync_results.total_unparseable_dates += number_of_unparseable_dates;
+                sync_results.parse_failures.extend(parse_failures.clone());
+                let listing_page = ListingPage {
+                    url: agenda_url.clone(),
+                    venue_id: self.venue.venue_id.clone(),
+                    definition_hash: self.definition_hash.clone(),
+                    agenda_urls,
+                    number_of_unparseable_items: number_of_unparseable_agenda_items,
+                    number_of_unparseable_dates,
+                    parse_failures,
+                };
+                if let Err(err) = self.store.upsert_listing_page(&listing_page).await {
+                    warn!("Cannot store the listing page {}: {}", agenda_url, err);
+                }
+                next_link
+            };
 
             sync_results.total_items += number_of_agenda_items;
             sync_results.total_urls_fetched += 1;
             sync_results.total_unparseable_items += number_of_unparseable_agenda_items;
-
-            needs_next_page = number_of_agenda_items >= number_of_agenda_last_iteration;
-            number_of_agenda_last_iteration = number_of_agenda_items;
             info!("number of results {}", sync_results);
+
+            if number_of_agenda_items > 0
+                && sync_results.total_items_inserted == number_of_inserted_items
+            {
+                consecutive_known_pages += 1;
+            } else {
+                consecutive_known_pages = 0;
+            }
+            if let SyncMode::Incremental { known_pages } = sync_mode {
+                if consecutive_known_pages >= known_pages {
+                    info!(
+                        "Stopped at {} after {} pages without new agenda items",
+                        agenda_url, consecutive_known_pages
+                    );
+                    break;
+                }
+            }
+
+            let page = Page {
+                number: page_number,
+                url: &agenda_url,
+                body: &body,
+                next_link,
+                number_of_items: number_of_agenda_items,
+                number_of_new_items: (seen_urls.len() - number_of_seen_urls) as u32,
+                total_items: seen_urls.len() as u32,
+            };
+            next_page_url = match self.pagination.next_page(&self.agenda_url, &page) {
+                NextPage::Url(page_url) if !visited_page_urls.contains(&page_url) => Some(page_url),
+                NextPage::Url(_) | NextPage::Last => None,
+                NextPage::MaxPagesReached => {
+                    truncated = true;
+                    None
+                }
+            };
+        }
+
+        if sync_mode == SyncMode::Full {
+            sync_results.selector_health = Some(
+                self.record_selector_health(sync_results, &seen_urls, sync_started_at)
+                    .await?,
+            );
+        }
+
+        // All pages are walked, items not seen are no longer on the listing.
+        if sync_mode != SyncMode::Full {
+            info!(
+                "Incremental sync of {}, not marking removed items",
+                self.venue
+            );
+        } else if truncated {
+            warn!(
+                "Not all pages of {} are fetched, not marking removed items",
+                self.venue
+            );
+        } else if seen_urls.is_empty() {
+            warn!(
+                "No agenda items seen for {}, not marking removed items",
+                self.venue
+            );
+        } else if let Some(layout_change_suspected) = sync_results
+            .selector_health
+            .as_ref()
+            .and_then(|health| health.layout_change_suspected.as_ref())
+        {
+            warn!(
+                "{} of {}, not marking removed items",
+                layout_change_suspected, self.venue
+            );
+        } else if sync_results.total_unparseable_items as f64
+            > sync_results.total_items as f64 * MAX_UNPARSEABLE_RATIO_TO_MARK_REMOVED
+        {
+            warn!(
+                "{} of {} agenda items of {} are unparseable, not marking removed items",
+                sync_results.total_unparseable_items, sync_results.total_items, self.venue
+            );
+        } else {
+            sync_results.total_items_removed = self
+                .mark_disappeared_agenda(&seen_urls, sync_started_at)
+                .await?;
         }
 
         info!("Sync completed {} {}", self.venue, sync_results);
-        Ok(sync_results)
+        Ok(())
     }
 
-    pub async fn sync_details(&self) -> Result<SyncingResult, ErrorKind> {
-        // fetch Agenda items that need details from store.
-        let mut sync_results = SyncingResult::with_zeroes();
+    /// The agenda items on the listing page and the items that cannot be parsed, from the JSON of
+    /// the page for venues with a json source, otherwise from its html.
+    fn agenda_items_from_page(
+        &self,
+        page: &str,
+        document: &Html,
+        page_url: &Url,
+    ) -> Result<(Vec<Agenda>, Vec<ParseFailure>), ErrorKind> {
+        match &self.json_source {
+            Some(json_source) => self.agenda_items_from_json(json_source, page, document, page_url),
+            None => Ok(self.agenda_items_from_document(document, page_url)),
+        }
+    }
 
-        let mut cursor = execute_on_agenda_items(&self.venue.venue_id, &self.db).await?;
-        while let Some(mut agenda) = cursor.try_next().await? {
-            if !agenda.needs_details {
-                continue;
-            }
-            sync_results.total_urls_fetched += 1;
-            let details_body = get_body_for_url(&self.client, &self.http_sender, &agenda.url).await;
-            match details_body {
-                Ok(body) => {
-                    let html_document = Html::parse_document(&body);
-
-                    // update other fields.
-
-                    agenda.needs_details = false;
-                    match update_agenda(&agenda, &self.db).await {
-                        Ok(_) => sync_results.total_items_updated += 1,
-                        _ => (),
-                    };
+    /// Check the selectors of the venue against the first page of its agenda, nothing is stored.
+    pub async fn check_selectors(&self) -> Result<SelectorCheck, ErrorKind> {
+        let page_url = self.pagination.first_page_url(&self.agenda_url)?;
+        let fetched_body = fetch_body_for_url(&self.client, &self.http_sender, &page_url).await?;
+        let body = self.pagination.page_html(1, &fetched_body.body)?;
+        self.check_page(&page_url, &body)
+    }
+
+    /// Check the selectors of the venue against a saved agenda page, links are resolved against
+    /// the agenda url of the venue.
+    pub fn check_file(&self, path: &Path) -> Result<SelectorCheck, ErrorKind> {
+        let page = fs::read_to_string(path).map_err(|err| ErrorKind::IoError {
+            message: format!("{}: {}", path.display(), err),
+        })?;
+        let page_url = self.pagination.first_page_url(&self.agenda_url)?;
+        let body = self.pagination.page_html(1, &page)?;
+        self.check_page(&page_url, &body)
+    }
+
+    /// Check the selectors of the venue against the page.
+    pub fn check_page(&self, page_url: &str, page: &str) -> Result<SelectorCheck, ErrorKind> {
+        let document = Html::parse_document(page);
+        let (agenda_items, parse_failures) =
+            self.agenda_items_from_page(page, &document, &Url::parse(page_url)?)?;
+        Ok(SelectorCheck {
+            venue_id: self.venue.venue_id.clone(),
+            page_url: page_url.to_string(),
+            number_of_items: (agenda_items.len() + parse_failures.len()) as u32,
+            number_of_unparseable_items: parse_failures.len() as u32,
+            agenda_items,
+            parse_failures,
+        })
+    }
+
+    /// The agenda items in the JSON of the listing page and the items that cannot be mapped. A
+    /// page without the JSON is an error, the site likely changed.
+    fn agenda_items_from_json(
+        &self,
+        json_source: &JsonSource,
+        page: &str,
+        document: &Html,
+        page_url: &Url,
+    ) -> Result<(Vec<Agenda>, Vec<ParseFailure>), ErrorKind> {
+        let json = json_source.json_from_page(page, document)?;
+        let mut parse_failures = Vec::new();
+        let agenda_items = json_source
+            .agenda_items(&json, &self.venue.venue_id, page_url)
+            .into_iter()
+            .filter_map(|it| match it {
+                Ok(agenda) => Some(agenda),
+                Err(err) => {
+                    warn!("Cannot map an item {}", err);
+                    parse_failures.push(ParseFailure::new(page_url.as_str(), None, err));
+                    None
                 }
+            })
+            .collect();
+        Ok((agenda_items, parse_failures))
+    }
+
+    /// The agenda items on the listing page and the items that cannot be parsed. Items in the
+    /// structured data of the page come first, the css selectors fill in their missing fields and
+    /// add the items the structured data lacks.
+    fn agenda_items_from_document(
+        &self,
+        document: &Html,
+        page_url: &Url,
+    ) -> (Vec<Agenda>, Vec<ParseFailure>) {
+        let mut parse_failures = Vec::new();
+        let mut agenda_items: Vec<Agenda> = Vec::new();
+        if self.structured_data {
+            for event in structured_data::events_from_document(document, page_url) {
+                match structured_data::agenda_from_event(&event, &self.venue.venue_id) {
+                    Some(agenda) if agenda_items.iter().all(|it| it.url != agenda.url) => {
+                        agenda_items.push(agenda)
+                    }
+                    Some(_) => {}
+                    None => {
+                        warn!("Cannot parse an event without url or name on {}", page_url);
+                        parse_failures.push(ParseFailure::new(
+                            page_url.as_str(),
+                            event.url.as_deref(),
+                            "structured data event without url or name",
+                        ));
+                    }
+                }
+            }
+        }
+        let css_selectors = match &self.css_selectors {
+            Some(css_selectors) => css_selectors,
+            None => return (agenda_items, parse_failures),
+        };
+
+        let number_of_structured_items = agenda_items.len();
+        for agenda_item_element in document.select(&css_selectors.agenda_item) {
+            let css_agenda = match parser::agenda_from_element(
+                &agenda_item_element,
+                css_selectors,
+                &self.venue.venue_id,
+                page_url,
+            ) {
+                Ok(css_agenda) => css_agenda,
                 Err(err) => {
-                    sync_results.total_urls_unfetchable += 1;
-                    info!("Cannot fetch: {}", err);
+                    let url = parser::agenda_url_from_element(
+                        &agenda_item_element,
+                        css_selectors,
+                        page_url,
+                    );
+                    // Elements the structured data already covers need not match all selectors.
+                    let is_structured = url.as_ref().is_some_and(|url| {
+                        agenda_items[..number_of_structured_items]
+                            .iter()
+                            .any(|it| &it.url == url)
+                    });
+                    if !is_structured {
+                        warn!("Cannot parse an item {}", err);
+                        parse_failures.push(
+                            ParseFailure::new(page_url.as_str(), url.as_deref(), err)
+                                .with_html(&agenda_item_element),
+                        );
+                    }
+                    continue;
                 }
+            };
+            let structured_agenda = agenda_items[..number_of_structured_items]
+                .iter_mut()
+                .find(|it| it.url == css_agenda.url);
+            match structured_agenda {
+                Some(agenda) => structured_data::fill_missing_fields(agenda, &css_agenda),
+                None => agenda_items.push(css_agenda),
             }
         }
+        (agenda_items, parse_failures)
+    }
+
+    /// Store the selector health of a full sync, compared with the earlier full syncs of the venue.
+    async fn record_selector_health(
+        &self,
+        sync_results: &SyncingResult,
+        seen_urls: &HashSet<String>,
+        checked_at: DateTime<Utc>,
+    ) -> Result<SelectorHealth, ErrorKind> {
+        let venue_id = &self.venue.venue_id;
+        let number_of_items_without_description = self
+            .store
+            .agenda_items_for_venue(venue_id)
+            .await?
+            .iter()
+            .filter(|agenda| seen_urls.contains(&agenda.url) && agenda.description.is_none())
+            .count() as u32;
+        let history = self
+            .store
+            .selector_health_for_venue(venue_id, HEALTH_HISTORY_SIZE)
+            .await?;
+        let selector_health = SelectorHealth {
+            venue_id: venue_id.clone(),
+            checked_at,
+            number_of_items: sync_results.total_items,
+            number_of_unparseable_items: sync_results.total_unparseable_items,
+            number_of_items_without_description,
+            layout_change_suspected: None,
+        }
+        .compare_with_history(&history);
+        if selector_health.layout_change_suspected.is_some() {
+            warn!("{}", selector_health);
+        }
+        self.store.insert_selector_health(&selector_health).await?;
+        Ok(selector_health)
+    }
+
+    /// Update the status and last_seen_at of all agenda items of the venue after a full sync. The
+    /// seen items get their last_seen_at in a single write, only the items whose status changes are
+    /// written one by one. Items missing for longer than the grace period are marked removed, or
+    /// past when they took place. Returns the number of items marked.
+    async fn mark_disappeared_agenda(
+        &self,
+        seen_urls: &HashSet<String>,
+        seen_at: DateTime<Utc>,
+    ) -> Result<u32, ErrorKind> {
+        let mut total_items_removed = 0;
+        let agenda_items = self
+            .store
+            .agenda_items_for_venue(&self.venue.venue_id)
+            .await?;
+        let urls: Vec<String> = seen_urls.iter().cloned().collect();
+        self.store.update_last_seen_at(&urls, seen_at).await?;
+        for mut agenda in agenda_items {
+            if seen_urls.contains(&agenda.url) {
+                let status = if agenda.is_marked_cancelled() {
+                    AgendaStatus::Cancelled
+                } else {
+                    AgendaStatus::Active
+                };
+                if agenda.status == status {
+                    continue;
+                }
+                agenda.last_seen_at = Some(seen_at);
+                agenda.status = status;
+            } else {
+                match agenda.last_seen_at {
+                    // Stored before last_seen_at existed, the grace period starts now.
+                    None => agenda.last_seen_at = Some(seen_at),
+                    Some(last_seen_at)
+                        if agenda.status == AgendaStatus::Active
+                            && seen_at - last_seen_at >= self.removal_grace_period =>
+                    {
+                        agenda.status = if agenda.took_place_before(seen_at) {
+                            AgendaStatus::Past
+                        } else {
+                            AgendaStatus::Removed
+                        };
+                        agenda.updated_at = Some(seen_at);
+                        total_items_removed += 1;
+                        info!("Agenda item no longer listed {}", agenda);
+                    }
+                    Some(_) => continue,
+                }
+            }
+            self.store.update_agenda(&agenda).await?;
+        }
+        Ok(total_items_removed)
+    }
+
+    /// Fetch the details of the agenda items that need them, at most max_concurrent_details at the
+    /// same time. A details page that cannot be fetched does not stop the others.
+    pub async fn sync_details(&self) -> Result<SyncingResult, ErrorKind> {
+        let agenda_items = self
+            .store
+            .agenda_items_for_venue(&self.venue.venue_id)
+            .await?
+            .into_iter()
+            .filter(|agenda| agenda.needs_details);
+
+        let mut sync_results = SyncingResult::with_zeroes();
+        let mut details_results = stream::iter(agenda_items)
+            .map(|agenda| self.sync_agenda_details(agenda))
+            .buffer_unordered(self.max_concurrent_details);
+        while let Some(details_result) = details_results.next().await {
+            sync_results.add(&details_result);
+        }
 
         info!("Details sync completed, results {}", sync_results);
         Ok(sync_results)
     }
+
+    /// Fetch the details page of a single agenda item and store the details.
+    async fn sync_agenda_details(&self, mut agenda: Agenda) -> SyncingResult {
+        let mut sync_results = SyncingResult::with_zeroes();
+        sync_results.total_urls_fetched += 1;
+        let details_url = agenda.details_page_url().to_string();
+        let details_body = fetch_body_for_url(&self.client, &self.http_sender, &details_url).await;
+        match details_body {
+            Ok(fetched_body) => {
+                sync_results.total_retries += fetched_body.retries;
+                sync_results
+                    .fetched_urls
+                    .push(FetchedUrl::fetched(&details_url, fetched_body.status_code));
+                if let Some(cache_hit) = fetched_body.cache_hit {
+                    sync_results.total_cache_hits += 1;
+                    sync_results.total_bytes_from_cache += cache_hit.body_size;
+                }
+                let details_event = {
+                    let html_document = Html::parse_document(&fetched_body.body);
+                    parser::details_from_document(
+                        &html_document,
+                        &self.detail_selectors,
+                        &mut agenda,
+                    );
+                    match Url::parse(&details_url) {
+                        Ok(page_url) if self.structured_data => {
+                            structured_data::details_event(&html_document, &page_url)
+                        }
+                        _ => None,
+                    }
+                };
+                if let Err(err) = apply_event_dates(&mut agenda, Utc::now()) {
+                    sync_results.total_unparseable_dates += 1;
+                    warn!("Cannot parse the dates of {}: {}", agenda.url, err);
+                    sync_results.parse_failures.push(ParseFailure::new(
+                        &details_url,
+                        Some(&agenda.url),
+                        err,
+                    ));
+                }
+                // The structured data is exact, it wins over the selected texts.
+                if let Some(details_event) = details_event {
+                    structured_data::apply_event(&details_event, &mut agenda);
+                }
+
+                agenda.needs_details = false;
+                if self.store.update_agenda(&agenda).await.is_ok() {
+                    sync_results.total_items_updated += 1;
+                }
+            }
+            Err(err) => {
+                if let ErrorKind::RetriesExhausted { retries, .. } = err {
+                    sync_results.total_retries += retries;
+                }
+                sync_results.total_urls_unfetchable += 1;
+                info!("Cannot fetch: {}", err);
+                sync_results
+                    .fetched_urls
+                    .push(FetchedUrl::failed(&details_url, &err));
+            }
+        }
+        sync_results
+    }
 }
 
+/// Sync all venues in the venue definitions, at most max_concurrent_venues at the same time, and
+/// return the added up results.
 pub async fn sync_venues(
     client: &Client,
-    db: &Database,
-    http_sender: Rc<dyn HttpSender>,
+    store: Arc<dyn AgendaStore>,
+    http_sender: Arc<dyn HttpSender>,
+    venue_definitions: &[VenueDefinition],
+    max_concurrent_venues: usize,
 ) -> Result<SyncingResult, ErrorKind> {
     trace!("sync_venues");
 
-    let tivoli_syncer = VenueScraper::tivoli_with_sender_and_client(
-        Rc::clone(&http_sender),
-        client.clone(),
-        db.clone(),
-    )?;
-    let spot_groningen_syncer = VenueScraper::spot_groningen_with_sender_and_client(
-        Rc::clone(&http_sender),
-        client.clone(),
-        db.clone(),
-    )?;
-
-    let mut sync_results = SyncingResult::with_zeroes();
-    let (spot_result, tivoli_result) = join!(spot_groningen_syncer.sync(), tivoli_syncer.sync());
-
-    for result in [spot_result, tivoli_result] {
-        match result {
-            Err(err) => error!("Error syncing venue {}", err),
-            Ok(results) => {
-                sync_results.add(&results);
-            }
-        }
-    }
-
-    let details_result = spot_groningen_syncer.sync_details().await;
-    match details_result {
-        Ok(results) => sync_results.add(&results),
-        Err(err) => error!("Error syncing ddetails {}", err),
+    let venue_registry =
+        VenueRegistry::from_definitions(venue_definitions, http_sender, client, store)?
+            .with_max_concurrent_venues(max_concurrent_venues);
+    let sync_report = venue_registry.sync_all().await;
+    for failed_venue in sync_report.failed_venues() {
+        error!("Sync of venue failed: {}", failed_venue);
     }
 
+    let sync_results = sync_report.total();
     info!("Total {}", sync_results);
     Ok(sync_results)
 }
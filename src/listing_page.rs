@@ -0,0 +1,18 @@
+use crate::ParseFailure;
+use serde::{Deserialize, Serialize};
+
+/// What the last parse of a listing page of a venue found, so the page is not parsed again while
+/// neither the page nor the venue definition changed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListingPage {
+    pub url: String,
+    pub venue_id: String,
+    /// The hash of the venue definition the page was parsed with.
+    pub definition_hash: String,
+    /// The urls of the agenda items on the page.
+    pub agenda_urls: Vec<String>,
+    pub number_of_unparseable_items: u32,
+    pub number_of_unparseable_dates: u32,
+    /// The agenda items, and dates of agenda items, on the page that cannot be parsed.
+    pub parse_failures: Vec<ParseFailure>,
+}
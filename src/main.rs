@@ -1,31 +1,573 @@
-use std::env::var_os;
 use std::error::Error;
-use std::rc::Rc;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-use tracing::info;
-use venue_scraper_api::agenda::create_mongo_connection;
+use chrono::{Duration, Utc};
+use clap::{Parser, Subcommand, ValueEnum};
+use reqwest::Client;
+use serde_json::json;
+use tracing::{info, warn};
+use tracing_subscriber::EnvFilter;
+use venue_scraper_api::api::serve;
+use venue_scraper_api::cache::CachingHttpSender;
 use venue_scraper_api::config::Config;
-use venue_scraper_api::http_sender::DefaultHttpSender;
+use venue_scraper_api::errors::ErrorKind;
+use venue_scraper_api::export::export_icalendar;
+use venue_scraper_api::fixtures::RecordingHttpSender;
+use venue_scraper_api::health::{
+    LayoutChangeAlert, LogLayoutChangeAlert, WebhookLayoutChangeAlert,
+};
+use venue_scraper_api::http_sender::{DefaultHttpSender, HttpSender};
+use venue_scraper_api::politeness::PoliteHttpSender;
+use venue_scraper_api::registry::{RegistrySyncReport, VenueRegistry, VenueSyncReport};
+use venue_scraper_api::retry::RetryingHttpSender;
+use venue_scraper_api::stats::venue_stats;
+use venue_scraper_api::store::dry_run::{AgendaChange, DryRunAgendaStore};
+use venue_scraper_api::store::memory::MemoryAgendaStore;
+use venue_scraper_api::store::{create_store, AgendaStore};
+use venue_scraper_api::sync_run::daily_trends;
+use venue_scraper_api::venue_definition::{load_venue_definitions, VenueDefinition};
+use venue_scraper_api::{SelectorCheck, SyncingResult, VenueScraper};
 
-use venue_scraper_api::sync_venues;
+/// Directory of the test fixtures, the record command writes into it.
+const FIXTURES_DIR: &str = "tests/files";
+
+/// Scrapes the agendas of venues into the agenda store.
+#[derive(Parser)]
+#[command(name = "venue_scraper", version)]
+struct Cli {
+    /// The venues file, instead of VENUES_CONFIG.
+    #[arg(long, global = true, value_name = "PATH")]
+    config: Option<PathBuf>,
+    #[arg(long, global = true, value_enum, default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
+    /// Sync without writing to the agenda store, and report what would have been written.
+    #[arg(long, global = true)]
+    dry_run: bool,
+    #[arg(long, global = true, value_enum, default_value_t = OutputFormat::Text)]
+    output: OutputFormat,
+    /// Sync all venues when no command is given.
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Sync the agenda of all venues, or only of the given venues, and their details.
+    Sync {
+        #[arg(long = "venue", value_name = "ID")]
+        venues: Vec<String>,
+    },
+    /// Fetch the details of the agenda items that need them.
+    SyncDetails {
+        #[arg(long = "venue", value_name = "ID")]
+        venues: Vec<String>,
+    },
+    /// List the venues of the venues file.
+    ListVenues,
+    /// Export iCalendar feeds of the stored agenda items.
+    Export {
+        #[arg(default_value = "export")]
+        output_dir: PathBuf,
+    },
+    /// Show counts of the stored agenda items per venue.
+    Stats {
+        #[arg(long = "venue", value_name = "ID")]
+        venues: Vec<String>,
+    },
+    /// Show the selector health of the last full syncs per venue, fails when the layout of a venue
+    /// likely changed in its last full sync.
+    Health {
+        #[arg(long = "venue", value_name = "ID")]
+        venues: Vec<String>,
+        /// The number of full syncs to show per venue.
+        #[arg(long, default_value_t = 1)]
+        runs: usize,
+    },
+    /// Show the sync runs of the last days per venue, or their daily trends, or the last successful
+    /// sync run per venue.
+    Runs {
+        #[arg(long = "venue", value_name = "ID")]
+        venues: Vec<String>,
+        #[arg(long, default_value_t = 7)]
+        days: i64,
+        /// Add up the sync runs per day.
+        #[arg(long)]
+        trends: bool,
+        #[arg(long, conflicts_with_all = ["days", "trends"])]
+        last_successful: bool,
+    },
+    /// Check the selectors against the first agenda page of the venues, or against a saved agenda
+    /// page of one venue, nothing is stored.
+    CheckSelectors {
+        #[arg(long = "venue", value_name = "ID")]
+        venues: Vec<String>,
+        /// A saved agenda page, its links are resolved against the agenda url of the venue.
+        #[arg(long, value_name = "PATH")]
+        file: Option<PathBuf>,
+    },
+    /// Serve the agenda api.
+    Serve {
+        #[arg(default_value = "127.0.0.1:8080")]
+        address: SocketAddr,
+    },
+    /// Record the pages of a venue as a test case in the test fixtures.
+    Record { venue_id: String, test_case: String },
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum LogFormat {
+    Text,
+    Json,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    Text,
+    Json,
+}
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
-    if var_os("RUST_LOG").is_none() {
-        tracing_subscriber::fmt::init();
-    } else {
-        tracing_subscriber::fmt::init();
+    let cli = Cli::parse();
+    // The output goes to stdout, the logs to stderr.
+    let subscriber = tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .with_writer(std::io::stderr);
+    match cli.log_format {
+        LogFormat::Text => subscriber.init(),
+        LogFormat::Json => subscriber.json().init(),
     }
 
     info!("Starting application {}", env!("CARGO_PKG_VERSION"));
-    let config = Config::from_environment();
-    let client = reqwest::Client::new();
-    let db = create_mongo_connection(&config).await?;
-    let http_sender = Rc::new(DefaultHttpSender);
+    let mut config = Config::from_environment();
+    if let Some(venues_config) = &cli.config {
+        config.venues_config = venues_config.display().to_string();
+    }
+    let venue_definitions = load_venue_definitions(Path::new(&config.venues_config))?;
+    let output = cli.output;
+
+    match cli.command.unwrap_or(Command::Sync { venues: Vec::new() }) {
+        Command::Sync { venues } => {
+            let (store, dry_run_store) = store(&config, cli.dry_run).await?;
+            let registry = registry(&config, &venue_definitions, &venues, store, cli.dry_run)?
+                .with_layout_change_alert(layout_change_alert(&config, cli.dry_run)?);
+            info!("Start sync of the venues");
+            let sync_report = registry.sync_all().await;
+            print_sync_report(&sync_report, dry_run_store.as_deref(), output).await?;
+        }
+        Command::SyncDetails { venues } => {
+            let (store, dry_run_store) = store(&config, cli.dry_run).await?;
+            let registry = registry(&config, &venue_definitions, &venues, store, cli.dry_run)?;
+            info!("Start details sync of the venues");
+            let sync_report = registry.sync_all_details().await;
+            print_sync_report(&sync_report, dry_run_store.as_deref(), output).await?;
+        }
+        Command::ListVenues => {
+            for venue_definition in &venue_definitions {
+                match output {
+                    OutputFormat::Text => println!(
+                        "{}\t{}\t{}\t{}",
+                        venue_definition.id,
+                        venue_definition.name,
+                        venue_definition.agenda_url,
+                        venue_definition.tags.join(",")
+                    ),
+                    OutputFormat::Json => println!(
+                        "{}",
+                        json!({
+                            "id": venue_definition.id,
+                            "name": venue_definition.name,
+                            "agenda_url": venue_definition.agenda_url,
+                            "address": venue_definition.address,
+                            "tags": venue_definition.tags,
+                            "fetch_details": venue_definition.fetch_details,
+                        })
+                    ),
+                }
+            }
+        }
+        Command::Export { output_dir } => {
+            let store = create_store(&config).await?;
+            let paths = export_icalendar(store.as_ref(), &venue_definitions, &output_dir).await?;
+            info!(
+                "Exported {} calendars to {}",
+                paths.len(),
+                output_dir.display()
+            );
+            for path in paths {
+                println!("{}", path.display());
+            }
+        }
+        Command::Stats { venues } => {
+            let store = create_store(&config).await?;
+            let venue_definitions = selected_definitions(&venue_definitions, &venues)?;
+            for stats in venue_stats(store.as_ref(), &venue_definitions).await? {
+                match output {
+                    OutputFormat::Text => println!("{}", stats),
+                    OutputFormat::Json => println!("{}", serde_json::to_string(&stats)?),
+                }
+            }
+        }
+        Command::Health { venues, runs } => {
+            let store = create_store(&config).await?;
+            let mut suspected_venues = Vec::new();
+            for venue_definition in selected_definitions(&venue_definitions, &venues)? {
+                let venue_id = &venue_definition.id;
+                let history = store.selector_health_for_venue(venue_id, runs).await?;
+                if history.is_empty() {
+                    match output {
+                        OutputFormat::Text => println!("{}: no full sync yet", venue_id),
+                        OutputFormat::Json => {
+                            println!("{}", json!({"venue_id": venue_id, "health": null}))
+                        }
+                    }
+                }
+                for selector_health in &history {
+                    match output {
+                        OutputFormat::Text => println!("{}", selector_health),
+                        OutputFormat::Json => {
+                            println!("{}", serde_json::to_string(selector_health)?)
+                        }
+                    }
+                }
+                if let Some(last_health) = history.first() {
+                    if last_health.layout_change_suspected.is_some() {
+                        suspected_venues.push(venue_id.clone());
+                    }
+                }
+            }
+            if !suspected_venues.is_empty() {
+                return Err(format!(
+                    "Layout change suspected for {}",
+                    suspected_venues.join(", ")
+                )
+                .into());
+            }
+        }
+        Command::Runs {
+            venues,
+            days,
+            trends,
+            last_successful,
+        } => {
+            let store = create_store(&config).await?;
+            let since = Utc::now() - Duration::days(days);
+            for venue_definition in selected_definitions(&venue_definitions, &venues)? {
+                let venue_id = &venue_definition.id;
+                if last_successful {
+                    let sync_run = store.last_successful_sync_run(venue_id).await?;
+                    match (output, sync_run) {
+                        (OutputFormat::Text, Some(sync_run)) => println!("{}", sync_run),
+                        (OutputFormat::Text, None) => {
+                            println!("{}: no successful sync run yet", venue_id)
+                        }
+                        (OutputFormat::Json, sync_run) => {
+                            println!("{}", json!({"venue_id": venue_id, "sync_run": sync_run}))
+                        }
+                    }
+                    continue;
+                }
+                let sync_runs = store.sync_runs_for_venue(venue_id, since).await?;
+                if trends {
+                    for trend in daily_trends(&sync_runs) {
+                        match output {
+                            OutputFormat::Text => println!("{}", trend),
+                            OutputFormat::Json => println!("{}", serde_json::to_string(&trend)?),
+                        }
+                    }
+                } else {
+                    for sync_run in &sync_runs {
+                        match output {
+                            OutputFormat::Text => println!("{}", sync_run),
+                            OutputFormat::Json => {
+                                println!("{}", serde_json::to_string(sync_run)?)
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        Command::CheckSelectors { venues, file } => {
+            if file.is_some() && venues.len() != 1 {
+                return Err("Checking a file needs exactly one --venue".into());
+            }
+            // Checking stores nothing, the scrapers get a store of their own.
+            let store = Arc::new(MemoryAgendaStore::new());
+            let registry = registry(&config, &venue_definitions, &venues, store, true)?;
+            let mut failed_venues = Vec::new();
+            for venue_id in registry.venue_ids() {
+                let scraper = registry.scraper(venue_id).unwrap();
+                let selector_check = match &file {
+                    Some(file) => scraper.check_file(file),
+                    None => scraper.check_selectors().await,
+                };
+                match (&selector_check, output) {
+                    (Ok(check), OutputFormat::Text) => print_selector_check(check),
+                    (Ok(check), OutputFormat::Json) => {
+                        println!("{}", serde_json::to_string(check)?)
+                    }
+                    (Err(err), OutputFormat::Text) => println!("{}: failed: {}", venue_id, err),
+                    (Err(err), OutputFormat::Json) => println!(
+                        "{}",
+                        json!({"venue_id": venue_id, "error": err.to_string()})
+                    ),
+                }
+                match selector_check {
+                    Ok(check) if check.agenda_items.is_empty() => failed_venues.push(venue_id),
+                    Ok(_) => {}
+                    Err(_) => failed_venues.push(venue_id),
+                }
+            }
+            if !failed_venues.is_empty() {
+                return Err(
+                    format!("No agenda items found for {}", failed_venues.join(", ")).into(),
+                );
+            }
+        }
+        Command::Serve { address } => {
+            let store = create_store(&config).await?;
+            serve(address, store, venue_definitions).await?;
+        }
+        Command::Record {
+            venue_id,
+            test_case,
+        } => {
+            let venue_definition = venue_definitions
+                .iter()
+                .find(|venue_definition| venue_definition.id == venue_id)
+                .ok_or_else(|| ErrorKind::UnknownVenue {
+                    venue_id: venue_id.clone(),
+                })?;
+            let client = client(&config)?;
+            let polite_http_sender = PoliteHttpSender::new(
+                DefaultHttpSender,
+                client.clone(),
+                config.politeness_policy.clone(),
+            );
+            let http_sender = Arc::new(RecordingHttpSender::new(
+                polite_http_sender,
+                PathBuf::from(FIXTURES_DIR),
+                &test_case,
+            ));
+            // The store of the configuration is left alone, recording only fills the fixtures.
+            let scraper = VenueScraper::from_definition(
+                venue_definition,
+                http_sender,
+                client,
+                Arc::new(MemoryAgendaStore::new()),
+            )?;
 
-    info!("Start sync of the venues");
-    let sync_results = sync_venues(&client, &db, http_sender).await?;
-    info!("Sync results of the venues {}", sync_results);
+            info!("Recording {} as test case {}", venue_id, test_case);
+            let sync_results = scraper.sync().await?;
+            info!("Recorded the listing of {} {}", venue_id, sync_results);
+            if venue_definition.fetch_details {
+                let sync_results = scraper.sync_details().await?;
+                info!("Recorded the details of {} {}", venue_id, sync_results);
+            }
+        }
+    }
 
     Ok(())
 }
+
+fn client(config: &Config) -> Result<Client, reqwest::Error> {
+    Client::builder().user_agent(&config.user_agent).build()
+}
+
+/// The http sender of the syncs: cached, retrying and polite. Runs that store nothing only read the
+/// cache.
+fn http_sender(config: &Config, client: &Client, read_only: bool) -> Arc<dyn HttpSender> {
+    let polite_http_sender = PoliteHttpSender::new(
+        DefaultHttpSender,
+        client.clone(),
+        config.politeness_policy.clone(),
+    );
+    let retrying_http_sender =
+        RetryingHttpSender::new(polite_http_sender, config.retry_policy.clone());
+    match &config.http_cache_dir {
+        Some(http_cache_dir) => Arc::new(
+            CachingHttpSender::new(retrying_http_sender, PathBuf::from(http_cache_dir))
+                .with_read_only(read_only),
+        ),
+        None => Arc::new(retrying_http_sender),
+    }
+}
+
+/// The store of the configuration, for a dry run also as the dry run store that only records the
+/// writes.
+async fn store(
+    config: &Config,
+    dry_run: bool,
+) -> Result<(Arc<dyn AgendaStore>, Option<Arc<DryRunAgendaStore>>), ErrorKind> {
+    let store = create_store(config).await?;
+    if dry_run {
+        warn!("Dry run, nothing is stored");
+        let dry_run_store = Arc::new(DryRunAgendaStore::new(store));
+        Ok((dry_run_store.clone(), Some(dry_run_store)))
+    } else {
+        Ok((store, None))
+    }
+}
+
+/// The alert of suspected layout changes, posting to the webhook of the configuration when there is
+/// one. Dry runs only log.
+fn layout_change_alert(
+    config: &Config,
+    dry_run: bool,
+) -> Result<Arc<dyn LayoutChangeAlert>, reqwest::Error> {
+    match &config.layout_change_webhook_url {
+        Some(url) if !dry_run => Ok(Arc::new(WebhookLayoutChangeAlert::new(
+            client(config)?,
+            url,
+        ))),
+        _ => Ok(Arc::new(LogLayoutChangeAlert)),
+    }
+}
+
+/// The registry of the venues with the ids, all venues without ids. Registries that store nothing
+/// leave the http cache as it is.
+fn registry(
+    config: &Config,
+    venue_definitions: &[VenueDefinition],
+    venue_ids: &[String],
+    store: Arc<dyn AgendaStore>,
+    read_only: bool,
+) -> Result<VenueRegistry, Box<dyn Error>> {
+    let client = client(config)?;
+    let registry = VenueRegistry::from_definitions(
+        venue_definitions,
+        http_sender(config, &client, read_only),
+        &client,
+        store,
+    )?
+    .with_max_concurrent_venues(config.max_concurrent_venues);
+    if venue_ids.is_empty() {
+        Ok(registry)
+    } else {
+        Ok(registry.filter_by_ids(venue_ids)?)
+    }
+}
+
+/// The definitions of the venues with the ids, all definitions without ids.
+fn selected_definitions(
+    venue_definitions: &[VenueDefinition],
+    venue_ids: &[String],
+) -> Result<Vec<VenueDefinition>, ErrorKind> {
+    if let Some(venue_id) = venue_ids
+        .iter()
+        .find(|venue_id| !venue_definitions.iter().any(|it| &it.id == *venue_id))
+    {
+        return Err(ErrorKind::UnknownVenue {
+            venue_id: venue_id.clone(),
+        });
+    }
+    Ok(venue_definitions
+        .iter()
+        .filter(|it| venue_ids.is_empty() || venue_ids.contains(&it.id))
+        .cloned()
+        .collect())
+}
+
+/// Print the report of every venue and the total, after a dry run followed by the changes that
+/// would have been written and the items that failed to parse. Fails when a venue failed.
+async fn print_sync_report(
+    sync_report: &RegistrySyncReport,
+    dry_run_store: Option<&DryRunAgendaStore>,
+    output: OutputFormat,
+) -> Result<(), Box<dyn Error>> {
+    let total = sync_report.total();
+    let changes: Option<Vec<AgendaChange>> = match dry_run_store {
+        Some(dry_run_store) => Some(dry_run_store.changes().await?),
+        None => None,
+    };
+    match output {
+        OutputFormat::Text => {
+            for venue_report in &sync_report.venue_reports {
+                println!("{}", venue_report);
+            }
+            println!("Total {}", total);
+            if let Some(changes) = &changes {
+                println!("Dry run, {} changes not written", changes.len());
+                for change in changes {
+                    println!("{}", change);
+                }
+                for parse_failure in &total.parse_failures {
+                    println!("! {}", parse_failure);
+                }
+            }
+        }
+        OutputFormat::Json => {
+            let venue_reports: Vec<serde_json::Value> = sync_report
+                .venue_reports
+                .iter()
+                .map(venue_report_json)
+                .collect();
+            let mut report = json!({"venue_reports": venue_reports, "total": total});
+            if let Some(changes) = &changes {
+                report["changes"] = json!(changes);
+            }
+            println!("{}", report);
+        }
+    }
+
+    let failed_venues = sync_report.failed_venues();
+    if failed_venues.is_empty() {
+        Ok(())
+    } else {
+        let failed_venue_ids: Vec<&str> = failed_venues
+            .iter()
+            .map(|venue_report| venue_report.venue_id.as_str())
+            .collect();
+        Err(format!("Sync failed for {}", failed_venue_ids.join(", ")).into())
+    }
+}
+
+/// Print the agenda items found as a table, followed by the items that cannot be parsed.
+fn print_selector_check(check: &SelectorCheck) {
+    println!(
+        "{}: {} items, {} unparseable on {}",
+        check.venue_id, check.number_of_items, check.number_of_unparseable_items, check.page_url
+    );
+    // Texts of the html can span lines, the table shows them on one.
+    let single_line = |text: &str| text.split_whitespace().collect::<Vec<&str>>().join(" ");
+    let rows: Vec<(String, String, &str)> = check
+        .agenda_items
+        .iter()
+        .map(|agenda| {
+            (
+                single_line(&agenda.title),
+                single_line(agenda.start_text.as_deref().unwrap_or("-")),
+                agenda.url.as_str(),
+            )
+        })
+        .collect();
+    let title_width = rows.iter().map(|row| row.0.chars().count()).max();
+    let start_width = rows.iter().map(|row| row.1.chars().count()).max();
+    let (title_width, start_width) = (title_width.unwrap_or(0), start_width.unwrap_or(0));
+    for (title, start_text, url) in &rows {
+        println!(
+            "  {:title_width$}  {:start_width$}  {}",
+            title, start_text, url
+        );
+    }
+    for parse_failure in &check.parse_failures {
+        println!("  ! {}", parse_failure.reason);
+        if let Some(html) = &parse_failure.html {
+            println!("    {}", html);
+        }
+    }
+}
+
+fn venue_report_json(venue_report: &VenueSyncReport) -> serde_json::Value {
+    let result_json = |result: &Result<SyncingResult, ErrorKind>| match result {
+        Ok(result) => json!(result),
+        Err(err) => json!({ "error": err.to_string() }),
+    };
+    json!({
+        "venue_id": venue_report.venue_id,
+        "result": result_json(&venue_report.result),
+        "details_result": venue_report.details_result.as_ref().map(result_json),
+    })
+}
@@ -0,0 +1,237 @@
+use crate::ErrorKind;
+use scraper::{Html, Selector};
+use std::borrow::Cow;
+use tracing::{info, warn};
+use url::Url;
+
+/// Placeholder in a pagination url template that is replaced by the page number.
+pub const PAGE_PLACEHOLDER: &str = "{page}";
+/// Placeholder in a load more url template that is replaced by the number of items so far.
+pub const OFFSET_PLACEHOLDER: &str = "{offset}";
+
+/// Finds the agenda pages of a venue, compiled from its pagination definition.
+pub struct Pagination {
+    strategy: Strategy,
+    max_pages: u32,
+}
+
+/// The compiled pagination strategies, see PaginationStrategy of the venue definition.
+pub enum Strategy {
+    /// Only the agenda url.
+    SinglePage,
+    NextLink {
+        selector: Selector,
+    },
+    Numbered {
+        url_template: String,
+        first_page: u32,
+    },
+    Offset {
+        offset_parameter: String,
+        limit_parameter: String,
+        limit: u32,
+    },
+    LoadMore {
+        url_template: String,
+        first_page: u32,
+        html_pointer: String,
+        has_more_pointer: Option<String>,
+    },
+}
+
+/// What follows a fetched agenda page.
+#[derive(Debug, PartialEq, Eq)]
+pub enum NextPage {
+    Url(String),
+    /// The page is the last page of the agenda.
+    Last,
+    /// There are more pages, but the page is at max_pages.
+    MaxPagesReached,
+}
+
+/// A fetched agenda page, numbered from 1.
+pub struct Page<'a> {
+    pub number: u32,
+    pub url: &'a str,
+    pub body: &'a str,
+    /// The link to the next page, for the next link strategy.
+    pub next_link: Option<String>,
+    pub number_of_items: u32,
+    /// The items on the page that were not on the pages before it.
+    pub number_of_new_items: u32,
+    /// The items on this page and the pages before it.
+    pub total_items: u32,
+}
+
+impl Pagination {
+    pub fn new(strategy: Strategy, max_pages: u32) -> Pagination {
+        Pagination {
+            strategy,
+            max_pages,
+        }
+    }
+
+    pub fn single_page() -> Pagination {
+        Pagination::new(Strategy::SinglePage, 1)
+    }
+
+    /// The url of the first page, the agenda url with the offset parameters for the offset
+    /// strategy.
+    pub fn first_page_url(&self, agenda_url: &str) -> Result<String, ErrorKind> {
+        match &self.strategy {
+            Strategy::Offset {
+                offset_parameter,
+                limit_parameter,
+                limit,
+            } => with_query_parameters(
+                agenda_url,
+                &[
+                    (offset_parameter, "0".to_string()),
+                    (limit_parameter, limit.to_string()),
+                ],
+            ),
+            _ => Ok(agenda_url.to_string()),
+        }
+    }
+
+    /// Whether the pages must be parsed to find the next page.
+    pub fn follows_links(&self) -> bool {
+        matches!(self.strategy, Strategy::NextLink { .. })
+    }
+
+    /// The link to the next page in the document, resolved against the url of the page.
+    pub fn find_next_link(&self, document: &Html, page_url: &str) -> Option<String> {
+        let selector = match &self.strategy {
+            Strategy::NextLink { selector } => selector,
+            _ => return None,
+        };
+        let href = document.select(selector).next()?.value().attr("href")?;
+        Url::parse(page_url)
+            .and_then(|page_url| page_url.join(href.trim()))
+            .ok()
+            .map(|url| url.to_string())
+    }
+
+    /// The html with the agenda items of the page. Load more pages after the first are JSON with
+    /// the html at the html pointer.
+    pub fn page_html<'a>(
+        &self,
+        page_number: u32,
+        body: &'a str,
+    ) -> Result<Cow<'a, str>, ErrorKind> {
+        match &self.strategy {
+            Strategy::LoadMore { html_pointer, .. } if page_number > 1 => {
+                let json: serde_json::Value = serde_json::from_str(body)?;
+                match json.pointer(html_pointer) {
+                    Some(serde_json::Value::String(html)) => Ok(Cow::Owned(html.clone())),
+                    _ => Err(ErrorKind::JsonError {
+                        message: format!("no html at {} in the load more response", html_pointer),
+                    }),
+                }
+            }
+            _ => Ok(Cow::Borrowed(body)),
+        }
+    }
+
+    /// The page after the page. A page without items, or with only items of earlier pages, is
+    /// always the last, the pages after max_pages are not fetched.
+    pub fn next_page(&self, agenda_url: &str, page: &Page) -> NextPage {
+        match self.next_page_url(agenda_url, page) {
+            None => NextPage::Last,
+            Some(_) if page.number >= self.max_pages => {
+                warn!(
+                    "Stopped at {} after {} pages, the maximum number of pages",
+                    page.url, self.max_pages
+                );
+                NextPage::MaxPagesReached
+            }
+            Some(next_page_url) => NextPage::Url(next_page_url),
+        }
+    }
+
+    /// The url of the page after the page, None when the page is the last.
+    fn next_page_url(&self, agenda_url: &str, page: &Page) -> Option<String> {
+        if matches!(self.strategy, Strategy::SinglePage) {
+            return None;
+        }
+        if page.number_of_items == 0 || page.number_of_new_items == 0 {
+            info!("No new agenda items on {}, the last page", page.url);
+            return None;
+        }
+        let next_page_number = page.number + 1;
+        match &self.strategy {
+            Strategy::SinglePage => None,
+            Strategy::NextLink { .. } => page.next_link.clone(),
+            Strategy::Numbered {
+                url_template,
+                first_page,
+            } => Some(url_template.replace(
+                PAGE_PLACEHOLDER,
+                &(first_page + next_page_number - 2).to_string(),
+            )),
+            Strategy::Offset {
+                offset_parameter,
+                limit_parameter,
+                limit,
+            } => {
+                if page.number_of_items < *limit {
+                    return None;
+                }
+                with_query_parameters(
+                    agenda_url,
+                    &[
+                        (offset_parameter, (page.number * limit).to_string()),
+                        (limit_parameter, limit.to_string()),
+                    ],
+                )
+                .ok()
+            }
+            Strategy::LoadMore {
+                url_template,
+                first_page,
+                has_more_pointer,
+                ..
+            } => {
+                // The first page is the html of the agenda url, the later pages are JSON.
+                let has_more_pointer = has_more_pointer.as_ref().filter(|_| page.number > 1);
+                if let Some(has_more_pointer) = has_more_pointer {
+                    let has_more = serde_json::from_str::<serde_json::Value>(page.body)
+                        .ok()
+                        .and_then(|json| {
+                            json.pointer(has_more_pointer).and_then(|it| it.as_bool())
+                        });
+                    if has_more != Some(true) {
+                        return None;
+                    }
+                }
+                Some(
+                    url_template
+                        .replace(
+                            PAGE_PLACEHOLDER,
+                            &(first_page + next_page_number - 2).to_string(),
+                        )
+                        .replace(OFFSET_PLACEHOLDER, &page.total_items.to_string()),
+                )
+            }
+        }
+    }
+}
+
+/// The url with the query parameters set, replacing earlier values.
+fn with_query_parameters(url: &str, parameters: &[(&String, String)]) -> Result<String, ErrorKind> {
+    let mut url = Url::parse(url)?;
+    let other_pairs: Vec<(String, String)> = url
+        .query_pairs()
+        .filter(|(name, _)| !parameters.iter().any(|(parameter, _)| *parameter == name))
+        .map(|(name, value)| (name.into_owned(), value.into_owned()))
+        .collect();
+    url.query_pairs_mut()
+        .clear()
+        .extend_pairs(other_pairs)
+        .extend_pairs(
+            parameters
+                .iter()
+                .map(|(name, value)| (name.as_str(), value)),
+        );
+    Ok(url.to_string())
+}
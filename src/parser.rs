@@ -1,7 +1,10 @@
 use crate::agenda::Agenda;
+use crate::urls::{absolute_url, canonical_url};
 use crate::ErrorKind;
-use scraper::{ElementRef, Selector};
+use scraper::{ElementRef, Html, Selector};
+use sha2::{Digest, Sha256};
 use std::fmt::{Display, Formatter};
+use url::Url;
 
 #[derive(Debug)]
 pub struct CssSelectors {
@@ -9,6 +12,9 @@ pub struct CssSelectors {
     pub title: Selector,
     pub url: Selector,
     pub description: Selector,
+    pub date: Option<Selector>,
+    /// Take the date from this attribute of the date element instead of its text.
+    pub date_attribute: Option<String>,
 }
 
 impl Display for CssSelectors {
@@ -17,6 +23,22 @@ impl Display for CssSelectors {
     }
 }
 
+/// Selectors for the fields on the details page of an agenda item. All fields are optional, a
+/// selector that matches multiple elements yields the text of all of them.
+#[derive(Debug, Default)]
+pub struct DetailSelectors {
+    pub start: Option<Selector>,
+    pub doors_open: Option<Selector>,
+    pub price: Option<Selector>,
+    pub ticket_url: Option<Selector>,
+    pub image: Option<Selector>,
+    pub image_attribute: String,
+    pub long_description: Option<Selector>,
+    pub support_acts: Option<Selector>,
+    /// Only texts containing the prefix are support acts, the text after the prefix is used.
+    pub support_acts_prefix: Option<String>,
+}
+
 fn get_text_for_single(
     logical_selector_name: &str,
     text_element: &ElementRef,
@@ -35,7 +57,7 @@ fn get_select_on_element<'a>(
     search_in_element: &ElementRef<'a>,
     selector: &Selector,
 ) -> Result<ElementRef<'a>, ErrorKind> {
-    let selected = search_in_element.select(&selector);
+    let selected = search_in_element.select(selector);
     if selected.count() != 1 {
         return Err(ErrorKind::CannotFindSelector {
             selector: logical_selector_name.to_string(),
@@ -49,7 +71,7 @@ pub fn get_text_from_element(
     search_in: &ElementRef,
     selector: &Selector,
 ) -> Result<String, ErrorKind> {
-    let selected = get_select_on_element(logical_selector_name, &search_in, &selector)?;
+    let selected = get_select_on_element(logical_selector_name, search_in, selector)?;
     get_text_for_single(logical_selector_name, &selected)
 }
 
@@ -58,7 +80,7 @@ pub fn optional_text_from_element(
     search_in: &ElementRef,
     selector: &Selector,
 ) -> Result<Option<String>, ErrorKind> {
-    let selected_result = get_select_on_element(logical_selector_name, &search_in, &selector);
+    let selected_result = get_select_on_element(logical_selector_name, search_in, selector);
     match selected_result {
         Ok(selected) => match get_text_for_single(logical_selector_name, &selected) {
             Ok(text) => Ok(Some(text)),
@@ -75,8 +97,8 @@ pub fn get_text_from_attr(
     selector: &Selector,
     attr_name: &str,
 ) -> Result<String, ErrorKind> {
-    let selected_element = get_select_on_element(logical_selector_name, &search_in, &selector)?;
-    let attr = selected_element.value().attr(&attr_name);
+    let selected_element = get_select_on_element(logical_selector_name, search_in, selector)?;
+    let attr = selected_element.value().attr(attr_name);
     match attr {
         Some(value) => Ok(value.to_string()),
         None => Err(ErrorKind::CannotFindAttribute {
@@ -85,8 +107,22 @@ pub fn get_text_from_attr(
     }
 }
 
+pub fn optional_attr_from_element(
+    logical_selector_name: &str,
+    search_in: &ElementRef,
+    selector: &Selector,
+    attr_name: &str,
+) -> Result<Option<String>, ErrorKind> {
+    match get_text_from_attr(logical_selector_name, search_in, selector, attr_name) {
+        Ok(value) => Ok(Some(value)),
+        Err(ErrorKind::CannotFindSelector { selector: _ })
+        | Err(ErrorKind::CannotFindAttribute { attribute_name: _ }) => Ok(None),
+        Err(err) => Err(err),
+    }
+}
+
 pub fn selector_for(selector: &str) -> Result<Selector, ErrorKind> {
-    match Selector::parse(&selector) {
+    match Selector::parse(selector) {
         Ok(selector) => Ok(selector),
         Err(parse_error) => Err(ErrorKind::CssSelectorError {
             message: format!(
@@ -97,22 +133,187 @@ pub fn selector_for(selector: &str) -> Result<Selector, ErrorKind> {
     }
 }
 
+/// The agenda item in the element of the listing page. Its url is resolved against the url of the
+/// page and made canonical.
 pub fn agenda_from_element(
     search_in: &ElementRef,
     css_selectors: &CssSelectors,
-    venue_id: &String,
+    venue_id: &str,
+    page_url: &Url,
 ) -> Result<Agenda, ErrorKind> {
-    let url = get_text_from_attr("url", &search_in, &css_selectors.url, "href")?;
-    let title = get_text_from_element("title", &search_in, &css_selectors.title)?;
+    let href = get_text_from_attr("url", search_in, &css_selectors.url, "href")?;
+    let url = canonical_url(page_url, &href)?;
+    let details_url = absolute_url(page_url, &href)?.to_string();
+    let title = get_text_from_element("title", search_in, &css_selectors.title)?;
     let description =
-        optional_text_from_element("description:", &search_in, &css_selectors.description)?;
+        optional_text_from_element("description", search_in, &css_selectors.description)?;
+    let start_text = match (&css_selectors.date, &css_selectors.date_attribute) {
+        (Some(date), Some(date_attribute)) => {
+            optional_attr_from_element("date", search_in, date, date_attribute)?
+        }
+        (Some(date), None) => optional_text_from_element("date", search_in, date)?,
+        (None, _) => None,
+    };
 
     Ok(Agenda {
         _id: None,
-        venue_id: venue_id.clone(),
+        venue_id: venue_id.to_string(),
         title,
         description,
-        url: url.to_string(),
+        url,
+        details_url: Some(details_url),
         needs_details: true,
+        listing_hash: Some(listing_hash(search_in, &start_text)),
+        start_text,
+        ..Default::default()
+    })
+}
+
+/// The canonical url of the agenda item in the element, when its link can be found.
+pub fn agenda_url_from_element(
+    search_in: &ElementRef,
+    css_selectors: &CssSelectors,
+    page_url: &Url,
+) -> Option<String> {
+    let href = get_text_from_attr("url", search_in, &css_selectors.url, "href").ok()?;
+    canonical_url(page_url, &href).ok()
+}
+
+/// Hash of the listed content of an agenda item, the whole text of the element and the start text,
+/// so changes outside of the parsed fields, like a "sold out" label, are detected too.
+fn listing_hash(search_in: &ElementRef, start_text: &Option<String>) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(normalized_text(search_in));
+    hasher.update([0]);
+    hasher.update(start_text.as_deref().unwrap_or_default());
+    format!("{:x}", hasher.finalize())
+}
+
+/// The text of an element with all whitespace collapsed to single spaces.
+fn normalized_text(element: &ElementRef) -> String {
+    element
+        .text()
+        .flat_map(|text| text.split_whitespace())
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+/// The html of an element with all whitespace collapsed, cut off after `max_chars` characters.
+pub fn html_snippet(element: &ElementRef, max_chars: usize) -> String {
+    let html = element
+        .html()
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ");
+    match html.char_indices().nth(max_chars) {
+        Some((end, _)) => format!("{}…", &html[..end]),
+        None => html,
+    }
+}
+
+/// The non-empty texts of all elements in the document matching the selector.
+fn texts_of_all(document: &Html, selector: &Option<Selector>) -> Vec<String> {
+    match selector {
+        Some(selector) => document
+            .select(selector)
+            .map(|element| normalized_text(&element))
+            .filter(|text| !text.is_empty())
+            .collect(),
+        None => Vec::new(),
+    }
+}
+
+fn joined_texts(document: &Html, selector: &Option<Selector>, separator: &str) -> Option<String> {
+    let texts = texts_of_all(document, selector);
+    if texts.is_empty() {
+        None
+    } else {
+        Some(texts.join(separator))
+    }
+}
+
+/// The value of the attribute of the first element matching the selector.
+fn first_attr(document: &Html, selector: &Option<Selector>, attr_name: &str) -> Option<String> {
+    selector.as_ref().and_then(|selector| {
+        document
+            .select(selector)
+            .filter_map(|element| element.value().attr(attr_name))
+            .map(|value| value.trim().to_string())
+            .find(|value| !value.is_empty())
     })
 }
+
+/// The text following the first case-insensitive occurrence of prefix.
+fn text_after_prefix(text: &str, prefix: &str) -> Option<String> {
+    let lowercase_prefix = prefix.to_lowercase();
+    text.char_indices()
+        .map(|(index, _)| &text[index..])
+        .find(|rest| rest.to_lowercase().starts_with(&lowercase_prefix))
+        .map(|rest| rest.chars().skip(prefix.chars().count()).collect())
+}
+
+fn support_acts_from_texts(texts: Vec<String>, prefix: &Option<String>) -> Vec<String> {
+    texts
+        .iter()
+        .filter_map(|text| match prefix {
+            Some(prefix) => text_after_prefix(text, prefix),
+            None => Some(text.clone()),
+        })
+        .flat_map(|text| {
+            text.split(',')
+                .map(|act| act.trim().to_string())
+                .collect::<Vec<String>>()
+        })
+        .filter(|act| !act.is_empty())
+        .collect()
+}
+
+/// The link resolved against the url of the page, the link itself when either cannot be parsed.
+fn resolved_link(page_url: &Option<Url>, href: Option<String>) -> Option<String> {
+    match (page_url, href) {
+        (Some(page_url), Some(href)) => match absolute_url(page_url, &href) {
+            Ok(url) => Some(url.to_string()),
+            Err(_) => Some(href),
+        },
+        (_, href) => href,
+    }
+}
+
+/// Fill the detail fields of the agenda item from its details page. Links are resolved against
+/// the url of the details page. Fields the page has no value for keep their value, which may come
+/// from the listing.
+pub fn details_from_document(
+    document: &Html,
+    detail_selectors: &DetailSelectors,
+    agenda: &mut Agenda,
+) {
+    let page_url = Url::parse(agenda.details_page_url()).ok();
+    agenda.start_text =
+        joined_texts(document, &detail_selectors.start, " / ").or(agenda.start_text.take());
+    agenda.doors_open_text = joined_texts(document, &detail_selectors.doors_open, " / ")
+        .or(agenda.doors_open_text.take());
+    agenda.price = joined_texts(document, &detail_selectors.price, " / ").or(agenda.price.take());
+    agenda.ticket_url = resolved_link(
+        &page_url,
+        first_attr(document, &detail_selectors.ticket_url, "href"),
+    )
+    .or(agenda.ticket_url.take());
+    agenda.image_url = resolved_link(
+        &page_url,
+        first_attr(
+            document,
+            &detail_selectors.image,
+            &detail_selectors.image_attribute,
+        ),
+    )
+    .or(agenda.image_url.take());
+    agenda.long_description = joined_texts(document, &detail_selectors.long_description, "\n\n")
+        .or(agenda.long_description.take());
+    let support_acts = support_acts_from_texts(
+        texts_of_all(document, &detail_selectors.support_acts),
+        &detail_selectors.support_acts_prefix,
+    );
+    if !support_acts.is_empty() {
+        agenda.support_acts = support_acts;
+    }
+}
@@ -0,0 +1,164 @@
+use crate::http_sender::{body_for_response, HttpSender};
+use crate::robots::RobotsTxt;
+use crate::ErrorKind;
+use async_trait::async_trait;
+use reqwest::{Client, RequestBuilder, Response, StatusCode};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::{Mutex, OnceCell, Semaphore};
+use tokio::time::{sleep_until, Instant};
+use tracing::{info, warn};
+use url::Url;
+
+/// How gentle the requests to a single host are.
+#[derive(Debug, Clone)]
+pub struct PolitenessPolicy {
+    pub requests_per_second: f64,
+    /// Minimum time between the start of two requests to the host.
+    pub min_delay: Duration,
+    pub max_concurrent_per_host: usize,
+    /// The user agent to find the rules for in robots.txt.
+    pub user_agent: String,
+    pub obey_robots_txt: bool,
+}
+
+impl Default for PolitenessPolicy {
+    fn default() -> Self {
+        PolitenessPolicy {
+            requests_per_second: 2.0,
+            min_delay: Duration::from_millis(250),
+            max_concurrent_per_host: 2,
+            user_agent: "venue-scraper".to_string(),
+            obey_robots_txt: true,
+        }
+    }
+}
+
+impl PolitenessPolicy {
+    /// Time between the start of two requests to a host, the largest of the rate limit, the
+    /// minimum delay and the crawl delay of the host.
+    fn request_interval(&self, crawl_delay: Option<Duration>) -> Duration {
+        let rate_interval = if self.requests_per_second > 0.0 {
+            Duration::from_secs_f64(1.0 / self.requests_per_second)
+        } else {
+            Duration::ZERO
+        };
+        rate_interval
+            .max(self.min_delay)
+            .max(crawl_delay.unwrap_or_default())
+    }
+}
+
+/// The requests in flight and the robots.txt of a host.
+struct HostState {
+    connections: Semaphore,
+    next_request_at: Mutex<Instant>,
+    robots_txt: OnceCell<RobotsTxt>,
+}
+
+/// Sender pacing the requests per host, capping the concurrent requests per host and obeying
+/// the robots.txt of the host, which is fetched once.
+pub struct PoliteHttpSender<S> {
+    sender: S,
+    client: Client,
+    policy: PolitenessPolicy,
+    hosts: Mutex<HashMap<String, Arc<HostState>>>,
+}
+
+impl<S: HttpSender> PoliteHttpSender<S> {
+    pub fn new(sender: S, client: Client, policy: PolitenessPolicy) -> PoliteHttpSender<S> {
+        PoliteHttpSender {
+            sender,
+            client,
+            policy,
+            hosts: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn host_state(&self, origin: &str) -> Arc<HostState> {
+        self.hosts
+            .lock()
+            .await
+            .entry(origin.to_string())
+            .or_insert_with(|| {
+                Arc::new(HostState {
+                    connections: Semaphore::new(self.policy.max_concurrent_per_host.max(1)),
+                    next_request_at: Mutex::new(Instant::now()),
+                    robots_txt: OnceCell::new(),
+                })
+            })
+            .clone()
+    }
+
+    /// The robots.txt of the origin, a missing or unreadable robots.txt allows everything.
+    async fn fetch_robots_txt(&self, origin: &str) -> RobotsTxt {
+        let robots_url = format!("{}/robots.txt", origin);
+        let response = self.sender.send(self.client.get(&robots_url)).await;
+        let contents = match response {
+            Ok(response) if response.status() == StatusCode::OK => {
+                body_for_response(response).await
+            }
+            Ok(response) => {
+                info!("No robots.txt at {}: {}", robots_url, response.status());
+                return RobotsTxt::allow_all();
+            }
+            Err(err) => Err(err),
+        };
+        match contents {
+            Ok(contents) => RobotsTxt::parse(&contents, &self.policy.user_agent),
+            Err(err) => {
+                warn!("Cannot fetch {}, allowing all: {}", robots_url, err);
+                RobotsTxt::allow_all()
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl<S: HttpSender> HttpSender for PoliteHttpSender<S> {
+    async fn send(&self, request: RequestBuilder) -> Result<Response, ErrorKind> {
+        let url = match request.try_clone().and_then(|request| request.build().ok()) {
+            Some(built_request) => built_request.url().clone(),
+            None => return self.sender.send(request).await,
+        };
+        let origin = url.origin().ascii_serialization();
+        let host_state = self.host_state(&origin).await;
+
+        let robots_txt = if self.policy.obey_robots_txt {
+            Some(
+                host_state
+                    .robots_txt
+                    .get_or_init(|| self.fetch_robots_txt(&origin))
+                    .await,
+            )
+        } else {
+            None
+        };
+        if let Some(robots_txt) = robots_txt {
+            if !robots_txt.is_allowed(&path_and_query(&url)) {
+                return Err(ErrorKind::DisallowedByRobotsTxt {
+                    url: url.to_string(),
+                });
+            }
+        }
+
+        let _connection = host_state.connections.acquire().await.unwrap();
+        let start_at = {
+            let mut next_request_at = host_state.next_request_at.lock().await;
+            let start_at = (*next_request_at).max(Instant::now());
+            let crawl_delay = robots_txt.and_then(|robots_txt| robots_txt.crawl_delay);
+            *next_request_at = start_at + self.policy.request_interval(crawl_delay);
+            start_at
+        };
+        sleep_until(start_at).await;
+        self.sender.send(request).await
+    }
+}
+
+fn path_and_query(url: &Url) -> String {
+    match url.query() {
+        Some(query) => format!("{}?{}", url.path(), query),
+        None => url.path().to_string(),
+    }
+}
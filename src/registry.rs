@@ -0,0 +1,357 @@
+use crate::health::{LayoutChangeAlert, LogLayoutChangeAlert};
+use crate::http_sender::HttpSender;
+use crate::store::AgendaStore;
+use crate::sync_run::SyncRun;
+use crate::venue_definition::VenueDefinition;
+use crate::{ErrorKind, SyncingResult, VenueScraper};
+use chrono::Utc;
+use futures::FutureExt;
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::fmt::{Display, Formatter};
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+use tokio::task::JoinSet;
+use tracing::{error, info};
+
+/// The number of venues synced at the same time, unless the registry is told otherwise.
+pub const DEFAULT_MAX_CONCURRENT_VENUES: usize = 8;
+
+/// A venue in the registry, the scraper together with the definition it was created from.
+struct RegisteredVenue {
+    venue_definition: VenueDefinition,
+    venue_scraper: VenueScraper,
+}
+
+/// Holds the scrapers of all known venues.
+pub struct VenueRegistry {
+    /// Shared with the tasks syncing the venues.
+    venues: Vec<Arc<RegisteredVenue>>,
+    max_concurrent_venues: usize,
+    /// Told when a venue sync suspects the layout of the venue changed.
+    layout_change_alert: Arc<dyn LayoutChangeAlert>,
+    /// Stores a sync run for every venue synced.
+    store: Arc<dyn AgendaStore>,
+}
+
+/// The outcome of syncing a single venue.
+pub struct VenueSyncReport {
+    pub venue_id: String,
+    pub result: Result<SyncingResult, ErrorKind>,
+    /// What a failed listing sync did until it failed, like the urls it fetched.
+    pub partial_result: Option<SyncingResult>,
+    /// The result of the details sync, None if the venue does not fetch details.
+    pub details_result: Option<Result<SyncingResult, ErrorKind>>,
+}
+
+impl VenueSyncReport {
+    pub fn is_ok(&self) -> bool {
+        self.result.is_ok() && !matches!(self.details_result, Some(Err(_)))
+    }
+}
+
+impl Display for VenueSyncReport {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.result {
+            Ok(result) => write!(f, "Venue {}: {}", self.venue_id, result)?,
+            Err(err) => write!(f, "Venue {}: failed: {}", self.venue_id, err)?,
+        }
+        match &self.details_result {
+            Some(Ok(result)) => write!(f, ", details: {}", result)?,
+            Some(Err(err)) => write!(f, ", details failed: {}", err)?,
+            None => {}
+        }
+        let layout_change_suspected = self
+            .result
+            .as_ref()
+            .ok()
+            .and_then(|result| result.selector_health.as_ref())
+            .and_then(|selector_health| selector_health.layout_change_suspected.as_ref());
+        match layout_change_suspected {
+            Some(layout_change_suspected) => write!(f, ", {}", layout_change_suspected),
+            None => Ok(()),
+        }
+    }
+}
+
+/// The outcome of syncing all venues of a registry.
+pub struct RegistrySyncReport {
+    pub venue_reports: Vec<VenueSyncReport>,
+}
+
+impl RegistrySyncReport {
+    /// The results of all venues that synced, added up.
+    pub fn total(&self) -> SyncingResult {
+        let mut total = SyncingResult::with_zeroes();
+        for venue_report in self.venue_reports.iter() {
+            if let Ok(result) = &venue_report.result {
+                total.add(result);
+            }
+            if let Some(Ok(result)) = &venue_report.details_result {
+                total.add(result);
+            }
+        }
+        total
+    }
+
+    pub fn failed_venues(&self) -> Vec<&VenueSyncReport> {
+        self.venue_reports
+            .iter()
+            .filter(|venue_report| !venue_report.is_ok())
+            .collect()
+    }
+}
+
+impl VenueRegistry {
+    /// Create a registry with a scraper for every venue definition.
+    pub fn from_definitions(
+        venue_definitions: &[VenueDefinition],
+        http_sender: Arc<dyn HttpSender>,
+        client: &Client,
+        store: Arc<dyn AgendaStore>,
+    ) -> Result<VenueRegistry, ErrorKind> {
+        let mut venues = Vec::new();
+        for venue_definition in venue_definitions {
+            let venue_scraper = VenueScraper::from_definition(
+                venue_definition,
+                Arc::clone(&http_sender),
+                client.clone(),
+                Arc::clone(&store),
+            )?;
+            venues.push(Arc::new(RegisteredVenue {
+                venue_definition: venue_definition.clone(),
+                venue_scraper,
+            }));
+        }
+        Ok(VenueRegistry {
+            venues,
+            max_concurrent_venues: DEFAULT_MAX_CONCURRENT_VENUES,
+            layout_change_alert: Arc::new(LogLayoutChangeAlert),
+            store,
+        })
+    }
+
+    /// Sync at most max_concurrent_venues venues at the same time, at least one.
+    pub fn with_max_concurrent_venues(mut self, max_concurrent_venues: usize) -> VenueRegistry {
+        self.max_concurrent_venues = max_concurrent_venues.max(1);
+        self
+    }
+
+    /// Tell the alert about the venue syncs that suspect a layout change, instead of only logging.
+    pub fn with_layout_change_alert(
+        mut self,
+        layout_change_alert: Arc<dyn LayoutChangeAlert>,
+    ) -> VenueRegistry {
+        self.layout_change_alert = layout_change_alert;
+        self
+    }
+
+    pub fn len(&self) -> usize {
+        self.venues.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.venues.is_empty()
+    }
+
+    pub fn venue_ids(&self) -> Vec<&str> {
+        self.venues
+            .iter()
+            .map(|venue| venue.venue_definition.id.as_str())
+            .collect()
+    }
+
+    pub fn venue_definitions(&self) -> Vec<&VenueDefinition> {
+        self.venues
+            .iter()
+            .map(|venue| &venue.venue_definition)
+            .collect()
+    }
+
+    pub fn scraper(&self, venue_id: &str) -> Option<&VenueScraper> {
+        self.venues
+            .iter()
+            .find(|venue| venue.venue_definition.id == venue_id)
+            .map(|venue| &venue.venue_scraper)
+    }
+
+    /// Keep only the venues with the given ids. Every id must be a known venue.
+    pub fn filter_by_ids(self, venue_ids: &[String]) -> Result<VenueRegistry, ErrorKind> {
+        for venue_id in venue_ids {
+            if !self.venue_ids().contains(&venue_id.as_str()) {
+                return Err(ErrorKind::UnknownVenue {
+                    venue_id: venue_id.clone(),
+                });
+            }
+        }
+        Ok(self.filter(|venue_definition| venue_ids.contains(&venue_definition.id)))
+    }
+
+    /// Keep only the venues tagged with tag.
+    pub fn filter_by_tag(self, tag: &str) -> VenueRegistry {
+        self.filter(|venue_definition| venue_definition.tags.iter().any(|it| it == tag))
+    }
+
+    fn filter<P>(self, predicate: P) -> VenueRegistry
+    where
+        P: Fn(&VenueDefinition) -> bool,
+    {
+        VenueRegistry {
+            venues: self
+                .venues
+                .into_iter()
+                .filter(|venue| predicate(&venue.venue_definition))
+                .collect(),
+            max_concurrent_venues: self.max_concurrent_venues,
+            layout_change_alert: self.layout_change_alert,
+            store: self.store,
+        }
+    }
+
+    /// Sync all venues in the registry, every venue followed by its details if it fetches details.
+    /// The venues are synced in parallel tasks, at most max_concurrent_venues at the same time.
+    /// A failing or panicking venue does not stop the others, every venue gets its own report, in
+    /// the order of the registry.
+    pub async fn sync_all(&self) -> RegistrySyncReport {
+        self.run_all(SyncJob::Listing).await
+    }
+
+    /// Sync the details of the agenda items of all venues in the registry that need them, in
+    /// parallel tasks like sync_all. The result of a venue report is the details result.
+    pub async fn sync_all_details(&self) -> RegistrySyncReport {
+        self.run_all(SyncJob::Details).await
+    }
+
+    async fn run_all(&self, sync_job: SyncJob) -> RegistrySyncReport {
+        let mut venue_reports: Vec<Option<VenueSyncReport>> =
+            self.venues.iter().map(|_| None).collect();
+        let mut join_set = JoinSet::new();
+
+        for (index, venue) in self.venues.iter().enumerate() {
+            if join_set.len() >= self.max_concurrent_venues {
+                collect_venue_report(&mut join_set, &mut venue_reports).await;
+            }
+            let venue = Arc::clone(venue);
+            let layout_change_alert = Arc::clone(&self.layout_change_alert);
+            let store = Arc::clone(&self.store);
+            join_set.spawn(async move {
+                let started_at = Utc::now();
+                let venue_report = AssertUnwindSafe(sync_venue(&venue, sync_job))
+                    .catch_unwind()
+                    .await
+                    .unwrap_or_else(|panic| panicked_venue_report(&venue, panic));
+                let sync_run =
+                    SyncRun::from_report(&venue_report, sync_job, started_at, Utc::now());
+                if let Err(err) = store.insert_sync_run(&sync_run).await {
+                    error!(
+                        "Cannot store the sync run of {}: {}",
+                        venue_report.venue_id, err
+                    );
+                }
+                alert_layout_change(&venue_report, layout_change_alert.as_ref()).await;
+                (index, venue_report)
+            });
+        }
+        while !join_set.is_empty() {
+            collect_venue_report(&mut join_set, &mut venue_reports).await;
+        }
+
+        RegistrySyncReport {
+            venue_reports: venue_reports.into_iter().flatten().collect(),
+        }
+    }
+}
+
+/// Wait for the next venue to finish and put its report in its place.
+async fn collect_venue_report(
+    join_set: &mut JoinSet<(usize, VenueSyncReport)>,
+    venue_reports: &mut [Option<VenueSyncReport>],
+) {
+    match join_set.join_next().await {
+        Some(Ok((index, venue_report))) => {
+            info!("{}", venue_report);
+            venue_reports[index] = Some(venue_report);
+        }
+        // The tasks catch panics and are never aborted.
+        Some(Err(err)) => error!("Venue sync task failed: {}", err),
+        None => {}
+    }
+}
+
+/// What the tasks of a registry sync do for every venue.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SyncJob {
+    /// The listing, followed by the details if the venue fetches details.
+    Listing,
+    Details,
+}
+
+async fn sync_venue(venue: &RegisteredVenue, sync_job: SyncJob) -> VenueSyncReport {
+    let venue_id = &venue.venue_definition.id;
+    if let SyncJob::Details = sync_job {
+        let result = venue.venue_scraper.sync_details().await;
+        if let Err(err) = &result {
+            error!("Error syncing details of venue {}: {}", venue_id, err);
+        }
+        return VenueSyncReport {
+            venue_id: venue_id.clone(),
+            result,
+            partial_result: None,
+            details_result: None,
+        };
+    }
+    let (result, partial_result) = match venue.venue_scraper.sync_with_partial_result().await {
+        Ok(result) => (Ok(result), None),
+        Err(partial_sync) => {
+            error!("Error syncing venue {}: {}", venue_id, partial_sync.error);
+            (Err(partial_sync.error), Some(partial_sync.result))
+        }
+    };
+    let details_result = if venue.venue_definition.fetch_details {
+        let details_result = venue.venue_scraper.sync_details().await;
+        if let Err(err) = &details_result {
+            error!("Error syncing details of venue {}: {}", venue_id, err);
+        }
+        Some(details_result)
+    } else {
+        None
+    };
+    VenueSyncReport {
+        venue_id: venue_id.clone(),
+        result,
+        partial_result,
+        details_result,
+    }
+}
+
+async fn alert_layout_change(
+    venue_report: &VenueSyncReport,
+    layout_change_alert: &dyn LayoutChangeAlert,
+) {
+    if let Ok(result) = &venue_report.result {
+        match &result.selector_health {
+            Some(selector_health) if selector_health.layout_change_suspected.is_some() => {
+                layout_change_alert.alert(selector_health).await
+            }
+            _ => {}
+        }
+    }
+}
+
+fn panicked_venue_report(venue: &RegisteredVenue, panic: Box<dyn Any + Send>) -> VenueSyncReport {
+    let message = panic
+        .downcast_ref::<&str>()
+        .map(|message| message.to_string())
+        .or_else(|| panic.downcast_ref::<String>().cloned())
+        .unwrap_or_default();
+    let venue_id = venue.venue_definition.id.clone();
+    error!("Sync of venue {} panicked: {}", venue_id, message);
+    VenueSyncReport {
+        venue_id: venue_id.clone(),
+        result: Err(ErrorKind::VenueSyncPanicked { venue_id, message }),
+        partial_result: None,
+        details_result: None,
+    }
+}
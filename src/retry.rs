@@ -0,0 +1,172 @@
+use crate::http_sender::HttpSender;
+use crate::ErrorKind;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use reqwest::header::RETRY_AFTER;
+use reqwest::{RequestBuilder, Response, StatusCode};
+use std::time::Duration;
+use tracing::info;
+
+/// When and how often a request is retried.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// Attempts including the first one, 1 disables retrying.
+    pub max_attempts: u32,
+    /// The backoff before the first retry, doubled for every next retry.
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    /// Part of the backoff that is randomized, 0.0 for none, 1.0 for a backoff between zero and
+    /// the full backoff.
+    pub jitter: f64,
+    /// Give up instead of waiting when the server asks to retry after a longer time.
+    pub max_retry_after: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(10),
+            jitter: 0.5,
+            max_retry_after: Duration::from_secs(60),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// The backoff before the retry, for retry 1 and up.
+    pub fn backoff(&self, retry: u32) -> Duration {
+        let backoff = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(retry.saturating_sub(1)))
+            .min(self.max_backoff);
+        let jitter = self.jitter.clamp(0.0, 1.0);
+        if jitter == 0.0 {
+            return backoff;
+        }
+        let random_part = backoff.mul_f64(jitter * rand::thread_rng().gen::<f64>());
+        backoff.mul_f64(1.0 - jitter) + random_part
+    }
+}
+
+/// The number of retries it took to get the response, stored in the extensions of the response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Retries(pub u32);
+
+/// The number of retries done for the response, 0 when it did not pass a retrying sender.
+pub fn retries_of(response: &Response) -> u32 {
+    response
+        .extensions()
+        .get::<Retries>()
+        .map(|retries| retries.0)
+        .unwrap_or(0)
+}
+
+/// Statuses for which the same request may succeed a bit later.
+fn is_transient_status(status: StatusCode) -> bool {
+    matches!(
+        status,
+        StatusCode::TOO_MANY_REQUESTS
+            | StatusCode::BAD_GATEWAY
+            | StatusCode::SERVICE_UNAVAILABLE
+            | StatusCode::GATEWAY_TIMEOUT
+    )
+}
+
+/// The delay asked for by a Retry-After header, in seconds or as an http date.
+pub fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let retry_at = DateTime::parse_from_rfc2822(value).ok()?;
+    Some(
+        (retry_at.with_timezone(&Utc) - now)
+            .to_std()
+            .unwrap_or(Duration::ZERO),
+    )
+}
+
+/// Sender retrying idempotent requests on connection errors and transient statuses: 429, 502,
+/// 503 and 504. The retries are stored as `Retries` in the extensions of the response. When all
+/// attempts fail, the error is RetriesExhausted.
+pub struct RetryingHttpSender<S> {
+    sender: S,
+    policy: RetryPolicy,
+}
+
+impl<S: HttpSender> RetryingHttpSender<S> {
+    pub fn new(sender: S, policy: RetryPolicy) -> RetryingHttpSender<S> {
+        RetryingHttpSender { sender, policy }
+    }
+}
+
+/// The result with the number of retries it took on the response.
+fn with_retries(result: Result<Response, ErrorKind>, retries: u32) -> Result<Response, ErrorKind> {
+    result.map(|mut response| {
+        response.extensions_mut().insert(Retries(retries));
+        response
+    })
+}
+
+#[async_trait]
+impl<S: HttpSender> HttpSender for RetryingHttpSender<S> {
+    async fn send(&self, request: RequestBuilder) -> Result<Response, ErrorKind> {
+        // Only requests that can be sent again, with an idempotent method, are retried.
+        let retryable_request = request
+            .try_clone()
+            .and_then(|request| request.build().ok())
+            .map(|request| (request.method().is_idempotent(), request.url().to_string()));
+        let url = match retryable_request {
+            Some((true, url)) => url,
+            _ => return self.sender.send(request).await,
+        };
+
+        let mut request = request;
+        let mut retries = 0;
+        loop {
+            let next_request = request.try_clone();
+            let result = self.sender.send(request).await;
+
+            let (message, retry_after) = match &result {
+                Ok(response) if is_transient_status(response.status()) => {
+                    let retry_after = response
+                        .headers()
+                        .get(RETRY_AFTER)
+                        .and_then(|value| value.to_str().ok())
+                        .and_then(|value| parse_retry_after(value, Utc::now()));
+                    (format!("status {}", response.status()), retry_after)
+                }
+                Err(err @ ErrorKind::ConnectionError { .. }) => (err.to_string(), None),
+                _ => return with_retries(result, retries),
+            };
+
+            let delay = retry_after.unwrap_or_else(|| self.policy.backoff(retries + 1));
+            match next_request {
+                Some(next_request)
+                    if retries + 1 < self.policy.max_attempts
+                        && delay <= self.policy.max_retry_after =>
+                {
+                    retries += 1;
+                    info!(
+                        "Retry {} of {} in {:?}, after {}",
+                        retries, url, delay, message
+                    );
+                    tokio::time::sleep(delay).await;
+                    request = next_request;
+                }
+                // Without a retry the failure is reported as it is, like the status code.
+                _ if retries == 0 => return with_retries(result, retries),
+                _ => {
+                    return Err(ErrorKind::RetriesExhausted {
+                        url,
+                        retries,
+                        message,
+                    })
+                }
+            }
+        }
+    }
+}
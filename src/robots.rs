@@ -0,0 +1,134 @@
+use std::time::Duration;
+
+/// The rules of a robots.txt that apply to one user agent.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RobotsTxt {
+    /// Allow and disallow rules, true for allow, with the path pattern.
+    rules: Vec<(bool, String)>,
+    pub crawl_delay: Option<Duration>,
+}
+
+/// A group of records for one or more user agents.
+#[derive(Default)]
+struct Group {
+    user_agents: Vec<String>,
+    rules: Vec<(bool, String)>,
+    crawl_delay: Option<Duration>,
+}
+
+impl RobotsTxt {
+    /// Everything is allowed, for hosts without a robots.txt.
+    pub fn allow_all() -> RobotsTxt {
+        RobotsTxt::default()
+    }
+
+    /// Parse the robots.txt and keep the group for the user agent, or the `*` group when no group
+    /// names the user agent.
+    pub fn parse(contents: &str, user_agent: &str) -> RobotsTxt {
+        let user_agent = user_agent.to_lowercase();
+        let mut groups: Vec<Group> = Vec::new();
+        let mut in_user_agent_lines = false;
+
+        for line in contents.lines() {
+            let line = line.split('#').next().unwrap_or_default().trim();
+            let (field, value) = match line.split_once(':') {
+                Some((field, value)) => (field.trim().to_lowercase(), value.trim()),
+                None => continue,
+            };
+            match field.as_str() {
+                "user-agent" => {
+                    // Consecutive user-agent lines share one group.
+                    if !in_user_agent_lines {
+                        groups.push(Group::default());
+                    }
+                    in_user_agent_lines = true;
+                    groups
+                        .last_mut()
+                        .unwrap()
+                        .user_agents
+                        .push(value.to_lowercase());
+                }
+                "allow" | "disallow" | "crawl-delay" => {
+                    in_user_agent_lines = false;
+                    let group = match groups.last_mut() {
+                        Some(group) => group,
+                        None => continue,
+                    };
+                    match field.as_str() {
+                        "crawl-delay" => {
+                            group.crawl_delay = value
+                                .parse::<f64>()
+                                .ok()
+                                .filter(|seconds| *seconds >= 0.0)
+                                .map(Duration::from_secs_f64)
+                        }
+                        // An empty disallow allows everything.
+                        _ if value.is_empty() => {}
+                        _ => group.rules.push((field == "allow", value.to_string())),
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        let group = groups
+            .iter()
+            .position(|group| {
+                group
+                    .user_agents
+                    .iter()
+                    .any(|name| name != "*" && user_agent.contains(name.as_str()))
+            })
+            .or_else(|| {
+                groups
+                    .iter()
+                    .position(|group| group.user_agents.iter().any(|name| name == "*"))
+            })
+            .map(|index| groups.swap_remove(index));
+        match group {
+            Some(group) => RobotsTxt {
+                rules: group.rules,
+                crawl_delay: group.crawl_delay,
+            },
+            None => RobotsTxt::allow_all(),
+        }
+    }
+
+    /// Whether the path, with the query, may be fetched. The longest matching rule decides, allow
+    /// wins from disallow when they are as long.
+    pub fn is_allowed(&self, path: &str) -> bool {
+        self.rules
+            .iter()
+            .filter(|(_, pattern)| pattern_matches(pattern, path))
+            .max_by_key(|(allow, pattern)| (pattern.len(), *allow))
+            .map(|(allow, _)| *allow)
+            .unwrap_or(true)
+    }
+}
+
+/// Match a path against a robots.txt pattern, a prefix with `*` for any characters and a `$` at
+/// the end to match the end of the path.
+fn pattern_matches(pattern: &str, path: &str) -> bool {
+    let (pattern, anchored) = match pattern.strip_suffix('$') {
+        Some(pattern) => (pattern, true),
+        None => (pattern, false),
+    };
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+    if !path.starts_with(first) {
+        return false;
+    }
+    let mut position = first.len();
+    let parts: Vec<&str> = parts.collect();
+    for (index, part) in parts.iter().enumerate() {
+        let is_last = index == parts.len() - 1;
+        if is_last && anchored {
+            return path.len() >= position + part.len() && path.ends_with(part);
+        }
+        match path[position..].find(part) {
+            Some(found) => position += found + part.len(),
+            None => return false,
+        }
+    }
+    !anchored || position == path.len()
+}
@@ -0,0 +1,82 @@
+use crate::agenda::AgendaStatus;
+use crate::store::AgendaStore;
+use crate::venue_definition::VenueDefinition;
+use crate::ErrorKind;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::fmt::{Display, Formatter};
+
+/// Counts of the stored agenda items of a venue.
+#[derive(Debug, Serialize, Default, PartialEq, Eq)]
+pub struct VenueStats {
+    pub venue_id: String,
+    pub total_items: u32,
+    pub active_items: u32,
+    pub cancelled_items: u32,
+    pub removed_items: u32,
+    pub past_items: u32,
+    pub items_needing_details: u32,
+    /// Items without a parsed start date.
+    pub items_without_start: u32,
+    /// The start of the last full sync that found items on the listing.
+    pub last_seen_at: Option<DateTime<Utc>>,
+    /// The last time an item was inserted or changed.
+    pub last_updated_at: Option<DateTime<Utc>>,
+}
+
+impl Display for VenueStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let format_time = |time: &Option<DateTime<Utc>>| {
+            time.map(|time| time.to_rfc3339())
+                .unwrap_or_else(|| "-".to_string())
+        };
+        write!(
+            f,
+            "{}: {} items, {} active, {} cancelled, {} removed, {} past, {} need details, {} \
+             without start, last seen {}, last updated {}",
+            self.venue_id,
+            self.total_items,
+            self.active_items,
+            self.cancelled_items,
+            self.removed_items,
+            self.past_items,
+            self.items_needing_details,
+            self.items_without_start,
+            format_time(&self.last_seen_at),
+            format_time(&self.last_updated_at),
+        )
+    }
+}
+
+/// The stats of every venue in the venue definitions, in their order.
+pub async fn venue_stats(
+    store: &dyn AgendaStore,
+    venue_definitions: &[VenueDefinition],
+) -> Result<Vec<VenueStats>, ErrorKind> {
+    let mut all_stats = Vec::new();
+    for venue_definition in venue_definitions {
+        let mut stats = VenueStats {
+            venue_id: venue_definition.id.clone(),
+            ..Default::default()
+        };
+        for agenda in store.agenda_items_for_venue(&venue_definition.id).await? {
+            stats.total_items += 1;
+            match agenda.status {
+                AgendaStatus::Active => stats.active_items += 1,
+                AgendaStatus::Cancelled => stats.cancelled_items += 1,
+                AgendaStatus::Removed => stats.removed_items += 1,
+                AgendaStatus::Past => stats.past_items += 1,
+            }
+            if agenda.needs_details {
+                stats.items_needing_details += 1;
+            }
+            if agenda.starts_at.is_none() {
+                stats.items_without_start += 1;
+            }
+            stats.last_seen_at = stats.last_seen_at.max(agenda.last_seen_at);
+            stats.last_updated_at = stats.last_updated_at.max(agenda.updated_at);
+        }
+        all_stats.push(stats);
+    }
+    Ok(all_stats)
+}
@@ -0,0 +1,165 @@
+use crate::agenda::{Agenda, AgendaStatus};
+use crate::config::Config;
+use crate::health::SelectorHealth;
+use crate::listing_page::ListingPage;
+use crate::sync_run::SyncRun;
+use crate::ErrorKind;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::path::Path;
+use std::sync::Arc;
+
+pub mod dry_run;
+pub mod memory;
+pub mod mongo;
+pub mod sqlite;
+
+pub struct UpsertAgendaResult {
+    pub agenda: Agenda,
+    pub inserted: bool,
+    /// The stored agenda item changed on the listing and is updated.
+    pub updated: bool,
+}
+
+/// Persistence of the agenda items, and of the selector health and sync runs of the venues.
+#[async_trait]
+pub trait AgendaStore: Send + Sync {
+    /// Retrieve an Agenda by the url.
+    async fn get_agenda_by_url(&self, url: &str) -> Result<Option<Agenda>, ErrorKind>;
+
+    /// Retrieve an Agenda by its stable id.
+    async fn get_agenda_by_stable_id(&self, stable_id: &str) -> Result<Option<Agenda>, ErrorKind>;
+
+    async fn insert_agenda(&self, agenda: &Agenda) -> Result<(), ErrorKind>;
+
+    /// Replace the stored agenda with the same url, AgendaNotFound if there is none.
+    async fn update_agenda(&self, agenda: &Agenda) -> Result<(), ErrorKind>;
+
+    /// All agenda items of the venue, in insertion order.
+    async fn agenda_items_for_venue(&self, venue_id: &str) -> Result<Vec<Agenda>, ErrorKind>;
+
+    /// The agenda items of the venue with the status, in insertion order.
+    async fn agenda_items_for_venue_with_status(
+        &self,
+        venue_id: &str,
+        status: AgendaStatus,
+    ) -> Result<Vec<Agenda>, ErrorKind> {
+        let mut agenda_items = self.agenda_items_for_venue(venue_id).await?;
+        agenda_items.retain(|agenda| agenda.status == status);
+        Ok(agenda_items)
+    }
+
+    /// Set the time the stored agenda items with the urls were last seen on the listing, in one
+    /// write. Urls that are not stored are ignored.
+    async fn update_last_seen_at(
+        &self,
+        urls: &[String],
+        seen_at: DateTime<Utc>,
+    ) -> Result<(), ErrorKind>;
+
+    async fn insert_selector_health(
+        &self,
+        selector_health: &SelectorHealth,
+    ) -> Result<(), ErrorKind>;
+
+    /// The last selector health of the venue, at most limit, the most recent first.
+    async fn selector_health_for_venue(
+        &self,
+        venue_id: &str,
+        limit: usize,
+    ) -> Result<Vec<SelectorHealth>, ErrorKind>;
+
+    /// The listing page with the url, as it was last parsed.
+    async fn get_listing_page(&self, url: &str) -> Result<Option<ListingPage>, ErrorKind>;
+
+    /// Store the listing page, replacing the one with the same url.
+    async fn upsert_listing_page(&self, listing_page: &ListingPage) -> Result<(), ErrorKind>;
+
+    async fn insert_sync_run(&self, sync_run: &SyncRun) -> Result<(), ErrorKind>;
+
+    /// The sync runs of the venue that started at or after since, the oldest first.
+    async fn sync_runs_for_venue(
+        &self,
+        venue_id: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<SyncRun>, ErrorKind>;
+
+    /// The last sync run of the venue without errors.
+    async fn last_successful_sync_run(&self, venue_id: &str) -> Result<Option<SyncRun>, ErrorKind>;
+
+    /// Insert the agenda if no agenda with the url is stored yet. Otherwise update the stored one
+    /// when its listing hash differs from the hash of the agenda.
+    async fn upsert_agenda(&self, agenda: &Agenda) -> Result<UpsertAgendaResult, ErrorKind> {
+        let now = Utc::now();
+        match self.get_agenda_by_url(&agenda.url).await? {
+            Some(mut stored_agenda) if stored_agenda.listing_hash == agenda.listing_hash => {
+                // Unchanged, but it may have moved to another listing page or link.
+                if stored_agenda.listing_url != agenda.listing_url
+                    || stored_agenda.details_url != agenda.details_url
+                {
+                    stored_agenda.listing_url = agenda.listing_url.clone();
+                    stored_agenda.details_url = agenda.details_url.clone();
+                    self.update_agenda(&stored_agenda).await?;
+                }
+                Ok(UpsertAgendaResult {
+                    agenda: stored_agenda,
+                    inserted: false,
+                    updated: false,
+                })
+            }
+            Some(mut stored_agenda) if stored_agenda.listing_hash.is_none() => {
+                // Stored before the listing hash existed, only remember the hash.
+                stored_agenda.listing_hash = agenda.listing_hash.clone();
+                stored_agenda.listing_url = agenda.listing_url.clone();
+                stored_agenda.details_url = agenda.details_url.clone();
+                self.update_agenda(&stored_agenda).await?;
+                Ok(UpsertAgendaResult {
+                    agenda: stored_agenda,
+                    inserted: false,
+                    updated: false,
+                })
+            }
+            Some(mut stored_agenda) => {
+                stored_agenda.apply_listing_changes(agenda, now);
+                self.update_agenda(&stored_agenda).await?;
+                Ok(UpsertAgendaResult {
+                    agenda: stored_agenda,
+                    inserted: false,
+                    updated: true,
+                })
+            }
+            None => {
+                let new_agenda = Agenda {
+                    _id: None,
+                    needs_details: true,
+                    updated_at: Some(now),
+                    ..agenda.clone()
+                };
+                self.insert_agenda(&new_agenda).await?;
+
+                Ok(UpsertAgendaResult {
+                    agenda: new_agenda,
+                    inserted: true,
+                    updated: false,
+                })
+            }
+        }
+    }
+}
+
+/// Create the store configured by the store_type of the config: mongo, sqlite or memory.
+pub async fn create_store(config: &Config) -> Result<Arc<dyn AgendaStore>, ErrorKind> {
+    match config.store_type.as_str() {
+        "mongo" => {
+            let db = mongo::create_mongo_connection(config).await?;
+            Ok(Arc::new(mongo::MongoAgendaStore::new(db)))
+        }
+        "sqlite" => Ok(Arc::new(sqlite::SqliteAgendaStore::open(Path::new(
+            &config.sqlite_path,
+        ))?)),
+        "memory" => Ok(Arc::new(memory::MemoryAgendaStore::new())),
+        store_type => Err(ErrorKind::UnknownStoreType {
+            store_type: store_type.to_string(),
+        }),
+    }
+}
@@ -0,0 +1,356 @@
+use crate::agenda::{Agenda, AgendaStatus};
+use crate::health::SelectorHealth;
+use crate::listing_page::ListingPage;
+use crate::store::AgendaStore;
+use crate::sync_run::SyncRun;
+use crate::ErrorKind;
+use async_trait::async_trait;
+use chrono::{DateTime, FixedOffset, Utc};
+use serde::Serialize;
+use std::fmt::{Display, Formatter};
+use std::sync::{Arc, Mutex};
+
+/// Agenda store that reads from another store but only records the writes, for dry runs against
+/// the production store. The run reads its own writes back, so it takes the same decisions, and
+/// gives the same results, as a real run.
+pub struct DryRunAgendaStore {
+    store: Arc<dyn AgendaStore>,
+    /// The agenda items as the run wrote them, in the order they were first written.
+    written_items: Mutex<Vec<Agenda>>,
+    written_selector_health: Mutex<Vec<SelectorHealth>>,
+    written_listing_pages: Mutex<Vec<ListingPage>>,
+    written_sync_runs: Mutex<Vec<SyncRun>>,
+}
+
+/// A change the dry run would have made to an agenda item.
+#[derive(Debug, Serialize)]
+#[serde(tag = "change", rename_all = "lowercase")]
+pub enum AgendaChange {
+    New {
+        agenda: Box<Agenda>,
+    },
+    Changed {
+        url: String,
+        fields: Vec<FieldChange>,
+    },
+}
+
+/// A field of an agenda item the dry run would have changed, the values are shown as text.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct FieldChange {
+    pub field: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+impl DryRunAgendaStore {
+    pub fn new(store: Arc<dyn AgendaStore>) -> DryRunAgendaStore {
+        DryRunAgendaStore {
+            store,
+            written_items: Mutex::new(Vec::new()),
+            written_selector_health: Mutex::new(Vec::new()),
+            written_listing_pages: Mutex::new(Vec::new()),
+            written_sync_runs: Mutex::new(Vec::new()),
+        }
+    }
+
+    fn written_item(&self, url: &str) -> Option<Agenda> {
+        self.written_items
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|agenda| agenda.url == url)
+            .cloned()
+    }
+
+    fn record_write(&self, agenda: &Agenda) {
+        let mut written_items = self.written_items.lock().unwrap();
+        match written_items.iter_mut().find(|it| it.url == agenda.url) {
+            Some(written_agenda) => *written_agenda = agenda.clone(),
+            None => written_items.push(agenda.clone()),
+        }
+    }
+
+    /// The new agenda items and the changed fields of the stored ones, comparing the final state
+    /// of every written item with the other store. Writes that only change bookkeeping, like the
+    /// last time an item was seen, are left out.
+    pub async fn changes(&self) -> Result<Vec<AgendaChange>, ErrorKind> {
+        let written_items = self.written_items.lock().unwrap().clone();
+        let mut changes = Vec::new();
+        for agenda in written_items {
+            match self.store.get_agenda_by_url(&agenda.url).await? {
+                None => changes.push(AgendaChange::New {
+                    agenda: Box::new(agenda),
+                }),
+                Some(stored_agenda) => {
+                    let fields = changed_fields(&stored_agenda, &agenda);
+                    if !fields.is_empty() {
+                        changes.push(AgendaChange::Changed {
+                            url: agenda.url,
+                            fields,
+                        });
+                    }
+                }
+            }
+        }
+        Ok(changes)
+    }
+}
+
+#[async_trait]
+impl AgendaStore for DryRunAgendaStore {
+    async fn get_agenda_by_url(&self, url: &str) -> Result<Option<Agenda>, ErrorKind> {
+        match self.written_item(url) {
+            Some(agenda) => Ok(Some(agenda)),
+            None => self.store.get_agenda_by_url(url).await,
+        }
+    }
+
+    async fn get_agenda_by_stable_id(&self, stable_id: &str) -> Result<Option<Agenda>, ErrorKind> {
+        let written_agenda = self
+            .written_items
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|agenda| agenda.stable_id() == stable_id)
+            .cloned();
+        match written_agenda {
+            Some(agenda) => Ok(Some(agenda)),
+            None => self.store.get_agenda_by_stable_id(stable_id).await,
+        }
+    }
+
+    async fn insert_agenda(&self, agenda: &Agenda) -> Result<(), ErrorKind> {
+        self.record_write(agenda);
+        Ok(())
+    }
+
+    async fn update_agenda(&self, agenda: &Agenda) -> Result<(), ErrorKind> {
+        if self.get_agenda_by_url(&agenda.url).await?.is_none() {
+            return Err(ErrorKind::AgendaNotFound {
+                url: agenda.url.clone(),
+            });
+        }
+        self.record_write(agenda);
+        Ok(())
+    }
+
+    async fn agenda_items_for_venue(&self, venue_id: &str) -> Result<Vec<Agenda>, ErrorKind> {
+        let mut agenda_items = self.store.agenda_items_for_venue(venue_id).await?;
+        let written_items = self.written_items.lock().unwrap();
+        for agenda in agenda_items.iter_mut() {
+            if let Some(written_agenda) = written_items.iter().find(|it| it.url == agenda.url) {
+                *agenda = written_agenda.clone();
+            }
+        }
+        let new_items: Vec<Agenda> = written_items
+            .iter()
+            .filter(|agenda| agenda.venue_id == venue_id)
+            .filter(|agenda| agenda_items.iter().all(|it| it.url != agenda.url))
+            .cloned()
+            .collect();
+        agenda_items.extend(new_items);
+        Ok(agenda_items)
+    }
+
+    async fn update_last_seen_at(
+        &self,
+        urls: &[String],
+        seen_at: DateTime<Utc>,
+    ) -> Result<(), ErrorKind> {
+        for url in urls {
+            if let Some(mut agenda) = self.get_agenda_by_url(url).await? {
+                agenda.last_seen_at = Some(seen_at);
+                self.record_write(&agenda);
+            }
+        }
+        Ok(())
+    }
+
+    async fn insert_selector_health(
+        &self,
+        selector_health: &SelectorHealth,
+    ) -> Result<(), ErrorKind> {
+        self.written_selector_health
+            .lock()
+            .unwrap()
+            .push(selector_health.clone());
+        Ok(())
+    }
+
+    async fn selector_health_for_venue(
+        &self,
+        venue_id: &str,
+        limit: usize,
+    ) -> Result<Vec<SelectorHealth>, ErrorKind> {
+        let mut selector_health: Vec<SelectorHealth> = self
+            .written_selector_health
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .filter(|health| health.venue_id == venue_id)
+            .cloned()
+            .collect();
+        selector_health.extend(
+            self.store
+                .selector_health_for_venue(venue_id, limit)
+                .await?,
+        );
+        selector_health.truncate(limit);
+        Ok(selector_health)
+    }
+
+    async fn get_listing_page(&self, url: &str) -> Result<Option<ListingPage>, ErrorKind> {
+        let written_listing_page = self
+            .written_listing_pages
+            .lock()
+            .unwrap()
+            .iter()
+            .find(|listing_page| listing_page.url == url)
+            .cloned();
+        match written_listing_page {
+            Some(listing_page) => Ok(Some(listing_page)),
+            None => self.store.get_listing_page(url).await,
+        }
+    }
+
+    async fn upsert_listing_page(&self, listing_page: &ListingPage) -> Result<(), ErrorKind> {
+        let mut written_listing_pages = self.written_listing_pages.lock().unwrap();
+        match written_listing_pages
+            .iter_mut()
+            .find(|it| it.url == listing_page.url)
+        {
+            Some(written_listing_page) => *written_listing_page = listing_page.clone(),
+            None => written_listing_pages.push(listing_page.clone()),
+        }
+        Ok(())
+    }
+
+    async fn insert_sync_run(&self, sync_run: &SyncRun) -> Result<(), ErrorKind> {
+        self.written_sync_runs
+            .lock()
+            .unwrap()
+            .push(sync_run.clone());
+        Ok(())
+    }
+
+    async fn sync_runs_for_venue(
+        &self,
+        venue_id: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<SyncRun>, ErrorKind> {
+        let mut sync_runs = self.store.sync_runs_for_venue(venue_id, since).await?;
+        sync_runs.extend(
+            self.written_sync_runs
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|sync_run| sync_run.venue_id == venue_id && sync_run.started_at >= since)
+                .cloned(),
+        );
+        Ok(sync_runs)
+    }
+
+    async fn last_successful_sync_run(&self, venue_id: &str) -> Result<Option<SyncRun>, ErrorKind> {
+        let written_sync_run = self
+            .written_sync_runs
+            .lock()
+            .unwrap()
+            .iter()
+            .rev()
+            .find(|sync_run| sync_run.venue_id == venue_id && sync_run.successful)
+            .cloned();
+        match written_sync_run {
+            Some(sync_run) => Ok(Some(sync_run)),
+            None => self.store.last_successful_sync_run(venue_id).await,
+        }
+    }
+}
+
+/// The fields of the agenda item that differ, the fields people see.
+fn changed_fields(before: &Agenda, after: &Agenda) -> Vec<FieldChange> {
+    let date = |date: &Option<DateTime<FixedOffset>>| date.map(|date| date.to_rfc3339());
+    let acts = |acts: &[String]| Some(acts.join(", ")).filter(|acts| !acts.is_empty());
+    let status = |status: AgendaStatus| Some(format!("{:?}", status).to_lowercase());
+    [
+        (
+            "title",
+            Some(before.title.clone()),
+            Some(after.title.clone()),
+        ),
+        (
+            "description",
+            before.description.clone(),
+            after.description.clone(),
+        ),
+        (
+            "start_text",
+            before.start_text.clone(),
+            after.start_text.clone(),
+        ),
+        ("starts_at", date(&before.starts_at), date(&after.starts_at)),
+        ("ends_at", date(&before.ends_at), date(&after.ends_at)),
+        (
+            "doors_open_at",
+            date(&before.doors_open_at),
+            date(&after.doors_open_at),
+        ),
+        ("price", before.price.clone(), after.price.clone()),
+        (
+            "ticket_url",
+            before.ticket_url.clone(),
+            after.ticket_url.clone(),
+        ),
+        (
+            "image_url",
+            before.image_url.clone(),
+            after.image_url.clone(),
+        ),
+        (
+            "support_acts",
+            acts(&before.support_acts),
+            acts(&after.support_acts),
+        ),
+        ("status", status(before.status), status(after.status)),
+    ]
+    .into_iter()
+    .filter(|(_, before, after)| before != after)
+    .map(|(field, before, after)| FieldChange {
+        field: field.to_string(),
+        before,
+        after,
+    })
+    .collect()
+}
+
+impl Display for AgendaChange {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AgendaChange::New { agenda } => write!(
+                f,
+                "+ {} {} ({})",
+                agenda.url,
+                agenda.title,
+                agenda
+                    .starts_at
+                    .map(|starts_at| starts_at.to_rfc3339())
+                    .or_else(|| agenda.start_text.clone())
+                    .unwrap_or_else(|| "no start".to_string())
+            ),
+            AgendaChange::Changed { url, fields } => {
+                write!(f, "~ {}", url)?;
+                for field in fields {
+                    write!(
+                        f,
+                        "\n    {}: {} -> {}",
+                        field.field,
+                        field.before.as_deref().unwrap_or("-"),
+                        field.after.as_deref().unwrap_or("-")
+                    )?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
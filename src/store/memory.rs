@@ -0,0 +1,155 @@
+use crate::agenda::Agenda;
+use crate::health::SelectorHealth;
+use crate::listing_page::ListingPage;
+use crate::store::AgendaStore;
+use crate::sync_run::SyncRun;
+use crate::ErrorKind;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::sync::Mutex;
+
+/// Agenda store that keeps everything in memory, for tests and runs without a database.
+#[derive(Default)]
+pub struct MemoryAgendaStore {
+    agenda_items: Mutex<Vec<Agenda>>,
+    selector_health: Mutex<Vec<SelectorHealth>>,
+    listing_pages: Mutex<Vec<ListingPage>>,
+    sync_runs: Mutex<Vec<SyncRun>>,
+}
+
+impl MemoryAgendaStore {
+    pub fn new() -> MemoryAgendaStore {
+        MemoryAgendaStore::default()
+    }
+}
+
+#[async_trait]
+impl AgendaStore for MemoryAgendaStore {
+    async fn get_agenda_by_url(&self, url: &str) -> Result<Option<Agenda>, ErrorKind> {
+        let agenda_items = self.agenda_items.lock().unwrap();
+        Ok(agenda_items
+            .iter()
+            .find(|agenda| agenda.url == url)
+            .cloned())
+    }
+
+    async fn get_agenda_by_stable_id(&self, stable_id: &str) -> Result<Option<Agenda>, ErrorKind> {
+        let agenda_items = self.agenda_items.lock().unwrap();
+        Ok(agenda_items
+            .iter()
+            .find(|agenda| agenda.stable_id() == stable_id)
+            .cloned())
+    }
+
+    async fn insert_agenda(&self, agenda: &Agenda) -> Result<(), ErrorKind> {
+        self.agenda_items.lock().unwrap().push(agenda.clone());
+        Ok(())
+    }
+
+    async fn update_agenda(&self, agenda: &Agenda) -> Result<(), ErrorKind> {
+        let mut agenda_items = self.agenda_items.lock().unwrap();
+        match agenda_items.iter_mut().find(|it| it.url == agenda.url) {
+            Some(stored_agenda) => {
+                *stored_agenda = agenda.clone();
+                Ok(())
+            }
+            None => Err(ErrorKind::AgendaNotFound {
+                url: agenda.url.clone(),
+            }),
+        }
+    }
+
+    async fn agenda_items_for_venue(&self, venue_id: &str) -> Result<Vec<Agenda>, ErrorKind> {
+        let agenda_items = self.agenda_items.lock().unwrap();
+        Ok(agenda_items
+            .iter()
+            .filter(|agenda| agenda.venue_id == venue_id)
+            .cloned()
+            .collect())
+    }
+
+    async fn update_last_seen_at(
+        &self,
+        urls: &[String],
+        seen_at: DateTime<Utc>,
+    ) -> Result<(), ErrorKind> {
+        let mut agenda_items = self.agenda_items.lock().unwrap();
+        for agenda in agenda_items.iter_mut().filter(|it| urls.contains(&it.url)) {
+            agenda.last_seen_at = Some(seen_at);
+        }
+        Ok(())
+    }
+
+    async fn insert_selector_health(
+        &self,
+        selector_health: &SelectorHealth,
+    ) -> Result<(), ErrorKind> {
+        self.selector_health
+            .lock()
+            .unwrap()
+            .push(selector_health.clone());
+        Ok(())
+    }
+
+    async fn selector_health_for_venue(
+        &self,
+        venue_id: &str,
+        limit: usize,
+    ) -> Result<Vec<SelectorHealth>, ErrorKind> {
+        let selector_health = self.selector_health.lock().unwrap();
+        Ok(selector_health
+            .iter()
+            .rev()
+            .filter(|health| health.venue_id == venue_id)
+            .take(limit)
+            .cloned()
+            .collect())
+    }
+
+    async fn get_listing_page(&self, url: &str) -> Result<Option<ListingPage>, ErrorKind> {
+        let listing_pages = self.listing_pages.lock().unwrap();
+        Ok(listing_pages
+            .iter()
+            .find(|listing_page| listing_page.url == url)
+            .cloned())
+    }
+
+    async fn upsert_listing_page(&self, listing_page: &ListingPage) -> Result<(), ErrorKind> {
+        let mut listing_pages = self.listing_pages.lock().unwrap();
+        match listing_pages
+            .iter_mut()
+            .find(|it| it.url == listing_page.url)
+        {
+            Some(stored_listing_page) => *stored_listing_page = listing_page.clone(),
+            None => listing_pages.push(listing_page.clone()),
+        }
+        Ok(())
+    }
+
+    async fn insert_sync_run(&self, sync_run: &SyncRun) -> Result<(), ErrorKind> {
+        self.sync_runs.lock().unwrap().push(sync_run.clone());
+        Ok(())
+    }
+
+    async fn sync_runs_for_venue(
+        &self,
+        venue_id: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<SyncRun>, ErrorKind> {
+        let sync_runs = self.sync_runs.lock().unwrap();
+        Ok(sync_runs
+            .iter()
+            .filter(|sync_run| sync_run.venue_id == venue_id && sync_run.started_at >= since)
+            .cloned()
+            .collect())
+    }
+
+    async fn last_successful_sync_run(&self, venue_id: &str) -> Result<Option<SyncRun>, ErrorKind> {
+        let sync_runs = self.sync_runs.lock().unwrap();
+        Ok(sync_runs
+            .iter()
+            .rev()
+            .find(|sync_run| sync_run.venue_id == venue_id && sync_run.successful)
+            .cloned())
+    }
+}
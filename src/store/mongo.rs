@@ -0,0 +1,260 @@
+use crate::agenda::{Agenda, AgendaStatus};
+use crate::health::SelectorHealth;
+use crate::listing_page::ListingPage;
+use crate::store::AgendaStore;
+use crate::sync_run::SyncRun;
+use crate::{Config, ErrorKind};
+use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat, Utc};
+use futures::stream::TryStreamExt;
+use mongodb::bson::{doc, Document};
+use mongodb::options::{ClientOptions, FindOneOptions, FindOptions, ReplaceOptions};
+use mongodb::{Client, Collection, Database};
+use tracing::{info, trace};
+
+/// Agenda store backed by a mongo database.
+pub struct MongoAgendaStore {
+    db: Database,
+}
+
+impl MongoAgendaStore {
+    pub fn new(db: Database) -> MongoAgendaStore {
+        MongoAgendaStore { db }
+    }
+
+    /// Creates the agenda collection for the database.
+    fn agenda_collection(&self) -> Collection<Agenda> {
+        self.db.collection::<Agenda>("agenda")
+    }
+
+    /// The agenda collection for writing documents with fields besides those of Agenda.
+    fn agenda_document_collection(&self) -> Collection<Document> {
+        self.db.collection::<Document>("agenda")
+    }
+
+    fn selector_health_collection(&self) -> Collection<SelectorHealth> {
+        self.db.collection::<SelectorHealth>("selector_health")
+    }
+
+    fn listing_page_collection(&self) -> Collection<ListingPage> {
+        self.db.collection::<ListingPage>("listing_page")
+    }
+
+    fn sync_run_collection(&self) -> Collection<SyncRun> {
+        self.db.collection::<SyncRun>("sync_run")
+    }
+
+    /// The sync run collection for writing documents with a started_at that sorts in time.
+    fn sync_run_document_collection(&self) -> Collection<Document> {
+        self.db.collection::<Document>("sync_run")
+    }
+}
+
+/// The agenda item as a document, with its stable id for the lookups by id.
+fn agenda_document(agenda: &Agenda) -> Result<Document, ErrorKind> {
+    let mut document = mongodb::bson::to_document(agenda).map_err(mongodb::error::Error::from)?;
+    document.insert("stable_id", agenda.stable_id());
+    Ok(document)
+}
+
+/// The start of a sync run as text that sorts in time, always with six fraction digits.
+fn started_at_field(started_at: &DateTime<Utc>) -> String {
+    started_at.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+/// The sync run as a document, with its start in the form the lookups compare.
+fn sync_run_document(sync_run: &SyncRun) -> Result<Document, ErrorKind> {
+    let mut document = mongodb::bson::to_document(sync_run).map_err(mongodb::error::Error::from)?;
+    document.insert("started_at", started_at_field(&sync_run.started_at));
+    Ok(document)
+}
+
+#[async_trait]
+impl AgendaStore for MongoAgendaStore {
+    async fn get_agenda_by_url(&self, url: &str) -> Result<Option<Agenda>, ErrorKind> {
+        let optional_agenda = self
+            .agenda_collection()
+            .find_one(doc! {"url": &url}, None)
+            .await?;
+        Ok(optional_agenda)
+    }
+
+    async fn get_agenda_by_stable_id(&self, stable_id: &str) -> Result<Option<Agenda>, ErrorKind> {
+        let optional_agenda = self
+            .agenda_collection()
+            .find_one(doc! {"stable_id": stable_id}, None)
+            .await?;
+        if optional_agenda.is_some() {
+            return Ok(optional_agenda);
+        }
+        // Items stored before the stable id was get it on the first lookup that misses.
+        let mut cursor = self
+            .agenda_collection()
+            .find(doc! {"stable_id": {"$exists": false}}, None)
+            .await?;
+        let mut found_agenda = None;
+        while let Some(agenda) = cursor.try_next().await? {
+            self.agenda_document_collection()
+                .update_one(
+                    doc! {"url": &agenda.url},
+                    doc! {"$set": {"stable_id": agenda.stable_id()}},
+                    None,
+                )
+                .await?;
+            if agenda.stable_id() == stable_id {
+                found_agenda = Some(agenda);
+            }
+        }
+        Ok(found_agenda)
+    }
+
+    async fn insert_agenda(&self, agenda: &Agenda) -> Result<(), ErrorKind> {
+        let _insert_result = self
+            .agenda_document_collection()
+            .insert_one(agenda_document(agenda)?, None)
+            .await?;
+        Ok(())
+    }
+
+    async fn update_agenda(&self, agenda: &Agenda) -> Result<(), ErrorKind> {
+        let update_results = self
+            .agenda_document_collection()
+            .replace_one(doc! { "url": &agenda.url }, agenda_document(agenda)?, None)
+            .await?;
+
+        if update_results.matched_count != 1 {
+            Err(ErrorKind::AgendaNotFound {
+                url: agenda.url.clone(),
+            })
+        } else {
+            Ok(())
+        }
+    }
+
+    async fn agenda_items_for_venue(&self, venue_id: &str) -> Result<Vec<Agenda>, ErrorKind> {
+        let filter = doc! { "venue_id": venue_id };
+        let cursor = self.agenda_collection().find(filter, None).await?;
+        Ok(cursor.try_collect().await?)
+    }
+
+    async fn agenda_items_for_venue_with_status(
+        &self,
+        venue_id: &str,
+        status: AgendaStatus,
+    ) -> Result<Vec<Agenda>, ErrorKind> {
+        let filter = if status == AgendaStatus::Active {
+            // Items stored before the status existed are active.
+            doc! { "venue_id": venue_id, "status": {"$in": ["active", null]} }
+        } else {
+            let status = mongodb::bson::to_bson(&status).map_err(mongodb::error::Error::from)?;
+            doc! { "venue_id": venue_id, "status": status }
+        };
+        let cursor = self.agenda_collection().find(filter, None).await?;
+        Ok(cursor.try_collect().await?)
+    }
+
+    async fn update_last_seen_at(
+        &self,
+        urls: &[String],
+        seen_at: DateTime<Utc>,
+    ) -> Result<(), ErrorKind> {
+        let seen_at = mongodb::bson::to_bson(&seen_at).map_err(mongodb::error::Error::from)?;
+        self.agenda_document_collection()
+            .update_many(
+                doc! {"url": {"$in": urls}},
+                doc! {"$set": {"last_seen_at": seen_at}},
+                None,
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn insert_selector_health(
+        &self,
+        selector_health: &SelectorHealth,
+    ) -> Result<(), ErrorKind> {
+        self.selector_health_collection()
+            .insert_one(selector_health, None)
+            .await?;
+        Ok(())
+    }
+
+    async fn selector_health_for_venue(
+        &self,
+        venue_id: &str,
+        limit: usize,
+    ) -> Result<Vec<SelectorHealth>, ErrorKind> {
+        let options = FindOptions::builder()
+            .sort(doc! { "$natural": -1 })
+            .limit(limit as i64)
+            .build();
+        let cursor = self
+            .selector_health_collection()
+            .find(doc! { "venue_id": venue_id }, options)
+            .await?;
+        Ok(cursor.try_collect().await?)
+    }
+
+    async fn get_listing_page(&self, url: &str) -> Result<Option<ListingPage>, ErrorKind> {
+        Ok(self
+            .listing_page_collection()
+            .find_one(doc! {"url": url}, None)
+            .await?)
+    }
+
+    async fn upsert_listing_page(&self, listing_page: &ListingPage) -> Result<(), ErrorKind> {
+        self.listing_page_collection()
+            .replace_one(
+                doc! {"url": &listing_page.url},
+                listing_page,
+                ReplaceOptions::builder().upsert(true).build(),
+            )
+            .await?;
+        Ok(())
+    }
+
+    async fn insert_sync_run(&self, sync_run: &SyncRun) -> Result<(), ErrorKind> {
+        self.sync_run_document_collection()
+            .insert_one(sync_run_document(sync_run)?, None)
+            .await?;
+        Ok(())
+    }
+
+    async fn sync_runs_for_venue(
+        &self,
+        venue_id: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<SyncRun>, ErrorKind> {
+        let since = started_at_field(&since);
+        let options = FindOptions::builder()
+            .sort(doc! { "started_at": 1 })
+            .build();
+        let cursor = self
+            .sync_run_collection()
+            .find(
+                doc! { "venue_id": venue_id, "started_at": { "$gte": since } },
+                options,
+            )
+            .await?;
+        Ok(cursor.try_collect().await?)
+    }
+
+    async fn last_successful_sync_run(&self, venue_id: &str) -> Result<Option<SyncRun>, ErrorKind> {
+        let options = FindOneOptions::builder()
+            .sort(doc! { "started_at": -1 })
+            .build();
+        Ok(self
+            .sync_run_collection()
+            .find_one(doc! { "venue_id": venue_id, "successful": true }, options)
+            .await?)
+    }
+}
+
+pub async fn create_mongo_connection(config: &Config) -> Result<Database, ErrorKind> {
+    trace!("Connecting mongodb, config {}", config);
+    let client_options = ClientOptions::parse(&config.mongo_url).await?;
+    let client = Client::with_options(client_options)?;
+    info!("Mongo db client connected with config {}", config);
+    let db = client.database(&config.mongo_db);
+    Ok(db)
+}
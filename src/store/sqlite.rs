@@ -0,0 +1,312 @@
+use crate::agenda::{stable_id_for_url, Agenda, AgendaStatus};
+use crate::health::SelectorHealth;
+use crate::listing_page::ListingPage;
+use crate::store::AgendaStore;
+use crate::sync_run::SyncRun;
+use crate::ErrorKind;
+use async_trait::async_trait;
+use chrono::{DateTime, SecondsFormat, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Agenda store backed by an embedded SQLite database. Agenda items, selector health and sync runs
+/// are stored as json documents, with the columns of the lookups next to them.
+pub struct SqliteAgendaStore {
+    connection: Mutex<Connection>,
+}
+
+impl SqliteAgendaStore {
+    /// Open, or create, the database file at path.
+    pub fn open(path: &Path) -> Result<SqliteAgendaStore, ErrorKind> {
+        SqliteAgendaStore::with_connection(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> Result<SqliteAgendaStore, ErrorKind> {
+        SqliteAgendaStore::with_connection(Connection::open_in_memory()?)
+    }
+
+    fn with_connection(connection: Connection) -> Result<SqliteAgendaStore, ErrorKind> {
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS agenda (
+                url TEXT PRIMARY KEY,
+                venue_id TEXT NOT NULL,
+                document TEXT NOT NULL,
+                stable_id TEXT
+            );
+            CREATE INDEX IF NOT EXISTS agenda_venue_id ON agenda (venue_id);
+            CREATE TABLE IF NOT EXISTS selector_health (
+                venue_id TEXT NOT NULL,
+                document TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS selector_health_venue_id ON selector_health (venue_id);
+            CREATE TABLE IF NOT EXISTS listing_page (
+                url TEXT PRIMARY KEY,
+                document TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS sync_run (
+                venue_id TEXT NOT NULL,
+                started_at TEXT NOT NULL,
+                successful INTEGER NOT NULL,
+                document TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS sync_run_venue_id ON sync_run (venue_id, started_at);",
+        )?;
+        add_stable_ids(&connection)?;
+        connection
+            .execute_batch("CREATE INDEX IF NOT EXISTS agenda_stable_id ON agenda (stable_id);")?;
+        Ok(SqliteAgendaStore {
+            connection: Mutex::new(connection),
+        })
+    }
+}
+
+/// Add the stable_id column to databases created before it existed, and fill it in.
+fn add_stable_ids(connection: &Connection) -> Result<(), ErrorKind> {
+    let has_stable_id: bool = connection.query_row(
+        "SELECT COUNT(*) > 0 FROM pragma_table_info('agenda') WHERE name = 'stable_id'",
+        [],
+        |row| row.get(0),
+    )?;
+    if !has_stable_id {
+        connection.execute_batch("ALTER TABLE agenda ADD COLUMN stable_id TEXT;")?;
+    }
+    let mut statement = connection.prepare("SELECT url FROM agenda WHERE stable_id IS NULL")?;
+    let urls = statement
+        .query_map([], |row| row.get::<_, String>(0))?
+        .collect::<Result<Vec<String>, rusqlite::Error>>()?;
+    for url in urls {
+        connection.execute(
+            "UPDATE agenda SET stable_id = ?2 WHERE url = ?1",
+            params![url, stable_id_for_url(&url)],
+        )?;
+    }
+    Ok(())
+}
+
+fn agenda_from_document(document: &str) -> Result<Agenda, ErrorKind> {
+    Ok(serde_json::from_str(document)?)
+}
+
+/// The start of a sync run as text that sorts in time.
+fn started_at_column(started_at: &DateTime<Utc>) -> String {
+    started_at.to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+#[async_trait]
+impl AgendaStore for SqliteAgendaStore {
+    async fn get_agenda_by_url(&self, url: &str) -> Result<Option<Agenda>, ErrorKind> {
+        let connection = self.connection.lock().unwrap();
+        let document: Option<String> = connection
+            .query_row(
+                "SELECT document FROM agenda WHERE url = ?1",
+                params![url],
+                |row| row.get(0),
+            )
+            .optional()?;
+        document
+            .map(|document| agenda_from_document(&document))
+            .transpose()
+    }
+
+    async fn get_agenda_by_stable_id(&self, stable_id: &str) -> Result<Option<Agenda>, ErrorKind> {
+        let connection = self.connection.lock().unwrap();
+        let document: Option<String> = connection
+            .query_row(
+                "SELECT document FROM agenda WHERE stable_id = ?1",
+                params![stable_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        document
+            .map(|document| agenda_from_document(&document))
+            .transpose()
+    }
+
+    async fn insert_agenda(&self, agenda: &Agenda) -> Result<(), ErrorKind> {
+        let document = serde_json::to_string(agenda)?;
+        let connection = self.connection.lock().unwrap();
+        connection.execute(
+            "INSERT INTO agenda (url, venue_id, document, stable_id) VALUES (?1, ?2, ?3, ?4)",
+            params![agenda.url, agenda.venue_id, document, agenda.stable_id()],
+        )?;
+        Ok(())
+    }
+
+    async fn update_agenda(&self, agenda: &Agenda) -> Result<(), ErrorKind> {
+        let document = serde_json::to_string(agenda)?;
+        let connection = self.connection.lock().unwrap();
+        let updated_rows = connection.execute(
+            "UPDATE agenda SET venue_id = ?2, document = ?3 WHERE url = ?1",
+            params![agenda.url, agenda.venue_id, document],
+        )?;
+        if updated_rows != 1 {
+            return Err(ErrorKind::AgendaNotFound {
+                url: agenda.url.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    async fn agenda_items_for_venue(&self, venue_id: &str) -> Result<Vec<Agenda>, ErrorKind> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement =
+            connection.prepare("SELECT document FROM agenda WHERE venue_id = ?1 ORDER BY rowid")?;
+        let documents = statement
+            .query_map(params![venue_id], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<String>, rusqlite::Error>>()?;
+        documents
+            .iter()
+            .map(|document| agenda_from_document(document))
+            .collect()
+    }
+
+    async fn agenda_items_for_venue_with_status(
+        &self,
+        venue_id: &str,
+        status: AgendaStatus,
+    ) -> Result<Vec<Agenda>, ErrorKind> {
+        // Items stored before the status existed are active.
+        let status = serde_json::to_value(status)?;
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(
+            "SELECT document FROM agenda WHERE venue_id = ?1
+             AND COALESCE(json_extract(document, '$.status'), 'active') = ?2 ORDER BY rowid",
+        )?;
+        let documents = statement
+            .query_map(params![venue_id, status.as_str()], |row| {
+                row.get::<_, String>(0)
+            })?
+            .collect::<Result<Vec<String>, rusqlite::Error>>()?;
+        documents
+            .iter()
+            .map(|document| agenda_from_document(document))
+            .collect()
+    }
+
+    async fn update_last_seen_at(
+        &self,
+        urls: &[String],
+        seen_at: DateTime<Utc>,
+    ) -> Result<(), ErrorKind> {
+        let urls = serde_json::to_string(urls)?;
+        let seen_at = serde_json::to_string(&seen_at)?;
+        let connection = self.connection.lock().unwrap();
+        connection.execute(
+            "UPDATE agenda SET document = json_set(document, '$.last_seen_at', json(?2))
+            WHERE url IN (SELECT value FROM json_each(?1))",
+            params![urls, seen_at],
+        )?;
+        Ok(())
+    }
+
+    async fn insert_selector_health(
+        &self,
+        selector_health: &SelectorHealth,
+    ) -> Result<(), ErrorKind> {
+        let document = serde_json::to_string(selector_health)?;
+        let connection = self.connection.lock().unwrap();
+        connection.execute(
+            "INSERT INTO selector_health (venue_id, document) VALUES (?1, ?2)",
+            params![selector_health.venue_id, document],
+        )?;
+        Ok(())
+    }
+
+    async fn selector_health_for_venue(
+        &self,
+        venue_id: &str,
+        limit: usize,
+    ) -> Result<Vec<SelectorHealth>, ErrorKind> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(
+            "SELECT document FROM selector_health WHERE venue_id = ?1 ORDER BY rowid DESC LIMIT ?2",
+        )?;
+        let documents = statement
+            .query_map(params![venue_id, limit as i64], |row| {
+                row.get::<_, String>(0)
+            })?
+            .collect::<Result<Vec<String>, rusqlite::Error>>()?;
+        documents
+            .iter()
+            .map(|document| Ok(serde_json::from_str(document)?))
+            .collect()
+    }
+
+    async fn get_listing_page(&self, url: &str) -> Result<Option<ListingPage>, ErrorKind> {
+        let connection = self.connection.lock().unwrap();
+        let document: Option<String> = connection
+            .query_row(
+                "SELECT document FROM listing_page WHERE url = ?1",
+                params![url],
+                |row| row.get(0),
+            )
+            .optional()?;
+        document
+            .map(|document| Ok(serde_json::from_str(&document)?))
+            .transpose()
+    }
+
+    async fn upsert_listing_page(&self, listing_page: &ListingPage) -> Result<(), ErrorKind> {
+        let document = serde_json::to_string(listing_page)?;
+        let connection = self.connection.lock().unwrap();
+        connection.execute(
+            "INSERT INTO listing_page (url, document) VALUES (?1, ?2)
+             ON CONFLICT (url) DO UPDATE SET document = excluded.document",
+            params![listing_page.url, document],
+        )?;
+        Ok(())
+    }
+
+    async fn insert_sync_run(&self, sync_run: &SyncRun) -> Result<(), ErrorKind> {
+        let document = serde_json::to_string(sync_run)?;
+        let connection = self.connection.lock().unwrap();
+        connection.execute(
+            "INSERT INTO sync_run (venue_id, started_at, successful, document)
+             VALUES (?1, ?2, ?3, ?4)",
+            params![
+                sync_run.venue_id,
+                started_at_column(&sync_run.started_at),
+                sync_run.successful,
+                document
+            ],
+        )?;
+        Ok(())
+    }
+
+    async fn sync_runs_for_venue(
+        &self,
+        venue_id: &str,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<SyncRun>, ErrorKind> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare(
+            "SELECT document FROM sync_run WHERE venue_id = ?1 AND started_at >= ?2
+             ORDER BY started_at, rowid",
+        )?;
+        let documents = statement
+            .query_map(params![venue_id, started_at_column(&since)], |row| {
+                row.get::<_, String>(0)
+            })?
+            .collect::<Result<Vec<String>, rusqlite::Error>>()?;
+        documents
+            .iter()
+            .map(|document| Ok(serde_json::from_str(document)?))
+            .collect()
+    }
+
+    async fn last_successful_sync_run(&self, venue_id: &str) -> Result<Option<SyncRun>, ErrorKind> {
+        let connection = self.connection.lock().unwrap();
+        let document: Option<String> = connection
+            .query_row(
+                "SELECT document FROM sync_run WHERE venue_id = ?1 AND successful
+                 ORDER BY started_at DESC, rowid DESC LIMIT 1",
+                params![venue_id],
+                |row| row.get(0),
+            )
+            .optional()?;
+        document
+            .map(|document| Ok(serde_json::from_str(&document)?))
+            .transpose()
+    }
+}
@@ -0,0 +1,352 @@
+use crate::agenda::Agenda;
+use crate::dates::parse_iso_date_time;
+use crate::urls::{absolute_url, canonical_url, normalize_url};
+use chrono::{DateTime, FixedOffset, NaiveTime};
+use scraper::{ElementRef, Html, Node, Selector};
+use serde_json::{Map, Value};
+use sha2::{Digest, Sha256};
+use std::sync::OnceLock;
+use tracing::debug;
+use url::Url;
+
+/// An event from the schema.org JSON-LD or microdata of a page.
+#[derive(Debug, Clone, Default)]
+pub struct StructuredEvent {
+    pub url: Option<String>,
+    /// The link to the event page as given, resolved against the page.
+    pub details_url: Option<String>,
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub starts_at: Option<DateTime<FixedOffset>>,
+    pub ends_at: Option<DateTime<FixedOffset>>,
+    pub doors_open_at: Option<DateTime<FixedOffset>>,
+    /// The start date has no time.
+    pub all_day: bool,
+    pub price: Option<String>,
+    pub ticket_url: Option<String>,
+    pub image_url: Option<String>,
+    pub performers: Vec<String>,
+    /// Hash of the structured data of the event, to detect changes between syncs.
+    pub hash: String,
+}
+
+fn json_ld_selector() -> &'static Selector {
+    static SELECTOR: OnceLock<Selector> = OnceLock::new();
+    SELECTOR.get_or_init(|| Selector::parse(r#"script[type="application/ld+json"]"#).unwrap())
+}
+
+fn microdata_selector() -> &'static Selector {
+    static SELECTOR: OnceLock<Selector> = OnceLock::new();
+    SELECTOR.get_or_init(|| Selector::parse("[itemscope][itemtype]").unwrap())
+}
+
+/// The events in the JSON-LD scripts and the microdata of the document, links resolved against
+/// the url of the page.
+pub fn events_from_document(document: &Html, page_url: &Url) -> Vec<StructuredEvent> {
+    let mut event_values = Vec::new();
+    for script in document.select(json_ld_selector()) {
+        let json = script.text().collect::<String>();
+        match serde_json::from_str::<Value>(&json) {
+            Ok(value) => collect_events(&value, &mut event_values),
+            Err(err) => debug!("Cannot parse JSON-LD on {}: {}", page_url, err),
+        }
+    }
+    for element in document.select(microdata_selector()) {
+        // Nested events are part of the item around them.
+        let is_top_level = element.value().attr("itemprop").is_none();
+        if is_top_level && is_event_type(element.value().attr("itemtype").unwrap_or_default()) {
+            event_values.push(microdata_item(&element));
+        }
+    }
+    event_values
+        .iter()
+        .map(|value| event_from_value(value, page_url))
+        .collect()
+}
+
+/// Agenda item for the event, None when the event has no url or name.
+pub fn agenda_from_event(event: &StructuredEvent, venue_id: &str) -> Option<Agenda> {
+    let mut agenda = Agenda {
+        url: event.url.clone()?,
+        details_url: event.details_url.clone(),
+        title: event.name.clone()?,
+        venue_id: venue_id.to_string(),
+        needs_details: true,
+        listing_hash: Some(event.hash.clone()),
+        ..Default::default()
+    };
+    apply_event(event, &mut agenda);
+    Some(agenda)
+}
+
+/// Take over the fields the event has, the fields it does not have are left alone.
+pub fn apply_event(event: &StructuredEvent, agenda: &mut Agenda) {
+    if let Some(name) = &event.name {
+        agenda.title = name.clone();
+    }
+    if event.description.is_some() {
+        agenda.description = event.description.clone();
+    }
+    if event.starts_at.is_some() {
+        agenda.starts_at = event.starts_at;
+        agenda.ends_at = event.ends_at;
+        agenda.doors_open_at = event.doors_open_at;
+        agenda.all_day = event.all_day;
+    }
+    if event.price.is_some() {
+        agenda.price = event.price.clone();
+    }
+    if event.ticket_url.is_some() {
+        agenda.ticket_url = event.ticket_url.clone();
+    }
+    if event.image_url.is_some() {
+        agenda.image_url = event.image_url.clone();
+    }
+    // The performers besides the main act in the title.
+    let support_acts: Vec<String> = event
+        .performers
+        .iter()
+        .filter(|performer| !agenda.title.contains(performer.as_str()))
+        .cloned()
+        .collect();
+    if !support_acts.is_empty() {
+        agenda.support_acts = support_acts;
+    }
+}
+
+/// Event types are Event and its subtypes, like MusicEvent or TheaterEvent, and Festival.
+fn is_event_type(schema_type: &str) -> bool {
+    let schema_type = schema_type.rsplit('/').next().unwrap_or_default();
+    schema_type.ends_with("Event") || schema_type == "Festival"
+}
+
+fn has_event_type(object: &Map<String, Value>) -> bool {
+    match object.get("@type") {
+        Some(Value::String(schema_type)) => is_event_type(schema_type),
+        Some(Value::Array(schema_types)) => schema_types
+            .iter()
+            .filter_map(Value::as_str)
+            .any(is_event_type),
+        _ => false,
+    }
+}
+
+/// Collect the events anywhere in the JSON-LD, in `@graph`, item lists or other wrappers.
+fn collect_events(value: &Value, events: &mut Vec<Value>) {
+    match value {
+        Value::Array(values) => values
+            .iter()
+            .for_each(|value| collect_events(value, events)),
+        Value::Object(object) if has_event_type(object) => events.push(value.clone()),
+        Value::Object(object) => object
+            .values()
+            .for_each(|value| collect_events(value, events)),
+        _ => {}
+    }
+}
+
+/// The microdata item as JSON-LD, properties holding more than one value become arrays.
+fn microdata_item(element: &ElementRef) -> Value {
+    let mut properties = Map::new();
+    if let Some(item_type) = element.value().attr("itemtype") {
+        properties.insert("@type".to_string(), Value::String(item_type.to_string()));
+    }
+    collect_microdata_properties(element, &mut properties);
+    Value::Object(properties)
+}
+
+fn collect_microdata_properties(element: &ElementRef, properties: &mut Map<String, Value>) {
+    for child in element.children().filter_map(ElementRef::wrap) {
+        let child_element = child.value();
+        if let Some(names) = child_element.attr("itemprop") {
+            let value = if child_element.attr("itemscope").is_some() {
+                microdata_item(&child)
+            } else {
+                Value::String(microdata_value(&child))
+            };
+            for name in names.split_whitespace() {
+                match properties.get_mut(name) {
+                    Some(Value::Array(values)) => values.push(value.clone()),
+                    Some(existing) => {
+                        *existing = Value::Array(vec![existing.clone(), value.clone()]);
+                    }
+                    None => {
+                        properties.insert(name.to_string(), value.clone());
+                    }
+                }
+            }
+        }
+        // The properties in a nested item belong to that item.
+        if child_element.attr("itemscope").is_none() {
+            collect_microdata_properties(&child, properties);
+        }
+    }
+}
+
+/// The value of a microdata property, from the attribute the element keeps it in, otherwise its
+/// text.
+fn microdata_value(element: &ElementRef) -> String {
+    let value = element.value();
+    let attribute = match value.name() {
+        _ if value.attr("content").is_some() => "content",
+        "a" | "area" | "link" => "href",
+        "img" | "audio" | "video" | "source" | "iframe" | "embed" => "src",
+        "time" if value.attr("datetime").is_some() => "datetime",
+        "data" | "meter" => "value",
+        _ => return collapsed_text(element.text()),
+    };
+    value.attr(attribute).unwrap_or_default().trim().to_string()
+}
+
+fn collapsed_text<'a>(texts: impl Iterator<Item = &'a str>) -> String {
+    texts
+        .flat_map(|text| text.split_whitespace())
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+/// The text of a JSON-LD string, which may hold html entities or markup.
+pub(crate) fn plain_text(text: &str) -> String {
+    let fragment = Html::parse_fragment(text);
+    let texts = fragment.tree.nodes().filter_map(|node| match node.value() {
+        Node::Text(text) => Some(&text.text[..]),
+        _ => None,
+    });
+    collapsed_text(texts)
+}
+
+/// The values of a property holding one value or an array of values.
+fn values_of<'a>(value: &'a Value, name: &str) -> Vec<&'a Value> {
+    match value.get(name) {
+        Some(Value::Array(values)) => values.iter().collect(),
+        Some(value) => vec![value],
+        None => Vec::new(),
+    }
+}
+
+fn text_of(value: &Value, name: &str) -> Option<String> {
+    values_of(value, name)
+        .into_iter()
+        .find_map(Value::as_str)
+        .map(plain_text)
+        .filter(|text| !text.is_empty())
+}
+
+/// A link property, a url text or an object with a url, like an ImageObject.
+fn link_of(value: &Value, name: &str, page_url: &Url) -> Option<String> {
+    values_of(value, name).into_iter().find_map(|value| {
+        let href = match value {
+            Value::String(href) => href.as_str(),
+            Value::Object(object) => object.get("url").and_then(Value::as_str)?,
+            _ => return None,
+        };
+        absolute_url(page_url, href).ok().map(|url| url.to_string())
+    })
+}
+
+fn price_of(offer: &Value) -> Option<String> {
+    let price = match offer.get("price").or_else(|| offer.get("lowPrice"))? {
+        Value::String(price) => price.trim().to_string(),
+        Value::Number(price) => price.to_string(),
+        _ => return None,
+    };
+    if price.is_empty() {
+        return None;
+    }
+    match offer.get("priceCurrency").and_then(Value::as_str) {
+        Some("EUR") | None => Some(format!("€ {}", price.replace('.', ","))),
+        Some(currency) => Some(format!("{} {}", price, currency)),
+    }
+}
+
+fn event_from_value(value: &Value, page_url: &Url) -> StructuredEvent {
+    let start = text_of(value, "startDate").and_then(|text| parse_iso_date_time(&text));
+    let starts_at = start.map(|(starts_at, _)| starts_at);
+    let ends_at = text_of(value, "endDate")
+        .and_then(|text| parse_iso_date_time(&text))
+        .map(|(ends_at, _)| ends_at);
+    // The door time is a date-time, or only a time on the day of the start.
+    let doors_open_at = text_of(value, "doorTime").and_then(|text| {
+        parse_iso_date_time(&text)
+            .map(|(doors_open_at, _)| doors_open_at)
+            .or_else(|| {
+                let time = NaiveTime::parse_from_str(text.trim(), "%H:%M")
+                    .or_else(|_| NaiveTime::parse_from_str(text.trim(), "%H:%M:%S"))
+                    .ok()?;
+                let starts_at = starts_at?;
+                starts_at
+                    .date_naive()
+                    .and_time(time)
+                    .and_local_timezone(*starts_at.offset())
+                    .single()
+            })
+    });
+
+    let offers = values_of(value, "offers");
+    let prices: Vec<String> = offers.iter().filter_map(|offer| price_of(offer)).collect();
+    let ticket_url = offers
+        .iter()
+        .find_map(|offer| link_of(offer, "url", page_url));
+    let performers = values_of(value, "performer")
+        .into_iter()
+        .filter_map(|performer| match performer {
+            Value::String(name) => Some(plain_text(name)),
+            _ => text_of(performer, "name"),
+        })
+        .filter(|name| !name.is_empty())
+        .collect();
+
+    StructuredEvent {
+        url: text_of(value, "url").and_then(|href| canonical_url(page_url, &href).ok()),
+        details_url: text_of(value, "url")
+            .and_then(|href| absolute_url(page_url, &href).ok())
+            .map(|url| url.to_string()),
+        name: text_of(value, "name"),
+        description: text_of(value, "description"),
+        starts_at,
+        ends_at,
+        doors_open_at,
+        all_day: start.map(|(_, all_day)| all_day).unwrap_or_default(),
+        price: if prices.is_empty() {
+            None
+        } else {
+            Some(prices.join(" / "))
+        },
+        ticket_url,
+        image_url: link_of(value, "image", page_url),
+        performers,
+        hash: format!("{:x}", Sha256::digest(value.to_string().as_bytes())),
+    }
+}
+
+/// The event on the details page of an agenda item, the event with the url of the page, or
+/// otherwise the only event on the page.
+pub fn details_event(document: &Html, page_url: &Url) -> Option<StructuredEvent> {
+    let mut events = events_from_document(document, page_url);
+    let page_url = normalize_url(page_url.clone());
+    match events
+        .iter()
+        .position(|event| event.url.as_deref() == Some(page_url.as_str()))
+    {
+        Some(position) => Some(events.swap_remove(position)),
+        None if events.len() == 1 => events.pop(),
+        None => None,
+    }
+}
+
+/// Fill the fields the structured data of the agenda item lacks from the item found by the css
+/// selectors.
+pub fn fill_missing_fields(agenda: &mut Agenda, css_agenda: &Agenda) {
+    if agenda.description.is_none() {
+        agenda.description = css_agenda.description.clone();
+    }
+    if agenda.starts_at.is_none() {
+        agenda.start_text = css_agenda.start_text.clone();
+    }
+    // Changes to either are changes to the listing.
+    let mut hasher = Sha256::new();
+    hasher.update(agenda.listing_hash.as_deref().unwrap_or_default());
+    hasher.update([0]);
+    hasher.update(css_agenda.listing_hash.as_deref().unwrap_or_default());
+    agenda.listing_hash = Some(format!("{:x}", hasher.finalize()));
+}
@@ -0,0 +1,182 @@
+use crate::registry::{SyncJob, VenueSyncReport};
+use crate::{ErrorKind, SyncingResult};
+use chrono::{DateTime, NaiveDate, Utc};
+use serde::{Deserialize, Serialize};
+use std::fmt::{Display, Formatter};
+
+/// The sync of a single venue in a run of the registry, with its results and errors.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SyncRun {
+    pub venue_id: String,
+    pub job: SyncJob,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub duration_ms: i64,
+    /// The listing and the details synced without errors.
+    pub successful: bool,
+    /// The result of the listing sync, or of the details sync of a details job. The partial result
+    /// of a failed listing sync.
+    pub result: Option<SyncingResult>,
+    pub details_result: Option<SyncingResult>,
+    /// The error of a failed sync and the urls that could not be fetched.
+    pub errors: Vec<String>,
+}
+
+/// The sync runs of a venue on a day, added up.
+#[derive(Debug, Serialize, PartialEq, Eq)]
+pub struct SyncRunTrend {
+    pub venue_id: String,
+    pub date: NaiveDate,
+    pub runs: u32,
+    pub successful_runs: u32,
+    pub average_duration_ms: i64,
+    pub total_items: u32,
+    pub total_items_inserted: u32,
+    pub total_items_updated: u32,
+    pub total_items_removed: u32,
+    pub total_unparseable_items: u32,
+    pub total_urls_fetched: u32,
+    pub total_urls_unfetchable: u32,
+}
+
+impl SyncRun {
+    pub fn from_report(
+        venue_report: &VenueSyncReport,
+        job: SyncJob,
+        started_at: DateTime<Utc>,
+        finished_at: DateTime<Utc>,
+    ) -> SyncRun {
+        let mut errors = Vec::new();
+        let mut result_of = |result: &Result<SyncingResult, ErrorKind>| match result {
+            Ok(result) => {
+                errors.extend(result.fetched_urls.iter().filter_map(|it| it.error.clone()));
+                Some(result.clone())
+            }
+            Err(err) => {
+                errors.push(err.to_string());
+                None
+            }
+        };
+        // A failed listing sync keeps what it did until it failed.
+        let result = result_of(&venue_report.result).or(venue_report.partial_result.clone());
+        let details_result = venue_report.details_result.as_ref().and_then(result_of);
+        SyncRun {
+            venue_id: venue_report.venue_id.clone(),
+            job,
+            started_at,
+            finished_at,
+            duration_ms: (finished_at - started_at).num_milliseconds(),
+            successful: venue_report.is_ok(),
+            result,
+            details_result,
+            errors,
+        }
+    }
+
+    /// The listing and details results of the run.
+    fn results(&self) -> impl Iterator<Item = &SyncingResult> {
+        self.result.iter().chain(self.details_result.iter())
+    }
+}
+
+impl Display for SyncRun {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {:?} at {} in {} ms: {}",
+            self.venue_id,
+            self.job,
+            self.started_at.to_rfc3339(),
+            self.duration_ms,
+            if self.successful { "ok" } else { "failed" }
+        )?;
+        if let Some(result) = &self.result {
+            write!(
+                f,
+                ", {} items, {} inserted, {} updated, {} unparseable",
+                result.total_items,
+                result.total_items_inserted,
+                result.total_items_updated,
+                result.total_unparseable_items
+            )?;
+        }
+        for error in &self.errors {
+            write!(f, "\n    {}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for SyncRunTrend {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} {}: {} runs, {} successful, {} ms on average, {} items, {} inserted, {} updated, \
+             {} removed, {} unparseable, {} of {} urls unfetchable",
+            self.venue_id,
+            self.date,
+            self.runs,
+            self.successful_runs,
+            self.average_duration_ms,
+            self.total_items,
+            self.total_items_inserted,
+            self.total_items_updated,
+            self.total_items_removed,
+            self.total_unparseable_items,
+            self.total_urls_unfetchable,
+            self.total_urls_fetched
+        )
+    }
+}
+
+/// The sync runs per venue per day, in the order of the days, a venue at a time.
+pub fn daily_trends(sync_runs: &[SyncRun]) -> Vec<SyncRunTrend> {
+    let mut trends: Vec<SyncRunTrend> = Vec::new();
+    let mut durations: Vec<i64> = Vec::new();
+    for sync_run in sync_runs {
+        let date = sync_run.started_at.date_naive();
+        let index = match trends
+            .iter()
+            .position(|it| it.venue_id == sync_run.venue_id && it.date == date)
+        {
+            Some(index) => index,
+            None => {
+                trends.push(SyncRunTrend {
+                    venue_id: sync_run.venue_id.clone(),
+                    date,
+                    runs: 0,
+                    successful_runs: 0,
+                    average_duration_ms: 0,
+                    total_items: 0,
+                    total_items_inserted: 0,
+                    total_items_updated: 0,
+                    total_items_removed: 0,
+                    total_unparseable_items: 0,
+                    total_urls_fetched: 0,
+                    total_urls_unfetchable: 0,
+                });
+                durations.push(0);
+                trends.len() - 1
+            }
+        };
+        let trend = &mut trends[index];
+        trend.runs += 1;
+        if sync_run.successful {
+            trend.successful_runs += 1;
+        }
+        durations[index] += sync_run.duration_ms;
+        trend.average_duration_ms = durations[index] / trend.runs as i64;
+        for result in sync_run.results() {
+            trend.total_items += result.total_items;
+            trend.total_items_inserted += result.total_items_inserted;
+            trend.total_items_updated += result.total_items_updated;
+            trend.total_items_removed += result.total_items_removed;
+            trend.total_unparseable_items += result.total_unparseable_items;
+            trend.total_urls_fetched += result.total_urls_fetched;
+            trend.total_urls_unfetchable += result.total_urls_unfetchable;
+        }
+    }
+    trends
+        .sort_by(|trend, other| (&trend.venue_id, trend.date).cmp(&(&other.venue_id, other.date)));
+    trends
+}
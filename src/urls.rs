@@ -0,0 +1,57 @@
+use crate::ErrorKind;
+use url::Url;
+
+/// Query parameters that only track where a visitor came from, prefixes end with `_`.
+const TRACKING_PARAMETERS: [&str; 8] = [
+    "utm_", "fbclid", "gclid", "dclid", "msclkid", "mc_cid", "mc_eid", "_ga",
+];
+
+/// The absolute url of a link on the page, relative and protocol-relative links are resolved
+/// against the url of the page.
+pub fn absolute_url(page_url: &Url, href: &str) -> Result<Url, ErrorKind> {
+    Ok(page_url.join(href.trim())?)
+}
+
+/// The canonical url of a link to an agenda item, the dedup key of the agenda items. The link is
+/// resolved against the url of the page and normalized with normalize_url. The details page is
+/// fetched at the link as listed, see absolute_url.
+pub fn canonical_url(page_url: &Url, href: &str) -> Result<String, ErrorKind> {
+    Ok(normalize_url(absolute_url(page_url, href)?).to_string())
+}
+
+/// Normalize the url, so links to the same page get the same url: the fragment and tracking
+/// parameters are dropped, and paths that do not end in a file name end with a slash.
+pub fn normalize_url(mut url: Url) -> Url {
+    url.set_fragment(None);
+
+    if url.query().is_some() {
+        let query_pairs: Vec<(String, String)> = url
+            .query_pairs()
+            .filter(|(name, _)| !is_tracking_parameter(name))
+            .map(|(name, value)| (name.into_owned(), value.into_owned()))
+            .collect();
+        if query_pairs.is_empty() {
+            url.set_query(None);
+        } else {
+            url.query_pairs_mut().clear().extend_pairs(query_pairs);
+        }
+    }
+
+    let last_segment = url.path().rsplit('/').next().unwrap_or_default();
+    if !last_segment.is_empty() && !last_segment.contains('.') {
+        let path = format!("{}/", url.path());
+        url.set_path(&path);
+    }
+    url
+}
+
+fn is_tracking_parameter(name: &str) -> bool {
+    let name = name.to_lowercase();
+    TRACKING_PARAMETERS.iter().any(|parameter| {
+        if parameter.ends_with('_') {
+            name.starts_with(parameter)
+        } else {
+            name == *parameter
+        }
+    })
+}
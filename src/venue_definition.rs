@@ -0,0 +1,477 @@
+use crate::agenda::Venue;
+use crate::json_path::JsonPath;
+use crate::json_source::{EmbeddedIn, JsonFields, JsonSource};
+use crate::pagination::{Pagination, Strategy, OFFSET_PLACEHOLDER, PAGE_PLACEHOLDER};
+use crate::parser;
+use crate::parser::{CssSelectors, DetailSelectors};
+use crate::ErrorKind;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::Path;
+use url::Url;
+
+/// The contents of a venues file, a list of `[[venue]]` entries.
+#[derive(Debug, Deserialize)]
+struct VenuesFile {
+    #[serde(default, rename = "venue")]
+    venues: Vec<VenueDefinition>,
+}
+
+/// Declarative description of a venue and how its agenda is scraped.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct VenueDefinition {
+    pub id: String,
+    pub name: String,
+    pub agenda_url: String,
+    /// Street address of the venue, the location of its events in exports.
+    pub address: Option<String>,
+    /// Tags to select groups of venues, for example by city.
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Fetch the details page of new agenda items after syncing the agenda.
+    #[serde(default)]
+    pub fetch_details: bool,
+    /// Details pages fetched at the same time, the politeness of the http sender still applies.
+    #[serde(default = "default_max_concurrent_details")]
+    pub max_concurrent_details: usize,
+    /// Hours an agenda item must be missing from the listing before it is marked removed.
+    #[serde(default = "default_removal_grace_hours")]
+    pub removal_grace_hours: i64,
+    pub pagination: Option<PaginationDefinition>,
+    pub incremental: Option<IncrementalDefinition>,
+    /// Read the agenda items from the schema.org JSON-LD and microdata of the pages, the
+    /// selectors fill in the fields the structured data lacks.
+    #[serde(default)]
+    pub structured_data: bool,
+    pub selectors: Option<SelectorsDefinition>,
+    /// Read the agenda items from JSON instead of the html of the pages.
+    pub json: Option<JsonDefinition>,
+    pub detail_selectors: Option<DetailSelectorsDefinition>,
+}
+
+/// How the agenda pages after the agenda url are found, at most max_pages pages in total.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PaginationDefinition {
+    #[serde(flatten)]
+    pub strategy: PaginationStrategy,
+    #[serde(default = "default_max_pages")]
+    pub max_pages: u32,
+}
+
+/// Incremental syncs stop after known_pages consecutive pages without new agenda items. They do not
+/// mark removed items, so a full sync walks all pages when the last one is full_sync_hours ago.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IncrementalDefinition {
+    #[serde(default = "default_known_pages")]
+    pub known_pages: u32,
+    #[serde(default = "default_full_sync_hours")]
+    pub full_sync_hours: i64,
+}
+
+/// The pagination strategies, selected by `strategy` in the definition.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "strategy", rename_all = "snake_case")]
+pub enum PaginationStrategy {
+    /// Follow the link to the next page, found by the selector.
+    NextLink {
+        #[serde(default = "default_next_link_selector")]
+        selector: String,
+    },
+    /// Substitute `{page}` in the template, counting from first_page, until an empty page or a
+    /// page that is not found.
+    Numbered {
+        url_template: String,
+        #[serde(default = "default_first_page")]
+        first_page: u32,
+    },
+    /// Set the offset and limit query parameters of the agenda url, until a page has less than
+    /// limit items.
+    Offset {
+        #[serde(default = "default_offset_parameter")]
+        offset_parameter: String,
+        #[serde(default = "default_limit_parameter")]
+        limit_parameter: String,
+        limit: u32,
+    },
+    /// Load more items from a JSON endpoint, `{page}` in the template counts from first_page and
+    /// `{offset}` is the number of items so far. The html of the items is at the JSON pointer
+    /// html_pointer, there are more pages as long as the boolean at has_more_pointer is true.
+    LoadMore {
+        url_template: String,
+        #[serde(default = "default_first_page")]
+        first_page: u32,
+        html_pointer: String,
+        has_more_pointer: Option<String>,
+    },
+}
+
+/// The css selectors, as text, used to find agenda items on a page.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SelectorsDefinition {
+    pub agenda_item: String,
+    pub url: String,
+    pub title: String,
+    pub description: String,
+    /// The date of the agenda item on the listing page.
+    pub date: Option<String>,
+    /// Take the date from this attribute of the date element instead of its text.
+    pub date_attribute: Option<String>,
+}
+
+/// Where the JSON with the agenda items is and how its fields map onto agenda items. Without a
+/// script or variable the pages are JSON, like the responses of a wp-json endpoint.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct JsonDefinition {
+    /// Selector of the script element holding the JSON, like `script#__NEXT_DATA__`.
+    pub script: Option<String>,
+    /// The variable a script assigns the JSON to, like `window.__NUXT__`.
+    pub variable: Option<String>,
+    /// JSONPath-style path of the agenda items, like `$.props.pageProps.events[*]`.
+    pub items: String,
+    pub fields: JsonFieldsDefinition,
+}
+
+/// The JSONPath-style paths of the fields, relative to an agenda item.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct JsonFieldsDefinition {
+    pub url: String,
+    pub title: String,
+    pub description: Option<String>,
+    pub start: Option<String>,
+    pub end: Option<String>,
+    pub price: Option<String>,
+    pub ticket_url: Option<String>,
+    pub image: Option<String>,
+}
+
+fn default_max_pages() -> u32 {
+    50
+}
+
+fn default_known_pages() -> u32 {
+    2
+}
+
+fn default_full_sync_hours() -> i64 {
+    24
+}
+
+fn default_next_link_selector() -> String {
+    "link[rel=next], a[rel=next]".to_string()
+}
+
+fn default_first_page() -> u32 {
+    2
+}
+
+fn default_offset_parameter() -> String {
+    "offset".to_string()
+}
+
+fn default_limit_parameter() -> String {
+    "limit".to_string()
+}
+
+fn default_removal_grace_hours() -> i64 {
+    24
+}
+
+fn default_max_concurrent_details() -> usize {
+    4
+}
+
+fn default_image_attribute() -> String {
+    "src".to_string()
+}
+
+/// The css selectors, as text, used to find the fields on the details page of an agenda item.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct DetailSelectorsDefinition {
+    pub start: Option<String>,
+    pub doors_open: Option<String>,
+    pub price: Option<String>,
+    pub ticket_url: Option<String>,
+    pub image: Option<String>,
+    /// The attribute of the image element holding the url, lazy loading images use `data-src`.
+    #[serde(default = "default_image_attribute")]
+    pub image_attribute: String,
+    pub long_description: Option<String>,
+    pub support_acts: Option<String>,
+    pub support_acts_prefix: Option<String>,
+}
+
+impl Display for VenueDefinition {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("VenueDefinition")
+            .field("id", &self.id)
+            .field("name", &self.name)
+            .finish()
+    }
+}
+
+impl VenueDefinition {
+    /// Hash of the whole definition, a changed definition may parse the same page differently.
+    pub fn definition_hash(&self) -> Result<String, ErrorKind> {
+        let mut hasher = Sha256::new();
+        hasher.update(serde_json::to_string(self)?);
+        Ok(format!("{:x}", hasher.finalize()))
+    }
+
+    pub fn venue(&self) -> Venue {
+        Venue {
+            venue_id: self.id.clone(),
+            name: self.name.clone(),
+            address: self.address.clone(),
+        }
+    }
+
+    /// Compile the pagination of the definition, a single page if none is defined.
+    pub(crate) fn pagination(&self) -> Result<Pagination, ErrorKind> {
+        let pagination = match &self.pagination {
+            Some(pagination) => pagination,
+            None => return Ok(Pagination::single_page()),
+        };
+        if pagination.max_pages == 0 {
+            return Err(self.invalid("pagination max_pages must be at least 1".to_string()));
+        }
+        let strategy = match &pagination.strategy {
+            PaginationStrategy::NextLink { selector } => Strategy::NextLink {
+                selector: self.selector_for("pagination selector", selector)?,
+            },
+            PaginationStrategy::Numbered {
+                url_template,
+                first_page,
+            } => {
+                self.validate_url_template(url_template, &[PAGE_PLACEHOLDER])?;
+                Strategy::Numbered {
+                    url_template: url_template.clone(),
+                    first_page: *first_page,
+                }
+            }
+            PaginationStrategy::Offset {
+                offset_parameter,
+                limit_parameter,
+                limit,
+            } => {
+                if *limit == 0 {
+                    return Err(self.invalid("pagination limit must be at least 1".to_string()));
+                }
+                Strategy::Offset {
+                    offset_parameter: offset_parameter.clone(),
+                    limit_parameter: limit_parameter.clone(),
+                    limit: *limit,
+                }
+            }
+            PaginationStrategy::LoadMore {
+                url_template,
+                first_page,
+                html_pointer,
+                has_more_pointer,
+            } => {
+                self.validate_url_template(url_template, &[PAGE_PLACEHOLDER, OFFSET_PLACEHOLDER])?;
+                Strategy::LoadMore {
+                    url_template: url_template.clone(),
+                    first_page: *first_page,
+                    html_pointer: html_pointer.clone(),
+                    has_more_pointer: has_more_pointer.clone(),
+                }
+            }
+        };
+        Ok(Pagination::new(strategy, pagination.max_pages))
+    }
+
+    /// The url template must contain one of the placeholders and be a valid url.
+    fn validate_url_template(
+        &self,
+        url_template: &str,
+        placeholders: &[&str],
+    ) -> Result<(), ErrorKind> {
+        if !placeholders
+            .iter()
+            .any(|placeholder| url_template.contains(placeholder))
+        {
+            return Err(self.invalid(format!(
+                "pagination url_template does not contain {}",
+                placeholders.join(" or ")
+            )));
+        }
+        let url = placeholders
+            .iter()
+            .fold(url_template.to_string(), |url, placeholder| {
+                url.replace(placeholder, "1")
+            });
+        Url::parse(&url)
+            .map_err(|err| self.invalid(format!("pagination url_template is invalid: {}", err)))?;
+        Ok(())
+    }
+
+    /// Compile the selectors of the definition, None for venues using only structured data.
+    pub(crate) fn css_selectors(&self) -> Result<Option<CssSelectors>, ErrorKind> {
+        let selectors = match &self.selectors {
+            Some(selectors) => selectors,
+            None => return Ok(None),
+        };
+        Ok(Some(CssSelectors {
+            agenda_item: self.selector_for("agenda_item", &selectors.agenda_item)?,
+            url: self.selector_for("url", &selectors.url)?,
+            title: self.selector_for("title", &selectors.title)?,
+            description: self.selector_for("description", &selectors.description)?,
+            date: self.optional_selector_for("date", &selectors.date)?,
+            date_attribute: selectors.date_attribute.clone(),
+        }))
+    }
+
+    /// Compile the json source of the definition, None for venues reading the html of the pages.
+    pub(crate) fn json_source(&self) -> Result<Option<JsonSource>, ErrorKind> {
+        let json = match &self.json {
+            Some(json) => json,
+            None => return Ok(None),
+        };
+        let embedded_in = match (&json.script, &json.variable) {
+            (None, None) => EmbeddedIn::Page,
+            (Some(script), None) => EmbeddedIn::Script(self.selector_for("json script", script)?),
+            (None, Some(variable)) => EmbeddedIn::Variable(variable.clone()),
+            (Some(_), Some(_)) => {
+                return Err(self.invalid("json has both a script and a variable".to_string()))
+            }
+        };
+        let fields = &json.fields;
+        Ok(Some(JsonSource {
+            embedded_in,
+            items: self.json_path_for("items", &json.items)?,
+            fields: JsonFields {
+                url: self.json_path_for("url", &fields.url)?,
+                title: self.json_path_for("title", &fields.title)?,
+                description: self.optional_json_path_for("description", &fields.description)?,
+                start: self.optional_json_path_for("start", &fields.start)?,
+                end: self.optional_json_path_for("end", &fields.end)?,
+                price: self.optional_json_path_for("price", &fields.price)?,
+                ticket_url: self.optional_json_path_for("ticket_url", &fields.ticket_url)?,
+                image: self.optional_json_path_for("image", &fields.image)?,
+            },
+        }))
+    }
+
+    fn optional_json_path_for(
+        &self,
+        name: &str,
+        path: &Option<String>,
+    ) -> Result<Option<JsonPath>, ErrorKind> {
+        path.as_ref()
+            .map(|path| self.json_path_for(name, path))
+            .transpose()
+    }
+
+    fn json_path_for(&self, name: &str, path: &str) -> Result<JsonPath, ErrorKind> {
+        JsonPath::parse(path)
+            .map_err(|err| self.invalid(format!("json path {} is invalid: {}", name, err)))
+    }
+
+    /// Compile the detail selectors of the definition, no selectors if none are defined.
+    pub(crate) fn detail_selectors(&self) -> Result<DetailSelectors, ErrorKind> {
+        let detail_selectors = match &self.detail_selectors {
+            Some(detail_selectors) => detail_selectors,
+            None => return Ok(DetailSelectors::default()),
+        };
+        Ok(DetailSelectors {
+            start: self.optional_selector_for("start", &detail_selectors.start)?,
+            doors_open: self.optional_selector_for("doors_open", &detail_selectors.doors_open)?,
+            price: self.optional_selector_for("price", &detail_selectors.price)?,
+            ticket_url: self.optional_selector_for("ticket_url", &detail_selectors.ticket_url)?,
+            image: self.optional_selector_for("image", &detail_selectors.image)?,
+            image_attribute: detail_selectors.image_attribute.clone(),
+            long_description: self
+                .optional_selector_for("long_description", &detail_selectors.long_description)?,
+            support_acts: self
+                .optional_selector_for("support_acts", &detail_selectors.support_acts)?,
+            support_acts_prefix: detail_selectors.support_acts_prefix.clone(),
+        })
+    }
+
+    fn optional_selector_for(
+        &self,
+        name: &str,
+        selector: &Option<String>,
+    ) -> Result<Option<scraper::Selector>, ErrorKind> {
+        selector
+            .as_ref()
+            .map(|selector| self.selector_for(name, selector))
+            .transpose()
+    }
+
+    fn selector_for(&self, name: &str, selector: &str) -> Result<scraper::Selector, ErrorKind> {
+        parser::selector_for(selector)
+            .map_err(|err| self.invalid(format!("selector {} is invalid: {}", name, err)))
+    }
+
+    fn invalid(&self, message: String) -> ErrorKind {
+        ErrorKind::InvalidVenueDefinition {
+            venue_id: self.id.clone(),
+            message,
+        }
+    }
+
+    /// Validate the definition, the error points at the offending venue.
+    pub fn validate(&self) -> Result<(), ErrorKind> {
+        if self.id.trim().is_empty() {
+            return Err(self.invalid("id cannot be empty".to_string()));
+        }
+        if self.name.trim().is_empty() {
+            return Err(self.invalid("name cannot be empty".to_string()));
+        }
+        Url::parse(&self.agenda_url)
+            .map_err(|err| self.invalid(format!("agenda_url is invalid: {}", err)))?;
+        self.pagination()?;
+        if let Some(incremental) = &self.incremental {
+            if incremental.known_pages == 0 {
+                return Err(self.invalid("incremental known_pages must be at least 1".to_string()));
+            }
+        }
+        if self.max_concurrent_details == 0 {
+            return Err(self.invalid("max_concurrent_details must be at least 1".to_string()));
+        }
+        match (&self.selectors, &self.json) {
+            (None, None) if !self.structured_data => {
+                return Err(self.invalid(
+                    "selectors are required without structured_data or json".to_string(),
+                ));
+            }
+            (Some(_), Some(_)) => {
+                return Err(self.invalid("json replaces the selectors, define one".to_string()));
+            }
+            _ => {}
+        }
+        self.css_selectors()?;
+        self.json_source()?;
+        self.detail_selectors()?;
+        Ok(())
+    }
+}
+
+/// Parse and validate venue definitions from the text of a venues file (toml).
+pub fn parse_venue_definitions(contents: &str) -> Result<Vec<VenueDefinition>, ErrorKind> {
+    let venues_file: VenuesFile =
+        toml::from_str(contents).map_err(|err| ErrorKind::VenueConfigError {
+            message: err.to_string(),
+        })?;
+
+    let mut venue_ids = HashSet::new();
+    for venue_definition in venues_file.venues.iter() {
+        venue_definition.validate()?;
+        if !venue_ids.insert(venue_definition.id.as_str()) {
+            return Err(venue_definition.invalid("id is defined more than once".to_string()));
+        }
+    }
+    Ok(venues_file.venues)
+}
+
+/// Load and validate the venue definitions from the venues file at path.
+pub fn load_venue_definitions(path: &Path) -> Result<Vec<VenueDefinition>, ErrorKind> {
+    let contents = fs::read_to_string(path).map_err(|err| ErrorKind::VenueConfigError {
+        message: format!("Cannot read {}: {}", path.display(), err),
+    })?;
+    parse_venue_definitions(&contents)
+}
@@ -0,0 +1,95 @@
+use async_trait::async_trait;
+use reqwest::{RequestBuilder, Response, ResponseBuilderExt};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use venue_scraper_api::errors::ErrorKind;
+use venue_scraper_api::http_sender::HttpSender;
+use venue_scraper_api::store::AgendaStore;
+use venue_scraper_api::venue_definition::{parse_venue_definitions, VenueDefinition};
+use venue_scraper_api::VenueScraper;
+
+/// The agenda url of the json venue.
+pub const JSON_VENUE_AGENDA_URL: &str = "https://www.example.com/events";
+
+/// A venue reading its agenda items from the JSON served at its agenda url.
+pub const JSON_VENUE: &str = r#"
+[[venue]]
+id = "json_venue"
+name = "Json venue"
+agenda_url = "https://www.example.com/events"
+
+[venue.json]
+items = "$[*]"
+
+[venue.json.fields]
+url = "$.link"
+title = "$.title"
+description = "$.text"
+start = "$.start"
+price = "$.price"
+"#;
+
+#[allow(dead_code)]
+pub fn json_venue_definition() -> VenueDefinition {
+    parse_venue_definitions(JSON_VENUE).unwrap().remove(0)
+}
+
+/// Sender answering the agenda url of the json venue with the events it holds at the time, or
+/// with a `503 Service Unavailable` while failing. Other urls are not found.
+#[derive(Default)]
+pub struct ListingSender {
+    events: Mutex<serde_json::Value>,
+    failing: AtomicBool,
+}
+
+impl ListingSender {
+    #[allow(dead_code)]
+    pub fn new(events: serde_json::Value) -> ListingSender {
+        ListingSender {
+            events: Mutex::new(events),
+            failing: AtomicBool::new(false),
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn set_events(&self, events: serde_json::Value) {
+        *self.events.lock().unwrap() = events;
+    }
+
+    #[allow(dead_code)]
+    pub fn set_failing(&self, failing: bool) {
+        self.failing.store(failing, Ordering::SeqCst);
+    }
+}
+
+#[async_trait]
+impl HttpSender for ListingSender {
+    async fn send(&self, request: RequestBuilder) -> Result<Response, ErrorKind> {
+        let url = request.build().unwrap().url().clone();
+        let (status, body) = if url.as_str() != JSON_VENUE_AGENDA_URL {
+            (404, String::new())
+        } else if self.failing.load(Ordering::SeqCst) {
+            (503, String::new())
+        } else {
+            (200, self.events.lock().unwrap().to_string())
+        };
+        let response = http::response::Builder::new()
+            .url(url)
+            .status(status)
+            .body(body)
+            .unwrap();
+        Ok(response.into())
+    }
+}
+
+/// Scraper of the json venue, fetching with the sender.
+#[allow(dead_code)]
+pub fn json_venue_scraper(sender: Arc<ListingSender>, store: Arc<dyn AgendaStore>) -> VenueScraper {
+    VenueScraper::from_definition(
+        &json_venue_definition(),
+        sender,
+        reqwest::Client::new(),
+        store,
+    )
+    .unwrap()
+}
@@ -5,18 +5,25 @@ use tokio::time::sleep;
 use tracing::{info, Level};
 
 use mongodb::Database;
+use std::sync::Arc;
 
 use tracing_subscriber::fmt::format::FmtSpan;
 use tracing_subscriber::layer::SubscriberExt;
 use tracing_subscriber::{filter, fmt, prelude::*};
-use venue_scraper_api::agenda::{create_mongo_connection, Agenda};
+use venue_scraper_api::agenda::Agenda;
 use venue_scraper_api::config::Config;
+use venue_scraper_api::store::memory::MemoryAgendaStore;
+use venue_scraper_api::store::mongo::{create_mongo_connection, MongoAgendaStore};
+use venue_scraper_api::store::sqlite::SqliteAgendaStore;
+use venue_scraper_api::store::AgendaStore;
+
+pub mod json_venue;
 
 static LOG_INIT: Once = Once::new();
 
+#[allow(dead_code)]
 pub struct TestFixtures {
-    pub db: Database,
-    pub config: Config,
+    pub store: Arc<dyn AgendaStore>,
 }
 
 fn set_env_var_if_not_set(env_var: &str, default_value: &str) {
@@ -25,6 +32,8 @@ fn set_env_var_if_not_set(env_var: &str, default_value: &str) {
     }
 }
 
+/// Setup logging and a store for the test. The store is in memory, unless TEST_STORE is set to
+/// sqlite, an in memory SQLite database, or mongo, which uses the mongo database on port 5900.
 pub async fn setup() -> TestFixtures {
     LOG_INIT.call_once(|| {
         if env::var_os("RUST_LOG").is_none() {
@@ -43,6 +52,20 @@ pub async fn setup() -> TestFixtures {
             fmt::init();
         }
     });
+    match env::var("TEST_STORE").as_deref() {
+        Ok("mongo") => {}
+        Ok("sqlite") => {
+            return TestFixtures {
+                store: Arc::new(SqliteAgendaStore::open_in_memory().unwrap()),
+            }
+        }
+        _ => {
+            return TestFixtures {
+                store: Arc::new(MemoryAgendaStore::new()),
+            }
+        }
+    }
+
     set_env_var_if_not_set("ENVIRONMENT", "localhost");
     set_env_var_if_not_set("MONGO_USER", "venue_scraper_test");
     set_env_var_if_not_set("MONGO_PASS", "venue_scraper");
@@ -50,13 +73,16 @@ pub async fn setup() -> TestFixtures {
     set_env_var_if_not_set("MONGO_PORT", "5900");
     set_env_var_if_not_set("MONGO_DB", "venue-scraper-test");
     set_env_var_if_not_set("JWT_TOKEN_SECRET", "venue-scraper-test");
+    set_env_var_if_not_set("STORE_TYPE", "mongo");
 
     let config = Config::from_environment();
     let db = create_mongo_connection(&config).await.unwrap();
 
     empty_users_collection(&db).await;
 
-    TestFixtures { config, db }
+    TestFixtures {
+        store: Arc::new(MongoAgendaStore::new(db)),
+    }
 }
 
 static mut EMPTY_COLLECTION_BARRIER: u32 = 1;
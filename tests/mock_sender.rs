@@ -1,14 +1,14 @@
 use async_trait::async_trait;
-use mongodb::Database;
 use reqwest::ResponseBuilderExt;
 use std::path::Path;
-use std::rc::Rc;
+use std::sync::Arc;
 use tokio::fs::File;
 use tokio::io::AsyncReadExt;
 use tracing::trace;
-use url::Url;
 use venue_scraper_api::errors::ErrorKind;
 use venue_scraper_api::http_sender::HttpSender;
+use venue_scraper_api::store::AgendaStore;
+use venue_scraper_api::venue_definition::{load_venue_definitions, VenueDefinition};
 use venue_scraper_api::VenueScraper;
 
 pub struct MockSender {
@@ -62,20 +62,52 @@ impl HttpSender for MockSender {
     }
 }
 
+/// The definition of the venue with venue_id from the venues file.
 #[allow(dead_code)]
-pub fn spot_groningen_with_mock_sender(test_case: &str, db: Database) -> VenueScraper {
-    let mock_sender = Rc::new(MockSender {
-        test_case: String::from(test_case),
-    });
-    let client = reqwest::Client::new();
-    VenueScraper::spot_groningen_with_sender_and_client(mock_sender, client, db).unwrap()
+pub fn venue_definition(venue_id: &str) -> VenueDefinition {
+    load_venue_definitions(Path::new("venues.toml"))
+        .unwrap()
+        .into_iter()
+        .find(|venue_definition| venue_definition.id == venue_id)
+        .unwrap()
 }
 
+/// Create a scraper for the venue definition, using the mock sender.
 #[allow(dead_code)]
-pub fn tivoli_utrecht_with_mock_sender(test_case: &str, db: Database) -> VenueScraper {
-    let mock_sender = Rc::new(MockSender {
+pub fn scraper_with_mock_sender(
+    venue_definition: &VenueDefinition,
+    test_case: &str,
+    store: Arc<dyn AgendaStore>,
+) -> VenueScraper {
+    let mock_sender = Arc::new(MockSender {
         test_case: String::from(test_case),
     });
     let client = reqwest::Client::new();
-    VenueScraper::tivoli_with_sender_and_client(mock_sender, client, db).unwrap()
+    VenueScraper::from_definition(venue_definition, mock_sender, client, store).unwrap()
+}
+
+/// Create a scraper for the venue with venue_id from the venues file, using the mock sender.
+#[allow(dead_code)]
+pub fn venue_with_mock_sender(
+    venue_id: &str,
+    test_case: &str,
+    store: Arc<dyn AgendaStore>,
+) -> VenueScraper {
+    scraper_with_mock_sender(&venue_definition(venue_id), test_case, store)
+}
+
+#[allow(dead_code)]
+pub fn spot_groningen_with_mock_sender(
+    test_case: &str,
+    store: Arc<dyn AgendaStore>,
+) -> VenueScraper {
+    venue_with_mock_sender("spot_groningen", test_case, store)
+}
+
+#[allow(dead_code)]
+pub fn tivoli_utrecht_with_mock_sender(
+    test_case: &str,
+    store: Arc<dyn AgendaStore>,
+) -> VenueScraper {
+    venue_with_mock_sender("tivoli_utrecht", test_case, store)
 }
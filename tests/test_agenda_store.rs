@@ -0,0 +1,260 @@
+use chrono::{TimeZone, Timelike, Utc};
+use std::sync::Arc;
+use venue_scraper_api::agenda::{Agenda, AgendaStatus};
+use venue_scraper_api::errors::ErrorKind;
+use venue_scraper_api::health::SelectorHealth;
+use venue_scraper_api::listing_page::ListingPage;
+use venue_scraper_api::registry::SyncJob;
+use venue_scraper_api::store::memory::MemoryAgendaStore;
+use venue_scraper_api::store::sqlite::SqliteAgendaStore;
+use venue_scraper_api::store::AgendaStore;
+use venue_scraper_api::sync_run::SyncRun;
+
+fn agenda(url: &str, venue_id: &str) -> Agenda {
+    Agenda {
+        url: url.to_string(),
+        title: format!("Title of {}", url),
+        venue_id: venue_id.to_string(),
+        ..Agenda::default()
+    }
+}
+
+/// The behaviour every store should have.
+async fn check_store(store: Arc<dyn AgendaStore>) {
+    assert!(store
+        .get_agenda_by_url("https://a")
+        .await
+        .unwrap()
+        .is_none());
+
+    let result = store
+        .upsert_agenda(&agenda("https://a", "venue_1"))
+        .await
+        .unwrap();
+    assert!(result.inserted);
+    assert!(result.agenda.needs_details);
+
+    let result = store
+        .upsert_agenda(&agenda("https://a", "venue_1"))
+        .await
+        .unwrap();
+    assert!(!result.inserted);
+
+    store
+        .insert_agenda(&agenda("https://b", "venue_2"))
+        .await
+        .unwrap();
+    store
+        .insert_agenda(&agenda("https://c", "venue_1"))
+        .await
+        .unwrap();
+
+    let mut updated_agenda = result.agenda;
+    updated_agenda.needs_details = false;
+    updated_agenda.price = Some("€ 10".to_string());
+    store.update_agenda(&updated_agenda).await.unwrap();
+
+    let stored_agenda = store.get_agenda_by_url("https://a").await.unwrap().unwrap();
+    assert!(!stored_agenda.needs_details);
+    assert_eq!(stored_agenda.price.unwrap(), "€ 10");
+
+    let urls: Vec<String> = store
+        .agenda_items_for_venue("venue_1")
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|agenda| agenda.url)
+        .collect();
+    assert_eq!(urls, vec!["https://a", "https://c"]);
+
+    // Items stored without a listing hash only take over the hash.
+    let mut changed_agenda = agenda("https://a", "venue_1");
+    changed_agenda.listing_hash = Some("hash-1".to_string());
+    let result = store.upsert_agenda(&changed_agenda).await.unwrap();
+    assert!(!result.inserted && !result.updated);
+
+    changed_agenda.title = "Changed title".to_string();
+    changed_agenda.listing_hash = Some("hash-2".to_string());
+    let result = store.upsert_agenda(&changed_agenda).await.unwrap();
+    assert!(result.updated);
+    let stored_agenda = store.get_agenda_by_url("https://a").await.unwrap().unwrap();
+    assert_eq!(stored_agenda.title, "Changed title");
+    assert!(stored_agenda.needs_details);
+    assert_eq!(stored_agenda.price.unwrap(), "€ 10");
+
+    let stable_id = agenda("https://c", "venue_1").stable_id();
+    let stored_agenda = store.get_agenda_by_stable_id(&stable_id).await.unwrap();
+    assert_eq!(stored_agenda.unwrap().url, "https://c");
+    assert!(store
+        .get_agenda_by_stable_id("unknown")
+        .await
+        .unwrap()
+        .is_none());
+
+    let mut removed_agenda = store.get_agenda_by_url("https://c").await.unwrap().unwrap();
+    removed_agenda.status = AgendaStatus::Removed;
+    store.update_agenda(&removed_agenda).await.unwrap();
+    let urls_of = |agenda_items: Vec<Agenda>| -> Vec<String> {
+        agenda_items.into_iter().map(|agenda| agenda.url).collect()
+    };
+    assert_eq!(
+        urls_of(
+            store
+                .agenda_items_for_venue_with_status("venue_1", AgendaStatus::Active)
+                .await
+                .unwrap()
+        ),
+        vec!["https://a"]
+    );
+    assert_eq!(
+        urls_of(
+            store
+                .agenda_items_for_venue_with_status("venue_1", AgendaStatus::Removed)
+                .await
+                .unwrap()
+        ),
+        vec!["https://c"]
+    );
+
+    let result = store.update_agenda(&agenda("https://d", "venue_1")).await;
+    assert!(matches!(result, Err(ErrorKind::AgendaNotFound { url }) if url == "https://d"));
+
+    let mut listing_page = ListingPage {
+        url: "https://listing".to_string(),
+        venue_id: "venue_1".to_string(),
+        definition_hash: "hash-1".to_string(),
+        agenda_urls: vec!["https://a".to_string(), "https://c".to_string()],
+        number_of_unparseable_items: 1,
+        number_of_unparseable_dates: 0,
+        parse_failures: Vec::new(),
+    };
+    store.upsert_listing_page(&listing_page).await.unwrap();
+    listing_page.definition_hash = "hash-2".to_string();
+    store.upsert_listing_page(&listing_page).await.unwrap();
+    let stored_listing_page = store
+        .get_listing_page("https://listing")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(stored_listing_page.definition_hash, "hash-2");
+    assert_eq!(stored_listing_page.agenda_urls, listing_page.agenda_urls);
+    assert!(store
+        .get_listing_page("https://unknown")
+        .await
+        .unwrap()
+        .is_none());
+
+    for (venue_id, hour) in [
+        ("venue_1", 1),
+        ("venue_2", 2),
+        ("venue_1", 3),
+        ("venue_1", 4),
+    ] {
+        let selector_health = SelectorHealth {
+            venue_id: venue_id.to_string(),
+            checked_at: Utc.with_ymd_and_hms(2030, 1, 1, hour, 0, 0).unwrap(),
+            number_of_items: hour,
+            number_of_unparseable_items: 0,
+            number_of_items_without_description: 0,
+            layout_change_suspected: None,
+        };
+        store
+            .insert_selector_health(&selector_health)
+            .await
+            .unwrap();
+    }
+    let items: Vec<u32> = store
+        .selector_health_for_venue("venue_1", 2)
+        .await
+        .unwrap()
+        .iter()
+        .map(|selector_health| selector_health.number_of_items)
+        .collect();
+    assert_eq!(items, vec![4, 3]);
+
+    for (venue_id, hour, successful) in [
+        ("venue_1", 1, true),
+        ("venue_2", 2, true),
+        ("venue_1", 3, true),
+        ("venue_1", 4, false),
+    ] {
+        let started_at = Utc.with_ymd_and_hms(2030, 1, 1, hour, 0, 0).unwrap();
+        let sync_run = SyncRun {
+            venue_id: venue_id.to_string(),
+            job: SyncJob::Listing,
+            started_at,
+            finished_at: started_at + chrono::Duration::seconds(5),
+            duration_ms: 5000,
+            successful,
+            result: None,
+            details_result: None,
+            errors: Vec::new(),
+        };
+        store.insert_sync_run(&sync_run).await.unwrap();
+    }
+    let hours_of = |sync_runs: Vec<SyncRun>| -> Vec<u32> {
+        sync_runs
+            .iter()
+            .map(|sync_run| sync_run.started_at.hour())
+            .collect()
+    };
+    let since = Utc.with_ymd_and_hms(2030, 1, 1, 3, 0, 0).unwrap();
+    assert_eq!(
+        hours_of(store.sync_runs_for_venue("venue_1", since).await.unwrap()),
+        vec![3, 4]
+    );
+    let sync_run = store.last_successful_sync_run("venue_1").await.unwrap();
+    assert_eq!(sync_run.unwrap().started_at.hour(), 3);
+    assert!(store
+        .last_successful_sync_run("venue_3")
+        .await
+        .unwrap()
+        .is_none());
+}
+
+#[tokio::test]
+async fn test_memory_store() {
+    check_store(Arc::new(MemoryAgendaStore::new())).await;
+}
+
+#[tokio::test]
+async fn test_sqlite_store() {
+    check_store(Arc::new(SqliteAgendaStore::open_in_memory().unwrap())).await;
+}
+
+/// Databases of before the stable ids get them when they are opened.
+#[tokio::test]
+async fn test_sqlite_store_adds_stable_ids() {
+    let path = std::env::temp_dir().join(format!("venue-scraper-{}.sqlite", std::process::id()));
+    let agenda = agenda("https://a", "venue_1");
+    {
+        let connection = rusqlite::Connection::open(&path).unwrap();
+        connection
+            .execute_batch(
+                "CREATE TABLE agenda (
+                    url TEXT PRIMARY KEY,
+                    venue_id TEXT NOT NULL,
+                    document TEXT NOT NULL
+                );",
+            )
+            .unwrap();
+        connection
+            .execute(
+                "INSERT INTO agenda (url, venue_id, document) VALUES (?1, ?2, ?3)",
+                rusqlite::params![
+                    agenda.url,
+                    agenda.venue_id,
+                    serde_json::to_string(&agenda).unwrap()
+                ],
+            )
+            .unwrap();
+    }
+
+    let store = SqliteAgendaStore::open(&path).unwrap();
+    let stored_agenda = store
+        .get_agenda_by_stable_id(&agenda.stable_id())
+        .await
+        .unwrap();
+    assert_eq!(stored_agenda.unwrap().url, "https://a");
+    std::fs::remove_file(path).unwrap();
+}
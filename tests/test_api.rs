@@ -0,0 +1,123 @@
+mod common;
+mod mock_sender;
+
+use mock_sender::spot_groningen_with_mock_sender;
+use serde_json::Value;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::Arc;
+use venue_scraper_api::agenda::AgendaStatus;
+use venue_scraper_api::api::router;
+use venue_scraper_api::store::AgendaStore;
+use venue_scraper_api::venue_definition::load_venue_definitions;
+
+/// Sync the Spot program and serve the api on a free port.
+async fn serve_synced_agenda() -> (SocketAddr, Arc<dyn AgendaStore>) {
+    let test_fixtures = common::setup().await;
+    let spot_groningen_syncer =
+        spot_groningen_with_mock_sender("details-test-case", test_fixtures.store.clone());
+    spot_groningen_syncer.sync().await.unwrap();
+    spot_groningen_syncer.sync_details().await.unwrap();
+
+    let venue_definitions = load_venue_definitions(Path::new("venues.toml")).unwrap();
+    let server = axum::Server::bind(&"127.0.0.1:0".parse().unwrap())
+        .serve(router(test_fixtures.store.clone(), venue_definitions).into_make_service());
+    let address = server.local_addr();
+    tokio::spawn(server);
+    (address, test_fixtures.store)
+}
+
+async fn get_json(address: SocketAddr, path: &str) -> (u16, Value) {
+    let response = reqwest::get(format!("http://{}{}", address, path))
+        .await
+        .unwrap();
+    let status = response.status().as_u16();
+    (status, response.json().await.unwrap())
+}
+
+fn titles(page: &Value) -> Vec<&str> {
+    page["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["title"].as_str().unwrap())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_api() {
+    let (address, store) = serve_synced_agenda().await;
+
+    let (status, venues) = get_json(address, "/venues").await;
+    assert_eq!(status, 200);
+    assert_eq!(venues[1]["id"], "spot_groningen");
+    assert_eq!(venues[1]["address"], "Trompsingel 27, 9724 DA Groningen");
+
+    let (status, page) = get_json(address, "/agenda?venue_id=spot_groningen").await;
+    assert_eq!(status, 200);
+    assert_eq!(page["total_items"], 6);
+    assert_eq!(page["items"][0]["starts_at"], "2022-09-04T20:15:00+02:00");
+
+    let (_, page) = get_json(address, "/agenda?page=2&page_size=4").await;
+    assert_eq!(page["total_items"], 6);
+    assert_eq!(page["total_pages"], 2);
+    assert_eq!(page["items"].as_array().unwrap().len(), 2);
+
+    let (_, page) = get_json(address, "/agenda?from=2022-09-06&to=2022-09-07").await;
+    assert_eq!(page["total_items"], 3);
+
+    let (_, page) = get_json(address, "/agenda?q=buffalo").await;
+    assert_eq!(titles(&page), vec!["Keb' Mo'"]);
+
+    let id = page["items"][0]["id"].as_str().unwrap();
+    let (status, keb_mo) = get_json(address, &format!("/agenda/{}", id)).await;
+    assert_eq!(status, 200);
+    assert_eq!(
+        keb_mo["url"],
+        "https://www.spotgroningen.nl/programma/keb-mo/"
+    );
+    assert_eq!(keb_mo["price"], "€ 35,60");
+    assert_eq!(keb_mo["status"], "active");
+
+    let (status, error) = get_json(address, "/agenda/unknown").await;
+    assert_eq!(status, 404);
+    assert!(error["error"].as_str().is_some());
+
+    let (status, _) = get_json(address, "/agenda?venue_id=paradiso").await;
+    assert_eq!(status, 404);
+    let (status, _) = get_json(address, "/agenda?from=tomorrow").await;
+    assert_eq!(status, 400);
+    let (status, _) = get_json(address, "/agenda?page=first").await;
+    assert_eq!(status, 400);
+
+    // Removed items are only listed when asked for.
+    let mut removed = store
+        .get_agenda_by_url("https://www.spotgroningen.nl/programma/keb-mo/")
+        .await
+        .unwrap()
+        .unwrap();
+    removed.status = AgendaStatus::Removed;
+    store.update_agenda(&removed).await.unwrap();
+    let (_, page) = get_json(address, "/agenda?venue_id=spot_groningen").await;
+    assert_eq!(page["total_items"], 5);
+    let (_, page) = get_json(address, "/agenda?status=removed").await;
+    assert_eq!(titles(&page), vec!["Keb' Mo'"]);
+    let (status, _) = get_json(address, "/agenda?status=gone").await;
+    assert_eq!(status, 400);
+
+    let (status, health) = get_json(address, "/health").await;
+    assert_eq!(status, 200);
+    assert_eq!(health[1]["venue_id"], "spot_groningen");
+    assert_eq!(health[1]["layout_change_suspected"], false);
+    assert_eq!(health[1]["selector_health"]["number_of_items"], 6);
+    assert!(health[0]["selector_health"].is_null());
+    let (status, health) = get_json(address, "/venues/spot_groningen/health?limit=5").await;
+    assert_eq!(status, 200);
+    assert_eq!(health.as_array().unwrap().len(), 1);
+    let (status, _) = get_json(address, "/venues/paradiso/health").await;
+    assert_eq!(status, 404);
+
+    let (status, openapi) = get_json(address, "/openapi.json").await;
+    assert_eq!(status, 200);
+    assert!(openapi["paths"]["/agenda/{id}"].is_object());
+}
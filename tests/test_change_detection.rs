@@ -0,0 +1,83 @@
+mod common;
+mod mock_sender;
+
+use common::json_venue::{json_venue_scraper, ListingSender};
+use mock_sender::spot_groningen_with_mock_sender;
+use serde_json::json;
+use std::sync::Arc;
+
+/// Test if changes on the listing page are detected.
+/// 1. Sync the program and the details.
+/// 2. Sync the program again, with a changed description and a sold out item.
+/// 3. Only the two changed items are updated and need their details again.
+#[tokio::test]
+async fn test_sync_detects_changes() {
+    let test_fixtures = common::setup().await;
+
+    // 1. Sync the program and the details.
+    let spot_groningen_syncer =
+        spot_groningen_with_mock_sender("details-test-case", test_fixtures.store.clone());
+    spot_groningen_syncer.sync().await.unwrap();
+    spot_groningen_syncer.sync_details().await.unwrap();
+
+    // Syncing the same program again changes nothing.
+    let syncing_result = spot_groningen_syncer.sync().await.unwrap();
+    assert_eq!(syncing_result.total_items, 6);
+    assert_eq!(syncing_result.total_items_inserted, 0);
+    assert_eq!(syncing_result.total_items_updated, 0);
+
+    // 2. Sync the changed program.
+    let spot_groningen_syncer =
+        spot_groningen_with_mock_sender("changes-test-case", test_fixtures.store.clone());
+    let syncing_result = spot_groningen_syncer.sync().await.unwrap();
+    assert_eq!(syncing_result.total_items, 6);
+    assert_eq!(syncing_result.total_items_inserted, 0);
+    assert_eq!(syncing_result.total_items_updated, 2);
+
+    let swamp_dogg = test_fixtures
+        .store
+        .get_agenda_by_url("https://www.spotgroningen.nl/programma/swamp-dogg/")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        swamp_dogg.description.unwrap(),
+        "Humoristische cultheld zingt funky southern soul en blues"
+    );
+    assert!(swamp_dogg.needs_details);
+
+    // 3. Only the changed items fetch their details again.
+    let syncing_result = spot_groningen_syncer.sync_details().await.unwrap();
+    assert_eq!(syncing_result.total_urls_fetched, 2);
+    assert_eq!(syncing_result.total_items_updated, 2);
+}
+
+/// A changed price on a JSON listing is stored, not only detected.
+#[tokio::test]
+async fn test_sync_stores_changed_listing_price() {
+    let test_fixtures = common::setup().await;
+    let event = |price: &str| {
+        json!([{
+            "link": "https://www.example.com/events/kane/",
+            "title": "Kane",
+            "start": "2030-03-14T20:30:00+01:00",
+            "price": price,
+        }])
+    };
+    let sender = Arc::new(ListingSender::new(event("€ 20")));
+    let syncer = json_venue_scraper(sender.clone(), test_fixtures.store.clone());
+    let syncing_result = syncer.sync().await.unwrap();
+    assert_eq!(syncing_result.total_items_inserted, 1);
+
+    sender.set_events(event("€ 25"));
+    let syncing_result = syncer.sync().await.unwrap();
+    assert_eq!(syncing_result.total_items_updated, 1);
+    let kane = test_fixtures
+        .store
+        .get_agenda_by_url("https://www.example.com/events/kane/")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(kane.price.unwrap(), "€ 25");
+    assert_eq!(kane.title, "Kane");
+}
@@ -0,0 +1,112 @@
+use std::process::{Command, Output};
+
+/// Run the venue scraper binary with an in memory store.
+fn venue_scraper(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_venue_scraper"))
+        .args(args)
+        .env("ENVIRONMENT", "test")
+        .env("STORE_TYPE", "memory")
+        .env("VENUES_CONFIG", "venues.toml")
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn test_list_venues_as_json() {
+    let output = venue_scraper(&["list-venues", "--output", "json"]);
+    assert!(output.status.success());
+    let venue_ids: Vec<String> = String::from_utf8(output.stdout)
+        .unwrap()
+        .lines()
+        .map(|line| {
+            let venue: serde_json::Value = serde_json::from_str(line).unwrap();
+            venue["id"].as_str().unwrap().to_string()
+        })
+        .collect();
+    assert!(venue_ids.contains(&"tivoli_utrecht".to_string()));
+    assert!(venue_ids.contains(&"spot_groningen".to_string()));
+}
+
+#[test]
+fn test_stats_of_a_venue() {
+    let output = venue_scraper(&["stats", "--venue", "spot_groningen", "--output", "json"]);
+    assert!(output.status.success());
+    let stats: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(stats["venue_id"], "spot_groningen");
+    assert_eq!(stats["total_items"], 0);
+}
+
+/// Without full syncs there is no selector health, and no layout change.
+#[test]
+fn test_health_before_the_first_sync() {
+    let output = venue_scraper(&["health", "--venue", "spot_groningen", "--output", "json"]);
+    assert!(output.status.success());
+    let health: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(health["venue_id"], "spot_groningen");
+    assert!(health["health"].is_null());
+}
+
+/// Before the first sync run there is no last successful one.
+#[test]
+fn test_last_successful_run_before_the_first_sync() {
+    let output = venue_scraper(&["runs", "--last-successful", "--venue", "spot_groningen"]);
+    assert!(output.status.success());
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "spot_groningen: no successful sync run yet\n"
+    );
+    let output = venue_scraper(&["runs", "--last-successful", "--trends"]);
+    assert!(!output.status.success());
+}
+
+/// Unknown venues fail before anything is fetched.
+#[test]
+fn test_unknown_venue_fails() {
+    for command in [
+        "sync",
+        "sync-details",
+        "stats",
+        "health",
+        "runs",
+        "check-selectors",
+    ] {
+        let output = venue_scraper(&[command, "--dry-run", "--venue", "no_such_venue"]);
+        assert!(!output.status.success(), "{}", command);
+        assert!(
+            String::from_utf8_lossy(&output.stderr).contains("no_such_venue"),
+            "{}",
+            command
+        );
+    }
+}
+
+#[test]
+fn test_check_selectors_against_a_file() {
+    let output = venue_scraper(&[
+        "check-selectors",
+        "--venue",
+        "tivoli_utrecht",
+        "--file",
+        "agenda.html",
+        "--output",
+        "json",
+    ]);
+    assert!(output.status.success());
+    let check: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(check["number_of_items"], 50);
+    assert_eq!(
+        check["agenda_items"][1]["url"],
+        "https://www.tivolivredenburg.nl/agenda/blood-red-shoes-22-07-2022/"
+    );
+
+    // A file is the page of one venue.
+    let output = venue_scraper(&["check-selectors", "--file", "agenda.html"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn test_missing_venues_file_fails() {
+    let output = venue_scraper(&["list-venues", "--config", "no-such-venues.toml"]);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("no-such-venues.toml"));
+}
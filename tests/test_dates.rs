@@ -0,0 +1,82 @@
+use chrono::{DateTime, TimeZone, Utc};
+use chrono_tz::Europe::Amsterdam;
+use chrono_tz::Tz;
+use venue_scraper_api::dates::parse_event_dates;
+use venue_scraper_api::errors::ErrorKind;
+
+fn reference() -> DateTime<Utc> {
+    Utc.with_ymd_and_hms(2024, 10, 1, 12, 0, 0).unwrap()
+}
+
+fn amsterdam(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Tz> {
+    Amsterdam
+        .with_ymd_and_hms(year, month, day, hour, minute, 0)
+        .unwrap()
+}
+
+#[test]
+fn test_parse_dates_with_times() {
+    let event_dates = parse_event_dates("za 12 okt 2024", reference()).unwrap();
+    assert_eq!(event_dates.starts_at, amsterdam(2024, 10, 12, 0, 0));
+    assert!(event_dates.all_day);
+
+    let event_dates = parse_event_dates("vr 2 dec 2022", reference()).unwrap();
+    assert_eq!(event_dates.starts_at, amsterdam(2022, 12, 2, 0, 0));
+
+    let event_dates = parse_event_dates("vrijdag 3 november · 20:00", reference()).unwrap();
+    assert_eq!(event_dates.starts_at, amsterdam(2024, 11, 3, 20, 0));
+    assert!(!event_dates.all_day);
+
+    let event_dates =
+        parse_event_dates("do 10 okt 2024 / Deuren 19:30 / Aanvang 20:30", reference()).unwrap();
+    assert_eq!(event_dates.starts_at, amsterdam(2024, 10, 10, 20, 30));
+    assert_eq!(
+        event_dates.doors_open_at,
+        Some(amsterdam(2024, 10, 10, 19, 30))
+    );
+
+    let event_dates = parse_event_dates(
+        "Zondag 4 september 2022 / SPOT/De Oosterpoort, Grote zaal / Trompsingel 27 / \
+         17:00 uur: Restaurant The Mix open / 20:15 uur: Start voorstelling / \
+         22:35 uur: Einde voorstelling / (Tijden onder voorbehoud)",
+        reference(),
+    )
+    .unwrap();
+    assert_eq!(event_dates.starts_at, amsterdam(2022, 9, 4, 20, 15));
+    assert_eq!(event_dates.ends_at, Some(amsterdam(2022, 9, 4, 22, 35)));
+    assert_eq!(event_dates.doors_open_at, None);
+
+    let event_dates = parse_event_dates("2022-08-30T20:00:00+02:00", reference()).unwrap();
+    assert_eq!(event_dates.starts_at, amsterdam(2022, 8, 30, 20, 0));
+
+    let event_dates = parse_event_dates("12-10-2024 23:00 - 02:00", reference()).unwrap();
+    assert_eq!(event_dates.ends_at, Some(amsterdam(2024, 10, 13, 2, 0)));
+}
+
+/// Dates without a year are the next occurrence.
+#[test]
+fn test_parse_dates_infers_year() {
+    let event_dates = parse_event_dates("ma 14 okt", reference()).unwrap();
+    assert_eq!(event_dates.starts_at, amsterdam(2024, 10, 14, 0, 0));
+
+    let event_dates = parse_event_dates("vr 12 jan 21:00", reference()).unwrap();
+    assert_eq!(event_dates.starts_at, amsterdam(2025, 1, 12, 21, 0));
+}
+
+#[test]
+fn test_parse_multi_day_festival() {
+    let event_dates = parse_event_dates("vr 27 t/m zo 29 juni 2025", reference()).unwrap();
+    assert_eq!(event_dates.starts_at, amsterdam(2025, 6, 27, 0, 0));
+    assert_eq!(event_dates.ends_at, Some(amsterdam(2025, 6, 29, 0, 0)));
+    assert!(event_dates.all_day);
+
+    let event_dates = parse_event_dates("28 december - 2 januari 2025", reference()).unwrap();
+    assert_eq!(event_dates.starts_at, amsterdam(2024, 12, 28, 0, 0));
+    assert_eq!(event_dates.ends_at, Some(amsterdam(2025, 1, 2, 0, 0)));
+}
+
+#[test]
+fn test_unparseable_dates() {
+    let result = parse_event_dates("Binnenkort meer informatie", reference());
+    assert!(matches!(result, Err(ErrorKind::UnparseableDate { .. })));
+}
@@ -0,0 +1,165 @@
+mod common;
+
+use common::json_venue::{json_venue_scraper, ListingSender};
+use serde_json::json;
+use std::sync::Arc;
+use venue_scraper_api::errors::ErrorKind;
+use venue_scraper_api::store::dry_run::{AgendaChange, DryRunAgendaStore};
+use venue_scraper_api::store::memory::MemoryAgendaStore;
+use venue_scraper_api::store::AgendaStore;
+use venue_scraper_api::VenueScraper;
+
+fn venue_scraper(events: serde_json::Value, store: Arc<dyn AgendaStore>) -> VenueScraper {
+    json_venue_scraper(Arc::new(ListingSender::new(events)), store)
+}
+
+fn first_listing() -> serde_json::Value {
+    json!([
+        {"link": "https://www.example.com/events/kane/", "title": "Kane", "start": "2030-03-14T20:30:00+01:00"},
+        {"link": "https://www.example.com/events/lizzo/", "title": "Lizzo", "start": "2030-03-16T20:00:00+01:00"},
+    ])
+}
+
+/// Kane is renamed, Lizzo is gone but within the grace period of removals, one event is new, one
+/// has no url and one an unparseable start.
+fn second_listing() -> serde_json::Value {
+    json!([
+        {"link": "https://www.example.com/events/kane/", "title": "Kane & Abel", "start": "2030-03-14T20:30:00+01:00"},
+        {"link": "https://www.example.com/events/new/", "title": "New", "start": "2030-04-01T21:00:00+02:00"},
+        {"title": "Without a link"},
+        {"link": "https://www.example.com/events/later/", "title": "Later", "start": "sometime soon"},
+    ])
+}
+
+/// A dry run gives the same results as a real run, leaves the store alone and reports the changes
+/// it would have written and the items that failed to parse.
+#[tokio::test]
+async fn test_dry_run_reports_without_writing() {
+    let test_fixtures = common::setup().await;
+    let store = test_fixtures.store.clone();
+    let real_store: Arc<dyn AgendaStore> = Arc::new(MemoryAgendaStore::new());
+    for store in [&store, &real_store] {
+        venue_scraper(first_listing(), store.clone())
+            .sync()
+            .await
+            .unwrap();
+    }
+
+    let mut real_result = venue_scraper(second_listing(), real_store.clone())
+        .sync()
+        .await
+        .unwrap();
+    let dry_run_store = Arc::new(DryRunAgendaStore::new(store.clone()));
+    let dry_run_result = venue_scraper(second_listing(), dry_run_store.clone())
+        .sync()
+        .await
+        .unwrap();
+
+    // Only the start of the syncs differs.
+    let dry_run_started_at = dry_run_result.selector_health.as_ref().unwrap().checked_at;
+    real_result.selector_health.as_mut().unwrap().checked_at = dry_run_started_at;
+    assert_eq!(
+        serde_json::to_value(&dry_run_result).unwrap(),
+        serde_json::to_value(&real_result).unwrap()
+    );
+    assert_eq!(dry_run_result.total_items_inserted, 2);
+    assert_eq!(dry_run_result.total_items_updated, 1);
+    assert_eq!(dry_run_result.total_items_removed, 0);
+
+    // The store is as the first listing left it, the dry run store reads its own writes.
+    let kane_url = "https://www.example.com/events/kane/";
+    let kane = store.get_agenda_by_url(kane_url).await.unwrap().unwrap();
+    assert_eq!(kane.title, "Kane");
+    assert!(store
+        .get_agenda_by_url("https://www.example.com/events/new/")
+        .await
+        .unwrap()
+        .is_none());
+    assert_eq!(
+        store
+            .selector_health_for_venue("json_venue", 10)
+            .await
+            .unwrap()
+            .len(),
+        1
+    );
+    let kane = dry_run_store
+        .get_agenda_by_url(kane_url)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(kane.title, "Kane & Abel");
+    assert_eq!(
+        dry_run_store
+            .agenda_items_for_venue("json_venue")
+            .await
+            .unwrap()
+            .len(),
+        4
+    );
+
+    let changes = dry_run_store.changes().await.unwrap();
+    let mut new_urls = Vec::new();
+    let mut changed_fields = Vec::new();
+    for change in &changes {
+        match change {
+            AgendaChange::New { agenda } => new_urls.push(agenda.url.as_str()),
+            AgendaChange::Changed { url, fields } => {
+                for field in fields {
+                    changed_fields.push((
+                        url.as_str(),
+                        field.field.as_str(),
+                        field.before.as_deref(),
+                        field.after.as_deref(),
+                    ));
+                }
+            }
+        }
+    }
+    new_urls.sort();
+    assert_eq!(
+        new_urls,
+        vec![
+            "https://www.example.com/events/later/",
+            "https://www.example.com/events/new/"
+        ]
+    );
+    assert_eq!(
+        changed_fields,
+        vec![(kane_url, "title", Some("Kane"), Some("Kane & Abel"))]
+    );
+    assert!(changes[0].to_string().starts_with("~ "));
+
+    let reasons: Vec<&str> = dry_run_result
+        .parse_failures
+        .iter()
+        .map(|parse_failure| parse_failure.reason.as_str())
+        .collect();
+    assert_eq!(reasons.len(), 2);
+    assert!(reasons
+        .iter()
+        .any(|reason| reason.starts_with("JsonPathError")));
+    assert!(reasons
+        .iter()
+        .any(|reason| reason.starts_with("UnparseableDate")));
+}
+
+#[tokio::test]
+async fn test_update_of_unknown_item_fails() {
+    let dry_run_store = DryRunAgendaStore::new(Arc::new(MemoryAgendaStore::new()));
+    let agenda = venue_scraper(first_listing(), Arc::new(MemoryAgendaStore::new()))
+        .check_page(
+            "https://www.example.com/events",
+            &first_listing().to_string(),
+        )
+        .unwrap()
+        .agenda_items
+        .remove(0);
+    assert!(matches!(
+        dry_run_store.update_agenda(&agenda).await,
+        Err(ErrorKind::AgendaNotFound { .. })
+    ));
+    dry_run_store.insert_agenda(&agenda).await.unwrap();
+    dry_run_store.update_agenda(&agenda).await.unwrap();
+    assert_eq!(dry_run_store.changes().await.unwrap().len(), 1);
+}
@@ -0,0 +1,111 @@
+mod common;
+mod mock_sender;
+
+use chrono::DateTime;
+use mock_sender::spot_groningen_with_mock_sender;
+use std::fs;
+use std::path::Path;
+use venue_scraper_api::agenda::{Agenda, AgendaStatus, Venue};
+use venue_scraper_api::export::{event_uid, export_icalendar, icalendar_feed};
+use venue_scraper_api::venue_definition::load_venue_definitions;
+
+/// Export the synced Spot agenda twice, the feeds should be the same.
+#[tokio::test]
+async fn test_export_icalendar() {
+    let test_fixtures = common::setup().await;
+    let spot_groningen_syncer =
+        spot_groningen_with_mock_sender("details-test-case", test_fixtures.store.clone());
+    spot_groningen_syncer.sync().await.unwrap();
+    spot_groningen_syncer.sync_details().await.unwrap();
+
+    let venue_definitions = load_venue_definitions(Path::new("venues.toml")).unwrap();
+    let output_dir =
+        std::env::temp_dir().join(format!("venue-scraper-export-{}", std::process::id()));
+    let paths = export_icalendar(
+        test_fixtures.store.as_ref(),
+        &venue_definitions,
+        &output_dir,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        paths,
+        vec![
+            output_dir.join("tivoli_utrecht.ics"),
+            output_dir.join("spot_groningen.ics"),
+            output_dir.join("all.ics")
+        ]
+    );
+
+    let spot_feed = fs::read_to_string(output_dir.join("spot_groningen.ics")).unwrap();
+    assert!(spot_feed.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+    assert!(spot_feed.ends_with("END:VCALENDAR\r\n"));
+    assert_eq!(spot_feed.matches("BEGIN:VEVENT").count(), 6);
+    assert!(spot_feed
+        .split("\r\n")
+        .all(|line| line.len() <= 75 && !line.contains('\n')));
+
+    let keb_mo = Agenda {
+        url: "https://www.spotgroningen.nl/programma/keb-mo/".to_string(),
+        ..Agenda::default()
+    };
+    assert_eq!(
+        event_uid(&keb_mo),
+        "31e5b73b46a082016c62d183c84d4968@venue-scraper"
+    );
+    assert!(spot_feed.contains(&format!("UID:{}\r\n", event_uid(&keb_mo))));
+    assert!(spot_feed.contains("DTSTART:20220907T180000Z\r\n"));
+    assert!(
+        spot_feed.contains("LOCATION:Spot Groningen\\, Trompsingel 27\\, 9724 DA Groningen\r\n")
+    );
+
+    let tivoli_feed = fs::read_to_string(output_dir.join("tivoli_utrecht.ics")).unwrap();
+    assert_eq!(tivoli_feed.matches("BEGIN:VEVENT").count(), 0);
+    let combined_feed = fs::read_to_string(output_dir.join("all.ics")).unwrap();
+    assert_eq!(combined_feed.matches("BEGIN:VEVENT").count(), 6);
+
+    // The export is reproducible.
+    export_icalendar(
+        test_fixtures.store.as_ref(),
+        &venue_definitions,
+        &output_dir,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        fs::read_to_string(output_dir.join("spot_groningen.ics")).unwrap(),
+        spot_feed
+    );
+    fs::remove_dir_all(output_dir).unwrap();
+}
+
+#[test]
+fn test_icalendar_all_day_and_cancelled_events() {
+    let venue = Venue {
+        venue_id: "venue".to_string(),
+        name: "Venue".to_string(),
+        address: None,
+    };
+    let festival = Agenda {
+        url: "https://venue.nl/festival".to_string(),
+        title: "Festival; day one, two".to_string(),
+        description: Some("Line one\nline two".to_string()),
+        starts_at: Some(DateTime::parse_from_rfc3339("2024-06-27T00:00:00+02:00").unwrap()),
+        ends_at: Some(DateTime::parse_from_rfc3339("2024-06-29T00:00:00+02:00").unwrap()),
+        all_day: true,
+        status: AgendaStatus::Removed,
+        ..Agenda::default()
+    };
+    let without_date = Agenda {
+        url: "https://venue.nl/without-date".to_string(),
+        ..Agenda::default()
+    };
+
+    let feed = icalendar_feed("Venue", &[(venue.clone(), festival), (venue, without_date)]);
+    assert_eq!(feed.matches("BEGIN:VEVENT").count(), 1);
+    assert!(feed.contains("DTSTART;VALUE=DATE:20240627\r\n"));
+    assert!(feed.contains("DTEND;VALUE=DATE:20240630\r\n"));
+    assert!(feed.contains("SUMMARY:Festival\\; day one\\, two\r\n"));
+    assert!(feed.contains("DESCRIPTION:Line one\\nline two\r\n"));
+    assert!(feed.contains("STATUS:CANCELLED\r\n"));
+}
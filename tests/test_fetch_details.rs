@@ -1,7 +1,42 @@
 mod common;
 mod mock_sender;
 
-use mock_sender::spot_groningen_with_mock_sender;
+use async_trait::async_trait;
+use mock_sender::{spot_groningen_with_mock_sender, venue_definition, MockSender};
+use reqwest::{RequestBuilder, Response};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use venue_scraper_api::errors::ErrorKind;
+use venue_scraper_api::http_sender::HttpSender;
+use venue_scraper_api::VenueScraper;
+
+/// Mock sender counting the requests in flight, failing the requests for failing_path.
+struct SlowSender {
+    mock_sender: MockSender,
+    failing_path: String,
+    in_flight: AtomicUsize,
+    max_in_flight: AtomicUsize,
+}
+
+#[async_trait]
+impl HttpSender for SlowSender {
+    async fn send(&self, request: RequestBuilder) -> Result<Response, ErrorKind> {
+        let url = request.try_clone().unwrap().build().unwrap().url().clone();
+        if url.path() == self.failing_path {
+            return Err(ErrorKind::ConnectionError {
+                url: url.to_string(),
+                message: "connection reset".to_string(),
+            });
+        }
+        let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let response = self.mock_sender.send(request).await;
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+        response
+    }
+}
 
 /// Test if only new items in need of details are fetched.
 /// 1. Make a regular sync.
@@ -14,7 +49,7 @@ async fn test_sync_with_details() {
 
     // 1. Fetch the program. This should give 6 agenda items.
     let spot_groningen_syncer =
-        spot_groningen_with_mock_sender("details-test-case", test_fixtures.db.clone());
+        spot_groningen_with_mock_sender("details-test-case", test_fixtures.store.clone());
     let result = spot_groningen_syncer.sync().await;
 
     assert!(result.is_ok());
@@ -29,11 +64,42 @@ async fn test_sync_with_details() {
     let syncing_result = details_result.unwrap();
     assert_eq!(syncing_result.total_urls_fetched, 6);
     assert_eq!(syncing_result.total_items_updated, 6);
+    assert_eq!(syncing_result.total_unparseable_dates, 0);
     assert_eq!(syncing_result.total_items_inserted, 0);
 
+    let keb_mo = test_fixtures
+        .store
+        .get_agenda_by_url("https://www.spotgroningen.nl/programma/keb-mo/")
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(!keb_mo.needs_details);
+    assert_eq!(
+        keb_mo.starts_at.unwrap().to_rfc3339(),
+        "2022-09-07T20:00:00+02:00"
+    );
+    assert!(keb_mo
+        .start_text
+        .unwrap()
+        .starts_with("Woensdag 7 september 2022"));
+    assert_eq!(keb_mo.price.unwrap(), "€ 35,60");
+    assert_eq!(
+        keb_mo.ticket_url.unwrap(),
+        "https://tickets.spotgroningen.nl/mtTicket/performance?id=104859&lang=nl"
+    );
+    assert_eq!(
+        keb_mo.image_url.unwrap(),
+        "https://www.spotgroningen.nl/wp-content/uploads/2020/04/image-768x468.png"
+    );
+    assert!(keb_mo
+        .long_description
+        .unwrap()
+        .starts_with("De levende blueslegende"));
+    assert_eq!(keb_mo.support_acts, vec!["Buffalo Nichols"]);
+
     // 3. The next run. Fetch the program.
     let spot_groningen_syncer =
-        spot_groningen_with_mock_sender("details-test-case-run-2", test_fixtures.db.clone());
+        spot_groningen_with_mock_sender("details-test-case-run-2", test_fixtures.store.clone());
     let result = spot_groningen_syncer.sync().await;
     assert!(result.is_ok());
     let syncing_result = result.unwrap();
@@ -48,6 +114,46 @@ async fn test_sync_with_details() {
     assert_eq!(syncing_result.total_urls_fetched, 4);
     assert_eq!(syncing_result.total_items_updated, 4);
     assert_eq!(syncing_result.total_items_inserted, 0);
+}
+
+/// Details pages are fetched at the same time, up to the limit of the venue, and a failing page
+/// does not stop the others.
+#[tokio::test]
+async fn test_sync_details_concurrently() {
+    let test_fixtures = common::setup().await;
+    let http_sender = Arc::new(SlowSender {
+        mock_sender: MockSender {
+            test_case: "details-test-case".to_string(),
+        },
+        failing_path: "/programma/keb-mo/".to_string(),
+        in_flight: AtomicUsize::new(0),
+        max_in_flight: AtomicUsize::new(0),
+    });
+    let mut spot_groningen = venue_definition("spot_groningen");
+    spot_groningen.max_concurrent_details = 3;
+    let spot_groningen_syncer = VenueScraper::from_definition(
+        &spot_groningen,
+        http_sender.clone(),
+        reqwest::Client::new(),
+        test_fixtures.store.clone(),
+    )
+    .unwrap();
+
+    let syncing_result = spot_groningen_syncer.sync().await.unwrap();
+    assert_eq!(syncing_result.total_items_inserted, 6);
+
+    http_sender.max_in_flight.store(0, Ordering::SeqCst);
+    let syncing_result = spot_groningen_syncer.sync_details().await.unwrap();
+    assert_eq!(syncing_result.total_urls_fetched, 6);
+    assert_eq!(syncing_result.total_urls_unfetchable, 1);
+    assert_eq!(syncing_result.total_items_updated, 5);
+    assert_eq!(http_sender.max_in_flight.load(Ordering::SeqCst), 3);
 
-    ()
+    let keb_mo = test_fixtures
+        .store
+        .get_agenda_by_url("https://www.spotgroningen.nl/programma/keb-mo/")
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(keb_mo.needs_details);
 }
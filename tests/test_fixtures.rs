@@ -0,0 +1,177 @@
+mod common;
+mod mock_sender;
+
+use async_trait::async_trait;
+use mock_sender::{venue_definition, MockSender};
+use reqwest::header::{HeaderValue, CONTENT_TYPE};
+use reqwest::{RequestBuilder, Response, ResponseBuilderExt, StatusCode};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use venue_scraper_api::errors::ErrorKind;
+use venue_scraper_api::fixtures::{
+    fixture_file_for, Manifest, RecordingHttpSender, ReplayHttpSender, MANIFEST_FILE,
+};
+use venue_scraper_api::http_sender::HttpSender;
+use venue_scraper_api::store::AgendaStore;
+use venue_scraper_api::VenueScraper;
+
+/// Mock sender with a content type on every page, and a gone page.
+struct RecordableSender {
+    mock_sender: MockSender,
+}
+
+#[async_trait]
+impl HttpSender for RecordableSender {
+    async fn send(&self, request: RequestBuilder) -> Result<Response, ErrorKind> {
+        let built_request = request.try_clone().unwrap().build().unwrap();
+        if built_request.url().path() == "/gone/" {
+            let response = http::response::Builder::new()
+                .url(built_request.url().clone())
+                .status(410)
+                .header("x-reason", "sold out")
+                .body("gone")
+                .unwrap();
+            return Ok(response.into());
+        }
+        let mut response = self.mock_sender.send(request).await?;
+        response.headers_mut().insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("text/html; charset=utf-8"),
+        );
+        Ok(response)
+    }
+}
+
+fn fixtures_dir(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("venue-scraper-{}-{}", name, std::process::id()))
+}
+
+fn spot_groningen(http_sender: Arc<dyn HttpSender>, store: Arc<dyn AgendaStore>) -> VenueScraper {
+    VenueScraper::from_definition(
+        &venue_definition("spot_groningen"),
+        http_sender,
+        reqwest::Client::new(),
+        store,
+    )
+    .unwrap()
+}
+
+fn recording_sender(fixtures_dir: &Path) -> Arc<RecordingHttpSender<RecordableSender>> {
+    Arc::new(RecordingHttpSender::new(
+        RecordableSender {
+            mock_sender: MockSender {
+                test_case: "details-test-case".to_string(),
+            },
+        },
+        fixtures_dir.to_path_buf(),
+        "recorded-test-case",
+    ))
+}
+
+/// Record a sync with details, replaying the recording gives the same results.
+#[tokio::test]
+async fn test_record_and_replay_sync() {
+    let fixtures_dir = fixtures_dir("record-sync");
+
+    let test_fixtures = common::setup().await;
+    let syncer = spot_groningen(recording_sender(&fixtures_dir), test_fixtures.store.clone());
+    let recorded_sync = syncer.sync().await.unwrap();
+    let recorded_details = syncer.sync_details().await.unwrap();
+    assert_eq!(recorded_sync.total_items_inserted, 6);
+    assert_eq!(recorded_details.total_items_updated, 6);
+
+    let test_case_dir = fixtures_dir
+        .join("www.spotgroningen.nl")
+        .join("recorded-test-case");
+    assert!(test_case_dir.join("programma/index").is_file());
+    assert!(test_case_dir.join("programma/keb-mo/index").is_file());
+    let manifest: Manifest =
+        serde_json::from_slice(&std::fs::read(test_case_dir.join(MANIFEST_FILE)).unwrap()).unwrap();
+    assert_eq!(manifest.entries.len(), 7);
+    let entry = manifest
+        .entries
+        .iter()
+        .find(|entry| entry.path == "/programma/")
+        .unwrap();
+    assert_eq!(entry.url, "https://www.spotgroningen.nl/programma/");
+    assert_eq!(entry.status, 200);
+    assert_eq!(entry.headers["content-type"], "text/html; charset=utf-8");
+
+    let test_fixtures = common::setup().await;
+    let replay_sender = Arc::new(ReplayHttpSender::new(
+        fixtures_dir.clone(),
+        "recorded-test-case",
+    ));
+    let syncer = spot_groningen(replay_sender, test_fixtures.store.clone());
+    let replayed_sync = syncer.sync().await.unwrap();
+    let replayed_details = syncer.sync_details().await.unwrap();
+    assert_eq!(
+        replayed_sync.total_items_inserted,
+        recorded_sync.total_items_inserted
+    );
+    assert_eq!(
+        replayed_details.total_items_updated,
+        recorded_details.total_items_updated
+    );
+    assert_eq!(replayed_details.total_urls_unfetchable, 0);
+
+    let _ = std::fs::remove_dir_all(&fixtures_dir);
+}
+
+/// The replay sender answers with the recorded status and headers, urls with a query have their
+/// own recording.
+#[tokio::test]
+async fn test_replay_status_headers_and_query() {
+    let fixtures_dir = fixtures_dir("record-query");
+    let recording_sender = recording_sender(&fixtures_dir);
+    let client = reqwest::Client::new();
+    for url in [
+        "https://www.spotgroningen.nl/programma/",
+        "https://www.spotgroningen.nl/programma/?page=2",
+        "https://www.spotgroningen.nl/gone/",
+    ] {
+        recording_sender.send(client.get(url)).await.unwrap();
+    }
+
+    let replay_sender = ReplayHttpSender::new(fixtures_dir.clone(), "recorded-test-case");
+    let response = replay_sender
+        .send(client.get("https://www.spotgroningen.nl/gone/"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::GONE);
+    assert_eq!(response.headers()["x-reason"], "sold out");
+    assert_eq!(response.text().await.unwrap(), "gone");
+
+    let response = replay_sender
+        .send(client.get("https://www.spotgroningen.nl/programma/?page=2"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[CONTENT_TYPE], "text/html; charset=utf-8");
+    let query_url = reqwest::Url::parse("https://www.spotgroningen.nl/programma/?page=2").unwrap();
+    assert_ne!(fixture_file_for(&query_url), "programma/index");
+
+    // Urls that were not recorded are not found.
+    let response = replay_sender
+        .send(client.get("https://www.spotgroningen.nl/programma/?page=3"))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let _ = std::fs::remove_dir_all(&fixtures_dir);
+}
+
+/// Test cases made by hand, without a manifest, are replayed by the path of the url.
+#[tokio::test]
+async fn test_replay_without_manifest() {
+    let test_fixtures = common::setup().await;
+    let replay_sender = Arc::new(ReplayHttpSender::new(
+        PathBuf::from("tests/files"),
+        "details-test-case",
+    ));
+    let syncer = spot_groningen(replay_sender, test_fixtures.store.clone());
+    let syncing_result = syncer.sync().await.unwrap();
+    assert_eq!(syncing_result.total_items_inserted, 6);
+    let syncing_result = syncer.sync_details().await.unwrap();
+    assert_eq!(syncing_result.total_items_updated, 6);
+}
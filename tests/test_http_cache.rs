@@ -0,0 +1,210 @@
+mod common;
+mod mock_sender;
+
+use async_trait::async_trait;
+use mock_sender::{venue_definition, MockSender};
+use reqwest::header::{HeaderValue, ETAG, IF_NONE_MATCH};
+use reqwest::{RequestBuilder, Response};
+use std::path::Path;
+use std::sync::Arc;
+use venue_scraper_api::agenda::AgendaStatus;
+use venue_scraper_api::cache::CachingHttpSender;
+use venue_scraper_api::errors::ErrorKind;
+use venue_scraper_api::http_sender::HttpSender;
+use venue_scraper_api::store::dry_run::DryRunAgendaStore;
+use venue_scraper_api::store::AgendaStore;
+use venue_scraper_api::venue_definition::VenueDefinition;
+use venue_scraper_api::VenueScraper;
+
+/// Mock sender with an etag for every page, answering `304 Not Modified` when it matches.
+struct ConditionalSender {
+    mock_sender: MockSender,
+}
+
+#[async_trait]
+impl HttpSender for ConditionalSender {
+    async fn send(&self, request: RequestBuilder) -> Result<Response, ErrorKind> {
+        let etag = format!("\"{}\"", self.mock_sender.test_case);
+        let built_request = request.try_clone().unwrap().build().unwrap();
+        if built_request.headers().get(IF_NONE_MATCH)
+            == Some(&HeaderValue::from_str(&etag).unwrap())
+        {
+            let response = http::response::Builder::new().status(304).body("").unwrap();
+            return Ok(response.into());
+        }
+
+        let mut response = self.mock_sender.send(request).await?;
+        if response.status().is_success() {
+            response
+                .headers_mut()
+                .insert(ETAG, HeaderValue::from_str(&etag).unwrap());
+        }
+        Ok(response)
+    }
+}
+
+fn caching_sender(test_case: &str, cache_dir: &Path) -> CachingHttpSender<ConditionalSender> {
+    CachingHttpSender::new(
+        ConditionalSender {
+            mock_sender: MockSender {
+                test_case: test_case.to_string(),
+            },
+        },
+        cache_dir.to_path_buf(),
+    )
+}
+
+fn scraper_with_cache(
+    venue_definition: &VenueDefinition,
+    http_sender: Arc<dyn HttpSender>,
+    store: Arc<dyn AgendaStore>,
+) -> VenueScraper {
+    VenueScraper::from_definition(venue_definition, http_sender, reqwest::Client::new(), store)
+        .unwrap()
+}
+
+fn spot_groningen_with_cache(cache_dir: &Path, store: Arc<dyn AgendaStore>) -> VenueScraper {
+    scraper_with_cache(
+        &venue_definition("spot_groningen"),
+        Arc::new(caching_sender("details-test-case", cache_dir)),
+        store,
+    )
+}
+
+/// Sync twice, the second sync gets the unchanged program from the cache.
+#[tokio::test]
+async fn test_sync_with_http_cache() {
+    let test_fixtures = common::setup().await;
+    let cache_dir =
+        std::env::temp_dir().join(format!("venue-scraper-cache-{}", std::process::id()));
+
+    let syncer = spot_groningen_with_cache(&cache_dir, test_fixtures.store.clone());
+    let syncing_result = syncer.sync().await.unwrap();
+    assert_eq!(syncing_result.total_cache_hits, 0);
+    assert_eq!(syncing_result.total_items_inserted, 6);
+    let syncing_result = syncer.sync_details().await.unwrap();
+    assert_eq!(syncing_result.total_cache_hits, 0);
+    assert_eq!(syncing_result.total_items_updated, 6);
+
+    // The unchanged page is not parsed again, the stored items are still seen.
+    let syncing_result = syncer.sync().await.unwrap();
+    assert_eq!(syncing_result.total_urls_fetched, 1);
+    assert_eq!(syncing_result.total_cache_hits, 1);
+    assert!(syncing_result.total_bytes_from_cache > 10_000);
+    assert_eq!(syncing_result.total_items, 6);
+    assert_eq!(syncing_result.total_items_inserted, 0);
+    assert_eq!(syncing_result.total_items_updated, 0);
+    assert_eq!(syncing_result.total_items_removed, 0);
+
+    // A store without the items parses the cached page.
+    let test_fixtures = common::setup().await;
+    let syncer = spot_groningen_with_cache(&cache_dir, test_fixtures.store.clone());
+    let syncing_result = syncer.sync().await.unwrap();
+    assert_eq!(syncing_result.total_cache_hits, 1);
+    assert_eq!(syncing_result.total_items_inserted, 6);
+    let syncing_result = syncer.sync_details().await.unwrap();
+    assert_eq!(syncing_result.total_cache_hits, 6);
+    assert_eq!(syncing_result.total_items_updated, 6);
+    let keb_mo = test_fixtures
+        .store
+        .get_agenda_by_url("https://www.spotgroningen.nl/programma/keb-mo/")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(keb_mo.price.unwrap(), "€ 35,60");
+
+    std::fs::remove_dir_all(cache_dir).unwrap();
+}
+
+/// An unchanged page is parsed again after the venue definition changed, so fixed selectors take
+/// effect. Until then the unparseable agenda items of the page are still counted.
+#[tokio::test]
+async fn test_changed_definition_parses_cached_page() {
+    let test_fixtures = common::setup().await;
+    let store = test_fixtures.store.clone();
+    let cache_dir = std::env::temp_dir().join(format!(
+        "venue-scraper-cache-definition-{}",
+        std::process::id()
+    ));
+    let mut spot_groningen = venue_definition("spot_groningen");
+    spot_groningen.selectors.as_mut().unwrap().title = "h1.program__title".to_string();
+
+    let syncer = scraper_with_cache(
+        &spot_groningen,
+        Arc::new(caching_sender("details-test-case", &cache_dir)),
+        store.clone(),
+    );
+    let syncing_result = syncer.sync().await.unwrap();
+    assert_eq!(syncing_result.total_cache_hits, 0);
+    assert_eq!(syncing_result.total_items_inserted, 0);
+    assert_eq!(syncing_result.total_unparseable_items, 6);
+
+    let syncing_result = syncer.sync().await.unwrap();
+    assert_eq!(syncing_result.total_cache_hits, 1);
+    assert_eq!(syncing_result.total_items, 6);
+    assert_eq!(syncing_result.total_unparseable_items, 6);
+    assert_eq!(syncing_result.parse_failures.len(), 6);
+    assert_eq!(syncing_result.total_items_inserted, 0);
+
+    let syncer = spot_groningen_with_cache(&cache_dir, store.clone());
+    let syncing_result = syncer.sync().await.unwrap();
+    assert_eq!(syncing_result.total_cache_hits, 1);
+    assert_eq!(syncing_result.total_unparseable_items, 0);
+    assert_eq!(syncing_result.total_items_inserted, 6);
+
+    std::fs::remove_dir_all(cache_dir).unwrap();
+}
+
+/// A dry run only reads the cache, so the real run after it still gets, and stores, the changed
+/// page instead of a `304 Not Modified`.
+#[tokio::test]
+async fn test_dry_run_leaves_cache() {
+    let test_fixtures = common::setup().await;
+    let store = test_fixtures.store.clone();
+    let cache_dir = std::env::temp_dir().join(format!(
+        "venue-scraper-cache-dry-run-{}",
+        std::process::id()
+    ));
+    let spot_groningen = venue_definition("spot_groningen");
+    let sarah_shook_url = "https://www.spotgroningen.nl/programma/sarah-shook-the-disarmers/";
+
+    let syncer = scraper_with_cache(
+        &spot_groningen,
+        Arc::new(caching_sender("removal-test-case-run-1", &cache_dir)),
+        store.clone(),
+    );
+    let syncing_result = syncer.sync().await.unwrap();
+    assert_eq!(syncing_result.total_items_inserted, 6);
+
+    // The page changed, a dry run sees it.
+    let dry_run_store = Arc::new(DryRunAgendaStore::new(store.clone()));
+    let syncer = scraper_with_cache(
+        &spot_groningen,
+        Arc::new(caching_sender("removal-test-case-run-2", &cache_dir).with_read_only(true)),
+        dry_run_store.clone(),
+    );
+    let syncing_result = syncer.sync().await.unwrap();
+    assert_eq!(syncing_result.total_cache_hits, 0);
+    assert_eq!(syncing_result.total_items, 3);
+    assert!(!dry_run_store.changes().await.unwrap().is_empty());
+    let sarah_shook = store.get_agenda_by_url(sarah_shook_url).await.unwrap();
+    assert_eq!(sarah_shook.unwrap().status, AgendaStatus::Active);
+
+    // The real run gets the changed page too.
+    let syncer = scraper_with_cache(
+        &spot_groningen,
+        Arc::new(caching_sender("removal-test-case-run-2", &cache_dir)),
+        store.clone(),
+    );
+    let syncing_result = syncer.sync().await.unwrap();
+    assert_eq!(syncing_result.total_cache_hits, 0);
+    assert_eq!(syncing_result.total_items, 3);
+    let sarah_shook = store.get_agenda_by_url(sarah_shook_url).await.unwrap();
+    assert_eq!(sarah_shook.unwrap().status, AgendaStatus::Cancelled);
+
+    // Now the real run stored it, it is cached.
+    let syncing_result = syncer.sync().await.unwrap();
+    assert_eq!(syncing_result.total_cache_hits, 1);
+
+    std::fs::remove_dir_all(cache_dir).unwrap();
+}
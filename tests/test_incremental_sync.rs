@@ -0,0 +1,86 @@
+mod common;
+mod mock_sender;
+
+use mock_sender::{scraper_with_mock_sender, venue_definition};
+use venue_scraper_api::store::AgendaStore;
+use venue_scraper_api::venue_definition::IncrementalDefinition;
+use venue_scraper_api::{SyncMode, VenueScraper};
+
+fn incremental_tivoli(
+    full_sync_hours: i64,
+    test_case: &str,
+    store: std::sync::Arc<dyn AgendaStore>,
+) -> VenueScraper {
+    let mut tivoli = venue_definition("tivoli_utrecht");
+    tivoli.incremental = Some(IncrementalDefinition {
+        known_pages: 2,
+        full_sync_hours,
+    });
+    scraper_with_mock_sender(&tivoli, test_case, store)
+}
+
+/// After a full sync, the next sync stops after two pages of known items, until a full sync is due.
+#[tokio::test]
+async fn test_incremental_sync() {
+    let test_fixtures = common::setup().await;
+    let tivoli_syncer = incremental_tivoli(24, "default-test-case", test_fixtures.store.clone());
+
+    // Nothing synced yet, so the first sync is a full sync.
+    assert_eq!(
+        tivoli_syncer.scheduled_sync_mode().await.unwrap(),
+        SyncMode::Full
+    );
+    let syncing_result = tivoli_syncer.sync().await.unwrap();
+    assert_eq!(syncing_result.total_urls_fetched, 12);
+    assert_eq!(syncing_result.total_items_inserted, 591);
+
+    assert_eq!(
+        tivoli_syncer.scheduled_sync_mode().await.unwrap(),
+        SyncMode::Incremental { known_pages: 2 }
+    );
+    let syncing_result = tivoli_syncer.sync().await.unwrap();
+    assert_eq!(syncing_result.total_urls_fetched, 2);
+    assert_eq!(syncing_result.total_items_inserted, 0);
+    assert_eq!(syncing_result.total_items_removed, 0);
+
+    // A full sync is always available.
+    let syncing_result = tivoli_syncer.sync_with_mode(SyncMode::Full).await.unwrap();
+    assert_eq!(syncing_result.total_urls_fetched, 12);
+    assert_eq!(syncing_result.total_items, 591);
+}
+
+/// The sync goes on until enough known pages, the new items of run 2 are on its second page.
+#[tokio::test]
+async fn test_incremental_sync_with_new_items() {
+    let test_fixtures = common::setup().await;
+    let tivoli_syncer = incremental_tivoli(24, "multiple-fetch-run-1", test_fixtures.store.clone());
+    let syncing_result = tivoli_syncer.sync().await.unwrap();
+    assert_eq!(syncing_result.total_items_inserted, 52);
+
+    let tivoli_syncer = incremental_tivoli(24, "multiple-fetch-run-2", test_fixtures.store.clone());
+    let syncing_result = tivoli_syncer
+        .sync_with_mode(SyncMode::Incremental { known_pages: 1 })
+        .await
+        .unwrap();
+    assert_eq!(syncing_result.total_urls_fetched, 1);
+    assert_eq!(syncing_result.total_items_inserted, 0);
+
+    let syncing_result = tivoli_syncer.sync().await.unwrap();
+    assert_eq!(syncing_result.total_urls_fetched, 2);
+    assert_eq!(syncing_result.total_items_inserted, 4);
+}
+
+/// Without a recent full sync, the sync is a full sync.
+#[tokio::test]
+async fn test_full_sync_when_due() {
+    let test_fixtures = common::setup().await;
+    let tivoli_syncer = incremental_tivoli(0, "default-test-case", test_fixtures.store.clone());
+    tivoli_syncer.sync().await.unwrap();
+
+    assert_eq!(
+        tivoli_syncer.scheduled_sync_mode().await.unwrap(),
+        SyncMode::Full
+    );
+    let syncing_result = tivoli_syncer.sync().await.unwrap();
+    assert_eq!(syncing_result.total_urls_fetched, 12);
+}
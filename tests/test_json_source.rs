@@ -0,0 +1,294 @@
+mod common;
+
+use async_trait::async_trait;
+use reqwest::{RequestBuilder, Response, ResponseBuilderExt};
+use serde_json::json;
+use std::sync::Arc;
+use venue_scraper_api::errors::ErrorKind;
+use venue_scraper_api::http_sender::HttpSender;
+use venue_scraper_api::json_path::JsonPath;
+use venue_scraper_api::venue_definition::parse_venue_definitions;
+use venue_scraper_api::VenueScraper;
+
+const NEXT_PAGE: &str = r#"
+<html><body><div id="__next"></div>
+<script id="__NEXT_DATA__" type="application/json">
+{"props": {"pageProps": {"events": [
+  {"slug": "kane", "title": "Kane &amp; Abel", "start": "2030-03-14T20:30:00+01:00",
+   "tickets": {"url": "/tickets/kane"}},
+  {"slug": "lizzo", "title": "Lizzo", "start": "zaterdag 16 maart 2030 20:00"},
+  {"title": "Without a slug"}
+]}}}
+</script>
+</body></html>
+"#;
+
+const NUXT_PAGE: &str = r#"
+<html><body>
+<script>if (window.__NUXT__ === undefined) { console.log("loading"); }</script>
+<script>window.__NUXT__ = {"data": [{"agenda": {"items": [
+  {"link": "https://www.example.com/nuxt/event-1", "name": "Nuxt one", "date": "2030-05-01"}
+]}}]}; window.__READY__ = true;</script>
+</body></html>
+"#;
+
+/// Sender answering the Next.js and Nuxt pages, and two pages of a wp-json endpoint.
+struct EmbeddedJsonSender;
+
+#[async_trait]
+impl HttpSender for EmbeddedJsonSender {
+    async fn send(&self, request: RequestBuilder) -> Result<Response, ErrorKind> {
+        let url = request.build().unwrap().url().clone();
+        let wp_page = |ids: std::ops::Range<u32>| {
+            let events: Vec<serde_json::Value> = ids
+                .map(|id| {
+                    json!({
+                        "link": format!("https://www.example.com/events/{}/", id),
+                        "title": {"rendered": format!("Event &#8211; {}", id)},
+                        "acf": {"start": "2030-06-01T21:00:00", "price": 12.5},
+                    })
+                })
+                .collect();
+            serde_json::to_string(&events).unwrap()
+        };
+        let (status, body) = match (url.path(), url.query()) {
+            ("/next/", _) => (200, NEXT_PAGE.to_string()),
+            ("/nuxt/", _) => (200, NUXT_PAGE.to_string()),
+            ("/wp-json/wp/v2/events", Some("per_page=2")) => (200, wp_page(1..3)),
+            ("/wp-json/wp/v2/events", Some("per_page=2&page=2")) => (200, wp_page(3..4)),
+            _ => (404, String::new()),
+        };
+        let response = http::response::Builder::new()
+            .url(url)
+            .status(status)
+            .body(body)
+            .unwrap();
+        Ok(response.into())
+    }
+}
+
+fn venue_scraper(venues: &str, test_fixtures: &common::TestFixtures) -> VenueScraper {
+    let venue_definition = parse_venue_definitions(venues).unwrap().remove(0);
+    VenueScraper::from_definition(
+        &venue_definition,
+        Arc::new(EmbeddedJsonSender),
+        reqwest::Client::new(),
+        test_fixtures.store.clone(),
+    )
+    .unwrap()
+}
+
+#[test]
+fn test_json_path() {
+    let json = json!({
+        "props": {"events": [{"id": 1, "tags": ["a"]}, {"id": 2, "my name": "x"}]},
+        "id": 0,
+    });
+    let ids = |path: &str| -> Vec<serde_json::Value> {
+        JsonPath::parse(path)
+            .unwrap()
+            .select(&json)
+            .into_iter()
+            .cloned()
+            .collect()
+    };
+    assert_eq!(ids("$.props.events[*].id"), vec![json!(1), json!(2)]);
+    assert_eq!(ids("$.props.events[1].id"), vec![json!(2)]);
+    assert_eq!(ids("$['props'][\"events\"][0].tags[0]"), vec![json!("a")]);
+    assert_eq!(ids("$.props.events[1]['my name']"), vec![json!("x")]);
+    assert_eq!(ids("$..id"), vec![json!(0), json!(1), json!(2)]);
+    assert_eq!(ids("$.props.*").len(), 1);
+    assert!(ids("$.props.missing[*]").is_empty());
+    assert_eq!(ids("$"), vec![json.clone()]);
+
+    for invalid in ["props.events", "$.", "$.events[", "$.events[x]", "$events"] {
+        assert!(
+            matches!(
+                JsonPath::parse(invalid),
+                Err(ErrorKind::JsonPathError { .. })
+            ),
+            "{}",
+            invalid
+        );
+    }
+}
+
+/// The events in `__NEXT_DATA__` become agenda items, ISO starts are exact and other start texts
+/// are parsed, the item without a url cannot be mapped.
+#[tokio::test]
+async fn test_sync_next_data() {
+    let test_fixtures = common::setup().await;
+    let venue_scraper = venue_scraper(
+        r#"
+        [[venue]]
+        id = "next_venue"
+        name = "Next venue"
+        agenda_url = "https://www.example.com/next/"
+
+        [venue.json]
+        script = "script#__NEXT_DATA__"
+        items = "$.props.pageProps.events"
+
+        [venue.json.fields]
+        url = "$.slug"
+        title = "$.title"
+        start = "$.start"
+        ticket_url = "$.tickets.url"
+        "#,
+        &test_fixtures,
+    );
+
+    let syncing_result = venue_scraper.sync().await.unwrap();
+    assert_eq!(syncing_result.total_items, 3);
+    assert_eq!(syncing_result.total_items_inserted, 2);
+    assert_eq!(syncing_result.total_unparseable_items, 1);
+    assert_eq!(syncing_result.total_unparseable_dates, 0);
+
+    let store = &test_fixtures.store;
+    let kane = store
+        .get_agenda_by_url("https://www.example.com/next/kane/")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(kane.title, "Kane & Abel");
+    assert_eq!(
+        kane.starts_at.unwrap().to_rfc3339(),
+        "2030-03-14T20:30:00+01:00"
+    );
+    assert_eq!(
+        kane.ticket_url.as_deref(),
+        Some("https://www.example.com/tickets/kane")
+    );
+    let lizzo = store
+        .get_agenda_by_url("https://www.example.com/next/lizzo/")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        lizzo.starts_at.unwrap().to_rfc3339(),
+        "2030-03-16T20:00:00+01:00"
+    );
+
+    let syncing_result = venue_scraper.sync().await.unwrap();
+    assert_eq!(syncing_result.total_items_inserted, 0);
+    assert_eq!(syncing_result.total_items_updated, 0);
+}
+
+/// The JSON assigned to `window.__NUXT__`, the script testing the variable is skipped.
+#[tokio::test]
+async fn test_sync_nuxt_variable() {
+    let test_fixtures = common::setup().await;
+    let venue_scraper = venue_scraper(
+        r#"
+        [[venue]]
+        id = "nuxt_venue"
+        name = "Nuxt venue"
+        agenda_url = "https://www.example.com/nuxt/"
+
+        [venue.json]
+        variable = "window.__NUXT__"
+        items = "$..items[*]"
+
+        [venue.json.fields]
+        url = "$.link"
+        title = "$.name"
+        start = "$.date"
+        "#,
+        &test_fixtures,
+    );
+
+    let syncing_result = venue_scraper.sync().await.unwrap();
+    assert_eq!(syncing_result.total_items_inserted, 1);
+    let agenda = test_fixtures
+        .store
+        .get_agenda_by_url("https://www.example.com/nuxt/event-1/")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(agenda.title, "Nuxt one");
+    assert!(agenda.all_day);
+}
+
+/// A wp-json endpoint is JSON itself, its pages are numbered until a page is not found.
+#[tokio::test]
+async fn test_sync_wp_json_pages() {
+    let test_fixtures = common::setup().await;
+    let venue_scraper = venue_scraper(
+        r#"
+        [[venue]]
+        id = "wp_venue"
+        name = "WordPress venue"
+        agenda_url = "https://www.example.com/wp-json/wp/v2/events?per_page=2"
+
+        [venue.pagination]
+        strategy = "numbered"
+        url_template = "https://www.example.com/wp-json/wp/v2/events?per_page=2&page={page}"
+
+        [venue.json]
+        items = "$[*]"
+
+        [venue.json.fields]
+        url = "$.link"
+        title = "$.title.rendered"
+        start = "$.acf.start"
+        price = "$.acf.price"
+        "#,
+        &test_fixtures,
+    );
+
+    let syncing_result = venue_scraper.sync().await.unwrap();
+    assert_eq!(syncing_result.total_urls_fetched, 2);
+    assert_eq!(syncing_result.total_items_inserted, 3);
+
+    let agenda = test_fixtures
+        .store
+        .get_agenda_by_url("https://www.example.com/events/3/")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(agenda.title, "Event – 3");
+    assert_eq!(agenda.price.as_deref(), Some("12.5"));
+    assert_eq!(
+        agenda.starts_at.unwrap().to_rfc3339(),
+        "2030-06-01T21:00:00+02:00"
+    );
+}
+
+#[test]
+fn test_invalid_json_definitions() {
+    for (json, selectors) in [
+        ("items = \"events\"", ""),
+        ("script = \"s\"\nvariable = \"v\"\nitems = \"$\"", ""),
+        (
+            "items = \"$\"",
+            "[venue.selectors]\nagenda_item = \"li\"\nurl = \"a\"\ntitle = \"a\"\ndescription = \"p\"",
+        ),
+    ] {
+        let venues = format!(
+            r#"
+            [[venue]]
+            id = "invalid_json"
+            name = "Invalid json"
+            agenda_url = "https://www.example.com/"
+
+            [venue.json]
+            {}
+
+            [venue.json.fields]
+            url = "$.link"
+            title = "$.title"
+
+            {}
+            "#,
+            json, selectors
+        );
+        assert!(
+            matches!(
+                parse_venue_definitions(&venues),
+                Err(ErrorKind::InvalidVenueDefinition { .. })
+            ),
+            "{}",
+            json
+        );
+    }
+}
@@ -0,0 +1,344 @@
+mod common;
+
+use async_trait::async_trait;
+use reqwest::{RequestBuilder, Response, ResponseBuilderExt};
+use std::sync::Arc;
+use venue_scraper_api::agenda::AgendaStatus;
+use venue_scraper_api::errors::ErrorKind;
+use venue_scraper_api::http_sender::HttpSender;
+use venue_scraper_api::store::AgendaStore;
+use venue_scraper_api::venue_definition::parse_venue_definitions;
+use venue_scraper_api::{SyncingResult, VenueScraper};
+
+const ITEMS_PER_PAGE: usize = 10;
+
+/// What the agenda answers for pages after the last.
+#[derive(Clone, Copy)]
+enum EndOfAgenda {
+    EmptyPage,
+    NotFound,
+    RepeatLastPage,
+}
+
+/// Sender generating an agenda of total_items items, with pages for every strategy:
+/// `/agenda/page/{page}/` with next links, `/agenda/?offset=..&limit=..` and `/more?page=..` with
+/// JSON. The agenda url `/agenda/` is the first page.
+struct AgendaSender {
+    total_items: usize,
+    end_of_agenda: EndOfAgenda,
+}
+
+impl AgendaSender {
+    fn items_html(&self, offset: usize, limit: usize) -> String {
+        (offset..(offset + limit).min(self.total_items))
+            .map(|item| {
+                format!(
+                    "<li><a href=\"/event/{}/\">Event {}</a><p>Description</p></li>",
+                    item, item
+                )
+            })
+            .collect()
+    }
+
+    fn last_page(&self) -> usize {
+        self.total_items.div_ceil(ITEMS_PER_PAGE)
+    }
+
+    /// The page number to show for the page, None when the page is not found.
+    fn shown_page(&self, page: usize) -> Option<usize> {
+        if page <= self.last_page() {
+            return Some(page);
+        }
+        match self.end_of_agenda {
+            EndOfAgenda::EmptyPage => Some(page),
+            EndOfAgenda::NotFound => None,
+            EndOfAgenda::RepeatLastPage => Some(self.last_page()),
+        }
+    }
+
+    fn page_html(&self, page: usize) -> String {
+        let next_link = if page < self.last_page() {
+            format!(
+                "<a rel=\"next\" href=\"/agenda/page/{}/\">Next</a>",
+                page + 1
+            )
+        } else {
+            String::new()
+        };
+        format!(
+            "<html><body><ul>{}</ul>{}</body></html>",
+            self.items_html((page - 1) * ITEMS_PER_PAGE, ITEMS_PER_PAGE),
+            next_link
+        )
+    }
+
+    fn body_for(&self, url: &reqwest::Url) -> Option<String> {
+        let query_parameter = |name: &str| {
+            url.query_pairs()
+                .find(|(parameter, _)| parameter == name)
+                .map(|(_, value)| value.parse::<usize>().unwrap())
+        };
+        let path = url.path();
+        if let (Some(offset), Some(limit)) = (query_parameter("offset"), query_parameter("limit")) {
+            let html = format!("<ul>{}</ul>", self.items_html(offset, limit));
+            return Some(html);
+        }
+        if path == "/more" {
+            let page = self.shown_page(query_parameter("page").unwrap())?;
+            let json = serde_json::json!({
+                "data": { "html": format!("<ul>{}</ul>", self.items_html((page - 1) * ITEMS_PER_PAGE, ITEMS_PER_PAGE)) },
+                "has_more": page < self.last_page(),
+            });
+            return Some(json.to_string());
+        }
+        if path == "/agenda/" {
+            return Some(self.page_html(1));
+        }
+        let page = path
+            .strip_prefix("/agenda/page/")?
+            .trim_end_matches('/')
+            .parse()
+            .ok()?;
+        Some(self.page_html(self.shown_page(page)?))
+    }
+}
+
+#[async_trait]
+impl HttpSender for AgendaSender {
+    async fn send(&self, request: RequestBuilder) -> Result<Response, ErrorKind> {
+        let url = request.build().unwrap().url().clone();
+        let (status, body) = match self.body_for(&url) {
+            Some(body) => (200, body),
+            None => (404, String::new()),
+        };
+        let response = http::response::Builder::new()
+            .url(url)
+            .status(status)
+            .body(body)
+            .unwrap();
+        Ok(response.into())
+    }
+}
+
+async fn sync_with_pagination(
+    pagination: &str,
+    total_items: usize,
+    end_of_agenda: EndOfAgenda,
+) -> SyncingResult {
+    let test_fixtures = common::setup().await;
+    venue_scraper(
+        pagination,
+        total_items,
+        end_of_agenda,
+        test_fixtures.store.clone(),
+    )
+    .sync()
+    .await
+    .unwrap()
+}
+
+/// Scraper of the agenda of the sender, removed items are marked at the first sync without them.
+fn venue_scraper(
+    pagination: &str,
+    total_items: usize,
+    end_of_agenda: EndOfAgenda,
+    store: Arc<dyn AgendaStore>,
+) -> VenueScraper {
+    let venues = format!(
+        r#"
+        [[venue]]
+        id = "paginated_venue"
+        name = "Paginated venue"
+        agenda_url = "https://agenda.example.com/agenda/"
+        removal_grace_hours = 0
+
+        [venue.pagination]
+        {}
+
+        [venue.selectors]
+        agenda_item = "li"
+        url = "a"
+        title = "a"
+        description = "p"
+        "#,
+        pagination
+    );
+    let venue_definition = parse_venue_definitions(&venues).unwrap().remove(0);
+    let http_sender = Arc::new(AgendaSender {
+        total_items,
+        end_of_agenda,
+    });
+    VenueScraper::from_definition(
+        &venue_definition,
+        http_sender,
+        reqwest::Client::new(),
+        store,
+    )
+    .unwrap()
+}
+
+/// Follow the next links until a page without one.
+#[tokio::test]
+async fn test_next_link_pagination() {
+    let result = sync_with_pagination(r#"strategy = "next_link""#, 23, EndOfAgenda::NotFound).await;
+    assert_eq!(result.total_urls_fetched, 3);
+    assert_eq!(result.total_items_inserted, 23);
+}
+
+/// Numbered pages until an empty page, a page that is not found, or a page without new items.
+#[tokio::test]
+async fn test_numbered_pagination() {
+    let numbered = r#"
+        strategy = "numbered"
+        url_template = "https://agenda.example.com/agenda/page/{page}/"
+    "#;
+
+    let result = sync_with_pagination(numbered, 23, EndOfAgenda::EmptyPage).await;
+    assert_eq!(result.total_urls_fetched, 4);
+    assert_eq!(result.total_items_inserted, 23);
+
+    // The last page is as full as the page before it.
+    let result = sync_with_pagination(numbered, 30, EndOfAgenda::NotFound).await;
+    assert_eq!(result.total_urls_fetched, 3);
+    assert_eq!(result.total_urls_unfetchable, 0);
+    assert_eq!(result.total_items_inserted, 30);
+
+    let result = sync_with_pagination(numbered, 23, EndOfAgenda::RepeatLastPage).await;
+    assert_eq!(result.total_urls_fetched, 4);
+    assert_eq!(result.total_items_inserted, 23);
+}
+
+/// Offset and limit until a page that is not full.
+#[tokio::test]
+async fn test_offset_pagination() {
+    let result = sync_with_pagination(
+        r#"
+        strategy = "offset"
+        limit = 10
+        "#,
+        23,
+        EndOfAgenda::NotFound,
+    )
+    .await;
+    assert_eq!(result.total_urls_fetched, 3);
+    assert_eq!(result.total_items_inserted, 23);
+}
+
+/// Load more JSON until has more is false.
+#[tokio::test]
+async fn test_load_more_pagination() {
+    let result = sync_with_pagination(
+        r#"
+        strategy = "load_more"
+        url_template = "https://agenda.example.com/more?page={page}"
+        html_pointer = "/data/html"
+        has_more_pointer = "/has_more"
+        "#,
+        23,
+        EndOfAgenda::RepeatLastPage,
+    )
+    .await;
+    assert_eq!(result.total_urls_fetched, 3);
+    assert_eq!(result.total_items_inserted, 23);
+}
+
+/// No more than max_pages pages are fetched.
+#[tokio::test]
+async fn test_max_pages() {
+    let result = sync_with_pagination(
+        r#"
+        strategy = "numbered"
+        url_template = "https://agenda.example.com/agenda/page/{page}/"
+        max_pages = 2
+        "#,
+        100,
+        EndOfAgenda::NotFound,
+    )
+    .await;
+    assert_eq!(result.total_urls_fetched, 2);
+    assert_eq!(result.total_items_inserted, 20);
+}
+
+/// A sync stopped by max_pages has not seen the items on the later pages, they are not removed.
+#[tokio::test]
+async fn test_max_pages_does_not_remove_later_items() {
+    let test_fixtures = common::setup().await;
+    let store = test_fixtures.store.clone();
+    let pagination = |max_pages: u32| {
+        format!(
+            r#"
+            strategy = "numbered"
+            url_template = "https://agenda.example.com/agenda/page/{{page}}/"
+            max_pages = {}
+            "#,
+            max_pages
+        )
+    };
+    let result = venue_scraper(&pagination(10), 50, EndOfAgenda::NotFound, store.clone())
+        .sync()
+        .await
+        .unwrap();
+    assert_eq!(result.total_items_inserted, 50);
+
+    let result = venue_scraper(&pagination(2), 50, EndOfAgenda::NotFound, store.clone())
+        .sync()
+        .await
+        .unwrap();
+    assert_eq!(result.total_urls_fetched, 2);
+    assert_eq!(result.total_items_removed, 0);
+    assert!(store
+        .agenda_items_for_venue("paginated_venue")
+        .await
+        .unwrap()
+        .iter()
+        .all(|agenda| agenda.status == AgendaStatus::Active));
+
+    // A listing that fits in max_pages is walked in full.
+    let result = venue_scraper(&pagination(10), 30, EndOfAgenda::NotFound, store.clone())
+        .sync()
+        .await
+        .unwrap();
+    assert_eq!(result.total_items_removed, 20);
+}
+
+/// Pagination without the placeholder, or without pages, is rejected.
+#[test]
+fn test_invalid_pagination() {
+    for pagination in [
+        r#"
+        strategy = "numbered"
+        url_template = "https://agenda.example.com/agenda/page/"
+        "#,
+        r#"
+        strategy = "offset"
+        limit = 0
+        "#,
+        r#"
+        strategy = "next_link"
+        max_pages = 0
+        "#,
+        r#"
+        strategy = "infinite_scroll"
+        "#,
+    ] {
+        let venues = format!(
+            r#"
+            [[venue]]
+            id = "paginated_venue"
+            name = "Paginated venue"
+            agenda_url = "https://agenda.example.com/agenda/"
+
+            [venue.pagination]
+            {}
+
+            [venue.selectors]
+            agenda_item = "li"
+            url = "a"
+            title = "a"
+            description = "p"
+            "#,
+            pagination
+        );
+        assert!(parse_venue_definitions(&venues).is_err(), "{}", pagination);
+    }
+}
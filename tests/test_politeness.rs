@@ -0,0 +1,123 @@
+mod mock_sender;
+
+use async_trait::async_trait;
+use futures::future::join_all;
+use mock_sender::MockSender;
+use reqwest::{Client, RequestBuilder, Response};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::time::Instant;
+use venue_scraper_api::errors::ErrorKind;
+use venue_scraper_api::http_sender::HttpSender;
+use venue_scraper_api::politeness::{PoliteHttpSender, PolitenessPolicy};
+use venue_scraper_api::robots::RobotsTxt;
+
+/// Mock sender keeping track of the requests, and the most requests in flight at once.
+struct CountingSender {
+    mock_sender: MockSender,
+    requests: Arc<AtomicUsize>,
+    in_flight: Arc<AtomicUsize>,
+    max_in_flight: Arc<AtomicUsize>,
+}
+
+impl CountingSender {
+    fn new(test_case: &str) -> CountingSender {
+        CountingSender {
+            mock_sender: MockSender {
+                test_case: test_case.to_string(),
+            },
+            requests: Arc::new(AtomicUsize::new(0)),
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            max_in_flight: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+}
+
+#[async_trait]
+impl HttpSender for CountingSender {
+    async fn send(&self, request: RequestBuilder) -> Result<Response, ErrorKind> {
+        self.requests.fetch_add(1, Ordering::SeqCst);
+        let in_flight = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+        self.max_in_flight.fetch_max(in_flight, Ordering::SeqCst);
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        let response = self.mock_sender.send(request).await;
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+        response
+    }
+}
+
+fn policy(obey_robots_txt: bool) -> PolitenessPolicy {
+    PolitenessPolicy {
+        requests_per_second: 1000.0,
+        min_delay: Duration::ZERO,
+        max_concurrent_per_host: 2,
+        user_agent: "venue-scraper/0.1".to_string(),
+        obey_robots_txt,
+    }
+}
+
+const PROGRAM_URL: &str = "https://www.spotgroningen.nl/programma/";
+
+#[tokio::test]
+async fn test_robots_txt_is_obeyed() {
+    let counting_sender = CountingSender::new("robots-test-case");
+    let requests = counting_sender.requests.clone();
+    let client = Client::new();
+    let sender = PoliteHttpSender::new(counting_sender, client.clone(), policy(true));
+
+    let result = sender
+        .send(client.get("https://www.spotgroningen.nl/programma/keb-mo/"))
+        .await;
+    assert!(matches!(
+        result,
+        Err(ErrorKind::DisallowedByRobotsTxt { url }) if url == "https://www.spotgroningen.nl/programma/keb-mo/"
+    ));
+
+    // The crawl delay of 50ms applies between the requests.
+    let started_at = Instant::now();
+    for _ in 0..3 {
+        sender.send(client.get(PROGRAM_URL)).await.unwrap();
+    }
+    assert!(started_at.elapsed() >= Duration::from_millis(100));
+
+    // robots.txt is fetched once, disallowed urls are not fetched.
+    assert_eq!(requests.load(Ordering::SeqCst), 4);
+}
+
+#[tokio::test]
+async fn test_concurrent_requests_are_capped_per_host() {
+    let counting_sender = CountingSender::new("default-test-case");
+    let max_in_flight = counting_sender.max_in_flight.clone();
+    let client = Client::new();
+    let sender = PoliteHttpSender::new(counting_sender, client.clone(), policy(false));
+
+    let responses = join_all((0..6).map(|_| sender.send(client.get(PROGRAM_URL)))).await;
+    assert!(responses.iter().all(|response| response.is_ok()));
+    assert_eq!(max_in_flight.load(Ordering::SeqCst), 2);
+}
+
+#[test]
+fn test_parse_robots_txt() {
+    let contents =
+        std::fs::read_to_string("tests/files/www.spotgroningen.nl/robots-test-case/robots.txt")
+            .unwrap();
+
+    let robots_txt = RobotsTxt::parse(&contents, "venue-scraper/0.1");
+    assert!(robots_txt.is_allowed("/programma/"));
+    assert!(!robots_txt.is_allowed("/programma/keb-mo/"));
+    assert!(!robots_txt.is_allowed("/files/programma.pdf"));
+    assert!(robots_txt.is_allowed("/files/programma.pdf?download=1"));
+    // The group of the user agent replaces the * group.
+    assert!(robots_txt.is_allowed("/wp-admin/"));
+    assert_eq!(robots_txt.crawl_delay, Some(Duration::from_millis(50)));
+
+    let robots_txt = RobotsTxt::parse(&contents, "other-bot");
+    assert!(robots_txt.is_allowed("/programma/keb-mo/"));
+    assert!(!robots_txt.is_allowed("/wp-admin/"));
+
+    let robots_txt = RobotsTxt::parse("User-agent: *\nDisallow: /\nAllow: /agenda", "bot");
+    assert!(robots_txt.is_allowed("/agenda/page/2/"));
+    assert!(!robots_txt.is_allowed("/nieuws/"));
+    assert!(RobotsTxt::parse("User-agent: *\nDisallow:", "bot").is_allowed("/"));
+}
@@ -0,0 +1,154 @@
+use async_trait::async_trait;
+use mock_sender::MockSender;
+use reqwest::{RequestBuilder, Response};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use venue_scraper_api::errors::ErrorKind;
+use venue_scraper_api::http_sender::HttpSender;
+use venue_scraper_api::registry::VenueRegistry;
+use venue_scraper_api::store::memory::MemoryAgendaStore;
+use venue_scraper_api::venue_definition::load_venue_definitions;
+
+mod mock_sender;
+
+/// Mock sender counting the hosts, so venues, with requests in flight, panicking on the requests
+/// of panicking_host.
+struct InstrumentedSender {
+    mock_sender: MockSender,
+    panicking_host: Option<String>,
+    in_flight: Mutex<HashMap<String, usize>>,
+    max_hosts_in_flight: AtomicUsize,
+}
+
+impl InstrumentedSender {
+    fn new(panicking_host: Option<&str>) -> InstrumentedSender {
+        InstrumentedSender {
+            mock_sender: MockSender {
+                test_case: String::from("default-test-case"),
+            },
+            panicking_host: panicking_host.map(String::from),
+            in_flight: Mutex::new(HashMap::new()),
+            max_hosts_in_flight: AtomicUsize::new(0),
+        }
+    }
+}
+
+#[async_trait]
+impl HttpSender for InstrumentedSender {
+    async fn send(&self, request: RequestBuilder) -> Result<Response, ErrorKind> {
+        let host = request
+            .try_clone()
+            .unwrap()
+            .build()
+            .unwrap()
+            .url()
+            .host_str()
+            .unwrap()
+            .to_string();
+        if Some(&host) == self.panicking_host.as_ref() {
+            panic!("Unexpected request to {}", host);
+        }
+        {
+            let mut in_flight = self.in_flight.lock().unwrap();
+            *in_flight.entry(host.clone()).or_default() += 1;
+            let hosts_in_flight = in_flight.values().filter(|count| **count > 0).count();
+            self.max_hosts_in_flight
+                .fetch_max(hosts_in_flight, Ordering::SeqCst);
+        }
+        tokio::time::sleep(Duration::from_millis(1)).await;
+        let response = self.mock_sender.send(request).await;
+        *self.in_flight.lock().unwrap().get_mut(&host).unwrap() -= 1;
+        response
+    }
+}
+
+async fn registry_with_all_venues() -> VenueRegistry {
+    let mock_sender = Arc::new(MockSender {
+        test_case: String::from("default-test-case"),
+    });
+    registry_with_sender(mock_sender)
+}
+
+fn registry_with_sender(http_sender: Arc<dyn HttpSender>) -> VenueRegistry {
+    let client = reqwest::Client::new();
+    let store = Arc::new(MemoryAgendaStore::new());
+    let venue_definitions = load_venue_definitions(Path::new("venues.toml")).unwrap();
+    VenueRegistry::from_definitions(&venue_definitions, http_sender, &client, store).unwrap()
+}
+
+#[tokio::test]
+async fn test_filter_registry() {
+    let venue_registry = registry_with_all_venues().await;
+    assert_eq!(
+        venue_registry.venue_ids(),
+        vec!["tivoli_utrecht", "spot_groningen"]
+    );
+
+    let venue_registry = venue_registry.filter_by_tag("groningen");
+    assert_eq!(venue_registry.venue_ids(), vec!["spot_groningen"]);
+
+    let venue_registry = registry_with_all_venues()
+        .await
+        .filter_by_ids(&["tivoli_utrecht".to_string()])
+        .unwrap();
+    assert_eq!(venue_registry.venue_ids(), vec!["tivoli_utrecht"]);
+
+    let result = registry_with_all_venues()
+        .await
+        .filter_by_ids(&["paradiso".to_string()]);
+    assert!(matches!(result, Err(ErrorKind::UnknownVenue { venue_id }) if venue_id == "paradiso"));
+}
+
+/// A scraper can be moved to a task on another worker thread.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_spawn_scraper() {
+    let venue_scraper = mock_sender::spot_groningen_with_mock_sender(
+        "default-test-case",
+        Arc::new(MemoryAgendaStore::new()),
+    );
+    let syncing_result = tokio::spawn(async move { venue_scraper.sync().await })
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(syncing_result.total_items_inserted > 0);
+}
+
+/// With one venue at a time, the venues never request at the same time.
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_sync_all_bounded() {
+    let http_sender = Arc::new(InstrumentedSender::new(None));
+    let venue_registry = registry_with_sender(http_sender.clone()).with_max_concurrent_venues(1);
+    let sync_report = venue_registry.sync_all().await;
+    assert!(sync_report.failed_venues().is_empty());
+    assert_eq!(http_sender.max_hosts_in_flight.load(Ordering::SeqCst), 1);
+
+    let http_sender = Arc::new(InstrumentedSender::new(None));
+    let venue_registry = registry_with_sender(http_sender.clone()).with_max_concurrent_venues(2);
+    let sync_report = venue_registry.sync_all().await;
+    assert!(sync_report.failed_venues().is_empty());
+    assert_eq!(http_sender.max_hosts_in_flight.load(Ordering::SeqCst), 2);
+}
+
+/// A panicking venue gets a failed report, the other venues sync, reports are in registry order.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_sync_all_isolates_panics() {
+    let http_sender = Arc::new(InstrumentedSender::new(Some("www.tivolivredenburg.nl")));
+    let sync_report = registry_with_sender(http_sender).sync_all().await;
+
+    let venue_ids: Vec<&str> = sync_report
+        .venue_reports
+        .iter()
+        .map(|venue_report| venue_report.venue_id.as_str())
+        .collect();
+    assert_eq!(venue_ids, vec!["tivoli_utrecht", "spot_groningen"]);
+    assert!(matches!(
+        &sync_report.venue_reports[0].result,
+        Err(ErrorKind::VenueSyncPanicked { venue_id, .. }) if venue_id == "tivoli_utrecht"
+    ));
+    assert!(sync_report.venue_reports[1].is_ok());
+    assert_eq!(sync_report.failed_venues().len(), 1);
+    assert!(sync_report.total().total_items_inserted > 0);
+}
@@ -0,0 +1,153 @@
+mod common;
+mod mock_sender;
+
+use common::json_venue::{json_venue_definition, ListingSender};
+use mock_sender::{scraper_with_mock_sender, venue_definition};
+use serde_json::json;
+use std::sync::Arc;
+use venue_scraper_api::agenda::AgendaStatus;
+use venue_scraper_api::store::AgendaStore;
+use venue_scraper_api::VenueScraper;
+
+async fn status_of(store: &dyn AgendaStore, url: &str) -> AgendaStatus {
+    store.get_agenda_by_url(url).await.unwrap().unwrap().status
+}
+
+/// Test if agenda items that disappear from the listing are marked.
+/// 1. Sync a program with 6 items, two of them upcoming.
+/// 2. Sync the program without two upcoming items and a past one, and with a cancelled one.
+/// Within the grace period nothing is marked, without one the missing items are.
+#[tokio::test]
+async fn test_sync_marks_disappeared_items() {
+    let test_fixtures = common::setup().await;
+    let store = test_fixtures.store.clone();
+
+    // 1. The full program.
+    let mut spot_groningen = venue_definition("spot_groningen");
+    let syncer =
+        scraper_with_mock_sender(&spot_groningen, "removal-test-case-run-1", store.clone());
+    let syncing_result = syncer.sync().await.unwrap();
+    assert_eq!(syncing_result.total_items_inserted, 6);
+    assert_eq!(syncing_result.total_items_removed, 0);
+
+    // 2. Items missing within the grace period are kept.
+    let syncer =
+        scraper_with_mock_sender(&spot_groningen, "removal-test-case-run-2", store.clone());
+    let syncing_result = syncer.sync().await.unwrap();
+    assert_eq!(syncing_result.total_items, 3);
+    assert_eq!(syncing_result.total_items_removed, 0);
+    let keb_mo_url = "https://www.spotgroningen.nl/programma/keb-mo/";
+    assert_eq!(status_of(&*store, keb_mo_url).await, AgendaStatus::Active);
+
+    // Without a grace period the missing items are marked.
+    spot_groningen.removal_grace_hours = 0;
+    let syncer =
+        scraper_with_mock_sender(&spot_groningen, "removal-test-case-run-2", store.clone());
+    let syncing_result = syncer.sync().await.unwrap();
+    assert_eq!(syncing_result.total_items_removed, 3);
+
+    assert_eq!(status_of(&*store, keb_mo_url).await, AgendaStatus::Removed);
+    assert_eq!(
+        status_of(
+            &*store,
+            "https://www.spotgroningen.nl/programma/swamp-dogg/"
+        )
+        .await,
+        AgendaStatus::Removed
+    );
+    assert_eq!(
+        status_of(
+            &*store,
+            "https://www.spotgroningen.nl/programma/herman-van-veen-11/"
+        )
+        .await,
+        AgendaStatus::Past
+    );
+    assert_eq!(
+        status_of(
+            &*store,
+            "https://www.spotgroningen.nl/programma/sarah-shook-the-disarmers/"
+        )
+        .await,
+        AgendaStatus::Cancelled
+    );
+    let michael_patrick_kelly = store
+        .get_agenda_by_url("https://www.spotgroningen.nl/programma/michael-patrick-kelly/")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(michael_patrick_kelly.status, AgendaStatus::Active);
+    assert!(michael_patrick_kelly.last_seen_at.is_some());
+
+    // Once marked, the items are not counted again.
+    let syncing_result = syncer.sync().await.unwrap();
+    assert_eq!(syncing_result.total_items_removed, 0);
+}
+
+fn event(index: u32, with_link: bool) -> serde_json::Value {
+    let mut event =
+        json!({"title": format!("Event {}", index), "start": "2030-03-14T20:30:00+01:00"});
+    if with_link {
+        event["link"] = json!(format!("https://www.example.com/events/{}/", index));
+    }
+    event
+}
+
+/// A listing on which many agenda items cannot be parsed does not tell which items are gone, so
+/// nothing is marked. The items that are seen still get their last_seen_at.
+#[tokio::test]
+async fn test_unparseable_listing_does_not_mark_items() {
+    let test_fixtures = common::setup().await;
+    let store = test_fixtures.store.clone();
+    let mut json_venue = json_venue_definition();
+    json_venue.removal_grace_hours = 0;
+    let sender = Arc::new(ListingSender::new(json!((0..10)
+        .map(|index| event(index, true))
+        .collect::<Vec<_>>())));
+    let syncer = VenueScraper::from_definition(
+        &json_venue,
+        sender.clone(),
+        reqwest::Client::new(),
+        store.clone(),
+    )
+    .unwrap();
+    let syncing_result = syncer.sync().await.unwrap();
+    assert_eq!(syncing_result.total_items_inserted, 10);
+    let first_seen_at = store
+        .get_agenda_by_url("https://www.example.com/events/0/")
+        .await
+        .unwrap()
+        .unwrap()
+        .last_seen_at
+        .unwrap();
+
+    // Half of the items lost their link.
+    sender.set_events(json!((0..10)
+        .map(|index| event(index, index < 5))
+        .collect::<Vec<_>>()));
+    let syncing_result = syncer.sync().await.unwrap();
+    assert_eq!(syncing_result.total_unparseable_items, 5);
+    assert_eq!(syncing_result.total_items_removed, 0);
+    for index in 0..10 {
+        let url = format!("https://www.example.com/events/{}/", index);
+        assert_eq!(status_of(&*store, &url).await, AgendaStatus::Active);
+    }
+
+    // Once the listing parses again, the missing items are marked.
+    sender.set_events(json!((0..9)
+        .map(|index| event(index, true))
+        .collect::<Vec<_>>()));
+    let syncing_result = syncer.sync().await.unwrap();
+    assert_eq!(syncing_result.total_items_removed, 1);
+    let first = store
+        .get_agenda_by_url("https://www.example.com/events/0/")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(first.status, AgendaStatus::Active);
+    assert!(first.last_seen_at.unwrap() > first_seen_at);
+    assert_eq!(
+        status_of(&*store, "https://www.example.com/events/9/").await,
+        AgendaStatus::Removed
+    );
+}
@@ -0,0 +1,188 @@
+mod common;
+mod mock_sender;
+
+use async_trait::async_trait;
+use chrono::{TimeZone, Utc};
+use mock_sender::{venue_definition, MockSender};
+use reqwest::{RequestBuilder, Response};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use venue_scraper_api::errors::ErrorKind;
+use venue_scraper_api::http_sender::HttpSender;
+use venue_scraper_api::retry::{parse_retry_after, RetryPolicy, RetryingHttpSender};
+use venue_scraper_api::VenueScraper;
+
+/// Sender failing the first requests with the status, or a connection error for status 0, before
+/// passing the requests on to the mock sender.
+struct FlakySender {
+    failures: u32,
+    status: u16,
+    calls: Arc<AtomicU32>,
+    mock_sender: MockSender,
+}
+
+impl FlakySender {
+    fn new(failures: u32, status: u16, calls: &Arc<AtomicU32>) -> FlakySender {
+        FlakySender {
+            failures,
+            status,
+            calls: calls.clone(),
+            mock_sender: MockSender {
+                test_case: "default-test-case".to_string(),
+            },
+        }
+    }
+}
+
+#[async_trait]
+impl HttpSender for FlakySender {
+    async fn send(&self, request: RequestBuilder) -> Result<Response, ErrorKind> {
+        let call = self.calls.fetch_add(1, Ordering::SeqCst);
+        if call >= self.failures {
+            return self.mock_sender.send(request).await;
+        }
+        let url = request.build().unwrap().url().clone();
+        if self.status == 0 {
+            return Err(ErrorKind::ConnectionError {
+                url: url.to_string(),
+                message: "connection refused".to_string(),
+            });
+        }
+        let response = http::response::Builder::new()
+            .status(self.status)
+            .header("Retry-After", "0")
+            .body("")
+            .unwrap();
+        Ok(response.into())
+    }
+}
+
+fn quick_retry_policy() -> RetryPolicy {
+    RetryPolicy {
+        max_attempts: 3,
+        initial_backoff: Duration::from_millis(1),
+        max_backoff: Duration::from_millis(4),
+        jitter: 0.0,
+        ..RetryPolicy::default()
+    }
+}
+
+async fn spot_groningen_with_flaky_sender(flaky_sender: FlakySender) -> VenueScraper {
+    let test_fixtures = common::setup().await;
+    let http_sender = Arc::new(RetryingHttpSender::new(flaky_sender, quick_retry_policy()));
+    VenueScraper::from_definition(
+        &venue_definition("spot_groningen"),
+        http_sender,
+        reqwest::Client::new(),
+        test_fixtures.store,
+    )
+    .unwrap()
+}
+
+#[tokio::test]
+async fn test_retry_transient_failures() {
+    for status in [0, 429, 502, 503, 504] {
+        let calls = Arc::new(AtomicU32::new(0));
+        let syncer = spot_groningen_with_flaky_sender(FlakySender::new(2, status, &calls)).await;
+
+        let syncing_result = syncer.sync().await.unwrap();
+        assert_eq!(syncing_result.total_retries, 2);
+        assert_eq!(syncing_result.total_urls_fetched, 1);
+        assert!(syncing_result.total_items > 0);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+}
+
+#[tokio::test]
+async fn test_retries_exhausted() {
+    let calls = Arc::new(AtomicU32::new(0));
+    let syncer = spot_groningen_with_flaky_sender(FlakySender::new(3, 503, &calls)).await;
+
+    let result = syncer.sync().await;
+    assert!(matches!(
+        result,
+        Err(ErrorKind::RetriesExhausted { retries: 2, .. })
+    ));
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_no_retry_for_other_failures() {
+    let calls = Arc::new(AtomicU32::new(0));
+    let syncer = spot_groningen_with_flaky_sender(FlakySender::new(1, 500, &calls)).await;
+
+    let result = syncer.sync().await;
+    assert!(matches!(
+        result,
+        Err(ErrorKind::StatusCodeFromUrl {
+            status_code: 500,
+            ..
+        })
+    ));
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+/// With retrying disabled a transient failure is reported with its status code.
+#[tokio::test]
+async fn test_no_retry_when_disabled() {
+    let test_fixtures = common::setup().await;
+    let calls = Arc::new(AtomicU32::new(0));
+    let retry_policy = RetryPolicy {
+        max_attempts: 1,
+        ..quick_retry_policy()
+    };
+    let syncer = VenueScraper::from_definition(
+        &venue_definition("spot_groningen"),
+        Arc::new(RetryingHttpSender::new(
+            FlakySender::new(1, 503, &calls),
+            retry_policy,
+        )),
+        reqwest::Client::new(),
+        test_fixtures.store,
+    )
+    .unwrap();
+
+    let partial_sync = syncer.sync_with_partial_result().await.unwrap_err();
+    assert!(matches!(
+        partial_sync.error,
+        ErrorKind::StatusCodeFromUrl {
+            status_code: 503,
+            ..
+        }
+    ));
+    assert_eq!(partial_sync.result.total_retries, 0);
+    assert_eq!(partial_sync.result.fetched_urls[0].status_code, Some(503));
+    assert_eq!(calls.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn test_retry_policy() {
+    let retry_policy = RetryPolicy {
+        initial_backoff: Duration::from_millis(100),
+        max_backoff: Duration::from_millis(300),
+        jitter: 0.0,
+        ..RetryPolicy::default()
+    };
+    assert_eq!(retry_policy.backoff(1), Duration::from_millis(100));
+    assert_eq!(retry_policy.backoff(2), Duration::from_millis(200));
+    assert_eq!(retry_policy.backoff(3), Duration::from_millis(300));
+
+    let retry_policy = RetryPolicy {
+        jitter: 0.5,
+        ..retry_policy
+    };
+    let backoff = retry_policy.backoff(2);
+    assert!(backoff >= Duration::from_millis(100) && backoff <= Duration::from_millis(200));
+
+    let now = Utc.with_ymd_and_hms(2022, 9, 7, 12, 0, 0).unwrap();
+    assert_eq!(
+        parse_retry_after("120", now),
+        Some(Duration::from_secs(120))
+    );
+    assert_eq!(
+        parse_retry_after("Wed, 07 Sep 2022 12:00:30 GMT", now),
+        Some(Duration::from_secs(30))
+    );
+    assert_eq!(parse_retry_after("soon", now), None);
+}
@@ -0,0 +1,85 @@
+mod common;
+
+use std::path::Path;
+use std::sync::Arc;
+use venue_scraper_api::errors::ErrorKind;
+use venue_scraper_api::http_sender::DefaultHttpSender;
+use venue_scraper_api::venue_definition::load_venue_definitions;
+use venue_scraper_api::VenueScraper;
+
+fn tivoli_scraper(test_fixtures: &common::TestFixtures) -> VenueScraper {
+    let venue_definition = load_venue_definitions(Path::new("venues.toml"))
+        .unwrap()
+        .into_iter()
+        .find(|venue_definition| venue_definition.id == "tivoli_utrecht")
+        .unwrap();
+    VenueScraper::from_definition(
+        &venue_definition,
+        Arc::new(DefaultHttpSender),
+        reqwest::Client::new(),
+        test_fixtures.store.clone(),
+    )
+    .unwrap()
+}
+
+/// The saved Tivoli agenda page, links are resolved against the agenda url of the venue.
+#[tokio::test]
+async fn test_check_saved_agenda_page() {
+    let test_fixtures = common::setup().await;
+    let selector_check = tivoli_scraper(&test_fixtures)
+        .check_file(Path::new("agenda.html"))
+        .unwrap();
+    assert_eq!(selector_check.venue_id, "tivoli_utrecht");
+    assert_eq!(selector_check.number_of_items, 50);
+    assert!(selector_check.parse_failures.is_empty());
+    let agenda = &selector_check.agenda_items[1];
+    assert!(agenda.title.starts_with("Blood"));
+    assert_eq!(
+        agenda.url,
+        "https://www.tivolivredenburg.nl/agenda/blood-red-shoes-22-07-2022/"
+    );
+    assert_eq!(agenda.start_text.as_deref(), Some("vr 22 jul 2022"));
+    // Nothing is stored.
+    assert!(test_fixtures
+        .store
+        .agenda_items_for_venue("tivoli_utrecht")
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+/// An element the selectors cannot parse is reported with the selector and its html.
+#[tokio::test]
+async fn test_unparseable_item_has_html_snippet() {
+    let test_fixtures = common::setup().await;
+    let page = r#"<ul>
+        <li class="agenda-list-item">
+          <a class="agenda-list-item__title-link" href="/agenda/kane/">Kane</a>
+        </li>
+        <li class="agenda-list-item">
+          <span>Without
+            a link</span>
+        </li>
+    </ul>"#;
+    let selector_check = tivoli_scraper(&test_fixtures)
+        .check_page("https://www.tivolivredenburg.nl/agenda/", page)
+        .unwrap();
+    assert_eq!(selector_check.number_of_items, 2);
+    assert_eq!(selector_check.number_of_unparseable_items, 1);
+    let parse_failure = &selector_check.parse_failures[0];
+    assert_eq!(parse_failure.reason, "CannotFindSelector: url");
+    assert_eq!(
+        parse_failure.html.as_deref(),
+        Some(r#"<li class="agenda-list-item"> <span>Without a link</span> </li>"#)
+    );
+}
+
+#[tokio::test]
+async fn test_missing_file_fails() {
+    let test_fixtures = common::setup().await;
+    let result = tivoli_scraper(&test_fixtures).check_file(Path::new("no-such-agenda.html"));
+    match result {
+        Err(ErrorKind::IoError { message }) => assert!(message.contains("no-such-agenda.html")),
+        other => panic!("Unexpected result {:?}", other),
+    }
+}
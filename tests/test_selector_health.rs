@@ -0,0 +1,303 @@
+mod common;
+mod mock_sender;
+
+use async_trait::async_trait;
+use chrono::{TimeZone, Utc};
+use common::json_venue::{json_venue_definition, ListingSender, JSON_VENUE};
+use mock_sender::{scraper_with_mock_sender, venue_definition};
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+use venue_scraper_api::agenda::AgendaStatus;
+use venue_scraper_api::health::{LayoutChangeAlert, SelectorHealth};
+use venue_scraper_api::registry::VenueRegistry;
+use venue_scraper_api::venue_definition::parse_venue_definitions;
+use venue_scraper_api::VenueScraper;
+
+/// Alert remembering the selector health it is told about.
+#[derive(Default)]
+struct RecordingAlert {
+    alerts: Mutex<Vec<SelectorHealth>>,
+}
+
+#[async_trait]
+impl LayoutChangeAlert for RecordingAlert {
+    async fn alert(&self, selector_health: &SelectorHealth) {
+        self.alerts.lock().unwrap().push(selector_health.clone());
+    }
+}
+
+fn events(count: u32, with_text: bool) -> serde_json::Value {
+    (0..count)
+        .map(|index| {
+            let mut event = json!({
+                "link": format!("https://www.example.com/events/{}/", index),
+                "title": format!("Event {}", index),
+                "start": "2030-03-14T20:30:00+01:00",
+            });
+            if with_text {
+                event["text"] = json!("About the event");
+            }
+            event
+        })
+        .collect()
+}
+
+fn selector_health(
+    number_of_items: u32,
+    number_of_unparseable_items: u32,
+    number_of_items_without_description: u32,
+) -> SelectorHealth {
+    SelectorHealth {
+        venue_id: "venue".to_string(),
+        checked_at: Utc.with_ymd_and_hms(2030, 1, 1, 0, 0, 0).unwrap(),
+        number_of_items,
+        number_of_unparseable_items,
+        number_of_items_without_description,
+        layout_change_suspected: None,
+    }
+}
+
+fn reasons(health: SelectorHealth, history: &[SelectorHealth]) -> Vec<String> {
+    health
+        .compare_with_history(history)
+        .layout_change_suspected
+        .map(|layout_change_suspected| layout_change_suspected.reasons)
+        .unwrap_or_default()
+}
+
+#[test]
+fn test_compare_with_history() {
+    let history = vec![
+        selector_health(20, 0, 2),
+        selector_health(22, 1, 1),
+        selector_health(18, 0, 0),
+    ];
+    assert!(reasons(selector_health(19, 1, 2), &history).is_empty());
+    assert_eq!(
+        reasons(selector_health(0, 0, 0), &history),
+        vec!["no agenda items found, 19.7 on average"]
+    );
+    assert_eq!(
+        reasons(selector_health(20, 20, 0), &history),
+        vec!["all 20 agenda items are unparseable"]
+    );
+    assert_eq!(
+        reasons(selector_health(5, 0, 0), &history),
+        vec!["5 agenda items found, 19.7 on average"]
+    );
+    assert_eq!(
+        reasons(selector_health(20, 8, 0), &history),
+        vec!["40% of the agenda items are unparseable, 2% on average"]
+    );
+    assert_eq!(
+        reasons(selector_health(20, 0, 20), &history),
+        vec!["100% of the agenda items have no description, 5% on average"]
+    );
+
+    // A short history is only enough to notice the listing is empty or broken.
+    assert!(reasons(selector_health(5, 0, 5), &history[..1]).is_empty());
+    assert_eq!(reasons(selector_health(0, 0, 0), &history[..1]).len(), 1);
+    assert!(reasons(selector_health(0, 0, 0), &[]).is_empty());
+
+    // Suspect runs are no baseline, the layout stays suspect until it is fixed.
+    let mut broken_history = history.clone();
+    for _ in 0..10 {
+        let health = selector_health(0, 0, 0).compare_with_history(&broken_history);
+        assert!(health.layout_change_suspected.is_some());
+        broken_history.insert(0, health);
+    }
+
+    // A listing that shrank for real is accepted after a few runs with as many agenda items.
+    let mut shrunk_history = history.clone();
+    for count in [8, 7, 8] {
+        let health = selector_health(count, 0, 0).compare_with_history(&shrunk_history);
+        assert!(health.layout_change_suspected.is_some());
+        shrunk_history.insert(0, health);
+    }
+    for count in [7, 8, 3] {
+        let health = selector_health(count, 0, 0).compare_with_history(&shrunk_history);
+        assert_eq!(health.layout_change_suspected.is_some(), count == 3);
+        shrunk_history.insert(0, health);
+    }
+}
+
+/// Every full sync stores its selector health, a listing that is suddenly empty and one that lost
+/// its descriptions are suspect and alerted.
+#[tokio::test]
+async fn test_layout_change_is_alerted() {
+    let test_fixtures = common::setup().await;
+    let sender = Arc::new(ListingSender::default());
+    let alert = Arc::new(RecordingAlert::default());
+    let registry = VenueRegistry::from_definitions(
+        &parse_venue_definitions(JSON_VENUE).unwrap(),
+        sender.clone(),
+        &reqwest::Client::new(),
+        test_fixtures.store.clone(),
+    )
+    .unwrap()
+    .with_layout_change_alert(alert.clone());
+
+    for count in [10, 11, 10] {
+        sender.set_events(events(count, true));
+        let sync_report = registry.sync_all().await;
+        let result = sync_report.venue_reports[0].result.as_ref().unwrap();
+        let health = result.selector_health.as_ref().unwrap();
+        assert_eq!(health.number_of_items, count);
+        assert_eq!(health.number_of_items_without_description, 0);
+        assert!(health.layout_change_suspected.is_none());
+    }
+    assert!(alert.alerts.lock().unwrap().is_empty());
+
+    sender.set_events(json!([]));
+    let sync_report = registry.sync_all().await;
+    assert!(sync_report.venue_reports[0]
+        .to_string()
+        .contains("layout change suspected: no agenda items found"));
+
+    sender.set_events(events(10, false));
+    registry.sync_all().await;
+
+    let alerts = alert.alerts.lock().unwrap().clone();
+    assert_eq!(alerts.len(), 2);
+    assert_eq!(
+        alerts[1].layout_change_suspected.as_ref().unwrap().reasons,
+        vec!["100% of the agenda items have no description, 0% on average"]
+    );
+
+    let history = test_fixtures
+        .store
+        .selector_health_for_venue("json_venue", 10)
+        .await
+        .unwrap();
+    assert_eq!(history.len(), 5);
+    assert_eq!(&history[0], &alerts[1]);
+    assert_eq!(history[1].number_of_items, 0);
+}
+
+/// The agenda items missing from a listing whose layout is suspected to have changed are not
+/// marked removed, even without a grace period.
+#[tokio::test]
+async fn test_suspect_layout_does_not_mark_items() {
+    let test_fixtures = common::setup().await;
+    let store = test_fixtures.store.clone();
+    let mut json_venue = json_venue_definition();
+    json_venue.removal_grace_hours = 0;
+    let sender = Arc::new(ListingSender::default());
+    let syncer = VenueScraper::from_definition(
+        &json_venue,
+        sender.clone(),
+        reqwest::Client::new(),
+        store.clone(),
+    )
+    .unwrap();
+
+    for count in [10, 11, 10] {
+        sender.set_events(events(count, true));
+        syncer.sync().await.unwrap();
+    }
+
+    sender.set_events(events(3, true));
+    let result = syncer.sync().await.unwrap();
+    let health = result.selector_health.unwrap();
+    assert_eq!(
+        health.layout_change_suspected.unwrap().reasons,
+        vec!["3 agenda items found, 10.3 on average"]
+    );
+    assert_eq!(result.total_items_removed, 0);
+    let agenda = store
+        .get_agenda_by_url("https://www.example.com/events/9/")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(agenda.status, AgendaStatus::Active);
+}
+
+/// A listing that shrank for real is suspect at first, after a few syncs with as many agenda items
+/// it is the new normal and the missing items are marked removed.
+#[tokio::test]
+async fn test_shrunk_listing_is_accepted() {
+    let test_fixtures = common::setup().await;
+    let store = test_fixtures.store.clone();
+    let mut json_venue = json_venue_definition();
+    json_venue.removal_grace_hours = 0;
+    let sender = Arc::new(ListingSender::default());
+    let syncer = VenueScraper::from_definition(
+        &json_venue,
+        sender.clone(),
+        reqwest::Client::new(),
+        store.clone(),
+    )
+    .unwrap();
+
+    for count in [10, 11, 10] {
+        sender.set_events(events(count, true));
+        syncer.sync().await.unwrap();
+    }
+
+    sender.set_events(events(3, true));
+    for _ in 0..3 {
+        let result = syncer.sync().await.unwrap();
+        assert!(result
+            .selector_health
+            .unwrap()
+            .layout_change_suspected
+            .is_some());
+        assert_eq!(result.total_items_removed, 0);
+    }
+
+    let result = syncer.sync().await.unwrap();
+    assert!(result
+        .selector_health
+        .unwrap()
+        .layout_change_suspected
+        .is_none());
+    assert_eq!(result.total_items_removed, 7);
+    let agenda = store
+        .get_agenda_by_url("https://www.example.com/events/9/")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(agenda.status, AgendaStatus::Removed);
+}
+
+/// A broken selector of a venue with an html listing makes every agenda item of the recorded
+/// listing unparseable, which is suspected to be a layout change.
+#[tokio::test]
+async fn test_broken_selector_is_suspected() {
+    let test_fixtures = common::setup().await;
+    let store = test_fixtures.store.clone();
+    let mut spot_groningen = venue_definition("spot_groningen");
+    spot_groningen.removal_grace_hours = 0;
+
+    let syncer = scraper_with_mock_sender(&spot_groningen, "default-test-case", store.clone());
+    let result = syncer.sync().await.unwrap();
+    assert!(result.total_items_inserted > 0);
+    assert!(result
+        .selector_health
+        .unwrap()
+        .layout_change_suspected
+        .is_none());
+
+    spot_groningen.selectors.as_mut().unwrap().title = "h1.program__title".to_string();
+    let syncer = scraper_with_mock_sender(&spot_groningen, "default-test-case", store.clone());
+    let broken_result = syncer.sync().await.unwrap();
+    assert_eq!(
+        broken_result.total_unparseable_items,
+        broken_result.total_items
+    );
+    let health = broken_result.selector_health.unwrap();
+    assert_eq!(
+        health.layout_change_suspected.unwrap().reasons,
+        vec![format!(
+            "all {} agenda items are unparseable",
+            result.total_items
+        )]
+    );
+    assert_eq!(broken_result.total_items_removed, 0);
+    assert!(store
+        .agenda_items_for_venue("spot_groningen")
+        .await
+        .unwrap()
+        .iter()
+        .all(|agenda| agenda.status == AgendaStatus::Active));
+}
@@ -0,0 +1,314 @@
+mod common;
+
+use async_trait::async_trait;
+use reqwest::{RequestBuilder, Response, ResponseBuilderExt};
+use std::sync::Arc;
+use venue_scraper_api::errors::ErrorKind;
+use venue_scraper_api::http_sender::HttpSender;
+use venue_scraper_api::venue_definition::parse_venue_definitions;
+use venue_scraper_api::VenueScraper;
+
+/// JSON-LD listing with a graph holding an item list of events, a css item the structured data
+/// lacks, an item without title the structured data covers and one without link it does not.
+const JSON_LD_LISTING: &str = r#"
+<html><head>
+<script type="application/ld+json">
+{
+  "@context": "https://schema.org",
+  "@graph": [
+    {"@type": "Organization", "name": "Example venue", "url": "https://www.example.com/"},
+    {
+      "@type": "ItemList",
+      "itemListElement": [
+        {
+          "@type": "ListItem",
+          "item": {
+            "@type": "MusicEvent",
+            "name": "Kane &amp; Abel",
+            "url": "/programma/kane-abel?utm_source=ld",
+            "startDate": "2030-03-14T20:30:00+01:00",
+            "endDate": "2030-03-14T23:00:00+01:00",
+            "doorTime": "19:30",
+            "image": {"@type": "ImageObject", "url": "/images/kane.jpg"},
+            "performer": [
+              {"@type": "MusicGroup", "name": "Kane"},
+              {"@type": "MusicGroup", "name": "The Openers"}
+            ],
+            "offers": {
+              "@type": "Offer",
+              "price": "35.60",
+              "priceCurrency": "EUR",
+              "url": "https://tickets.example.com/kane"
+            }
+          }
+        },
+        {
+          "@type": "ListItem",
+          "item": {
+            "@type": ["Event", "https://schema.org/Festival"],
+            "name": "Summer festival",
+            "url": "https://www.example.com/programma/summer-festival/",
+            "startDate": "2030-07-01"
+          }
+        }
+      ]
+    }
+  ]
+}
+</script>
+<script type="application/ld+json">{ not json </script>
+</head><body><ul>
+  <li><a href="/programma/kane-abel/">Kane &amp; Abel</a><p>Dutch rock</p></li>
+  <li><a href="/programma/summer-festival/">Summer festival</a></li>
+  <li><a href="/programma/css-only/">Css only</a><p>Only in the html</p>
+    <span class="date">zaterdag 16 maart 2030</span></li>
+  <li><a href="/programma/summer-festival/#tickets"><img src="/festival.jpg"></a></li>
+  <li><span>Without a link</span></li>
+</ul></body></html>
+"#;
+
+const MICRODATA_LISTING: &str = r#"
+<html><body>
+  <div itemscope itemtype="https://schema.org/MusicEvent">
+    <a itemprop="url" href="/programma/micro/"><span itemprop="name">Micro  band</span></a>
+    <meta itemprop="startDate" content="2030-04-02T21:00">
+    <div itemprop="location" itemscope itemtype="https://schema.org/Place">
+      <span itemprop="name">Small hall</span>
+    </div>
+    <div itemprop="offers" itemscope itemtype="https://schema.org/Offer">
+      <meta itemprop="priceCurrency" content="EUR"><span itemprop="price">12.50</span>
+    </div>
+  </div>
+</body></html>
+"#;
+
+/// Details page with an exact date in its JSON-LD and a date text for the css selectors.
+const DETAILS: &str = r#"
+<html><head>
+<script type="application/ld+json">
+{"@context": "https://schema.org", "@type": "MusicEvent", "name": "Css only",
+ "url": "https://www.example.com/programma/css-only/",
+ "startDate": "2030-03-16T21:15:00+01:00",
+ "description": "The whole <b>story</b>"}
+</script>
+</head><body>
+  <p class="start">zaterdag 16 maart 2030 20:00</p>
+  <p class="price">€ 10,00</p>
+</body></html>
+"#;
+
+/// Sender answering the listings and the details pages.
+struct StructuredDataSender;
+
+#[async_trait]
+impl HttpSender for StructuredDataSender {
+    async fn send(&self, request: RequestBuilder) -> Result<Response, ErrorKind> {
+        let url = request.build().unwrap().url().clone();
+        let body = match url.path() {
+            "/programma/" => JSON_LD_LISTING,
+            "/microdata/" => MICRODATA_LISTING,
+            "/programma/css-only/" => DETAILS,
+            _ => "<html><body></body></html>",
+        };
+        let response = http::response::Builder::new()
+            .url(url)
+            .status(200)
+            .body(body)
+            .unwrap();
+        Ok(response.into())
+    }
+}
+
+fn venue_scraper(venues: &str, test_fixtures: &common::TestFixtures) -> VenueScraper {
+    let venue_definition = parse_venue_definitions(venues).unwrap().remove(0);
+    VenueScraper::from_definition(
+        &venue_definition,
+        Arc::new(StructuredDataSender),
+        reqwest::Client::new(),
+        test_fixtures.store.clone(),
+    )
+    .unwrap()
+}
+
+/// The JSON-LD events are stored with exact dates, the css selectors fill in the description and
+/// add the item the structured data lacks.
+#[tokio::test]
+async fn test_sync_json_ld_with_css_fallback() {
+    let test_fixtures = common::setup().await;
+    let venue_scraper = venue_scraper(
+        r#"
+        [[venue]]
+        id = "json_ld_venue"
+        name = "JSON-LD venue"
+        agenda_url = "https://www.example.com/programma/"
+        structured_data = true
+        fetch_details = true
+
+        [venue.selectors]
+        agenda_item = "li"
+        url = "a"
+        title = "a"
+        description = "p"
+        date = "span.date"
+
+        [venue.detail_selectors]
+        start = "p.start"
+        price = "p.price"
+        "#,
+        &test_fixtures,
+    );
+
+    let syncing_result = venue_scraper.sync().await.unwrap();
+    assert_eq!(syncing_result.total_items, 4);
+    assert_eq!(syncing_result.total_items_inserted, 3);
+    assert_eq!(syncing_result.total_unparseable_items, 1);
+    let parse_failure = &syncing_result.parse_failures[0];
+    assert!(parse_failure.reason.starts_with("CannotFindSelector"));
+    assert!(parse_failure
+        .html
+        .as_deref()
+        .unwrap()
+        .contains("Without a link"));
+    assert_eq!(syncing_result.total_unparseable_dates, 0);
+
+    let store = &test_fixtures.store;
+    let kane = store
+        .get_agenda_by_url("https://www.example.com/programma/kane-abel/")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(kane.title, "Kane & Abel");
+    assert_eq!(kane.description.as_deref(), Some("Dutch rock"));
+    assert_eq!(
+        kane.starts_at.unwrap().to_rfc3339(),
+        "2030-03-14T20:30:00+01:00"
+    );
+    assert_eq!(
+        kane.ends_at.unwrap().to_rfc3339(),
+        "2030-03-14T23:00:00+01:00"
+    );
+    assert_eq!(
+        kane.doors_open_at.unwrap().to_rfc3339(),
+        "2030-03-14T19:30:00+01:00"
+    );
+    assert_eq!(kane.price.as_deref(), Some("€ 35,60"));
+    assert_eq!(
+        kane.ticket_url.as_deref(),
+        Some("https://tickets.example.com/kane")
+    );
+    assert_eq!(
+        kane.image_url.as_deref(),
+        Some("https://www.example.com/images/kane.jpg")
+    );
+    assert_eq!(kane.support_acts, vec!["The Openers"]);
+
+    let festival = store
+        .get_agenda_by_url("https://www.example.com/programma/summer-festival/")
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(festival.all_day);
+    assert_eq!(
+        festival.starts_at.unwrap().to_rfc3339(),
+        "2030-07-01T00:00:00+02:00"
+    );
+
+    let css_only_url = "https://www.example.com/programma/css-only/";
+    let css_only = store
+        .get_agenda_by_url(css_only_url)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        css_only.start_text.as_deref(),
+        Some("zaterdag 16 maart 2030")
+    );
+    assert!(css_only.all_day);
+
+    // The structured data of the details page wins over the selected start text, the price only
+    // has a selector.
+    let syncing_result = venue_scraper.sync_details().await.unwrap();
+    assert_eq!(syncing_result.total_urls_unfetchable, 0);
+    let css_only = store
+        .get_agenda_by_url(css_only_url)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        css_only.starts_at.unwrap().to_rfc3339(),
+        "2030-03-16T21:15:00+01:00"
+    );
+    assert!(!css_only.all_day);
+    assert_eq!(css_only.price.as_deref(), Some("€ 10,00"));
+    assert_eq!(css_only.description.as_deref(), Some("The whole story"));
+    let kane = store
+        .get_agenda_by_url("https://www.example.com/programma/kane-abel/")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(kane.title, "Kane & Abel");
+    // The details page of kane has no price, it keeps the price of the listing.
+    assert_eq!(kane.price.as_deref(), Some("€ 35,60"));
+    assert_eq!(
+        kane.ticket_url.as_deref(),
+        Some("https://tickets.example.com/kane")
+    );
+    assert_eq!(kane.support_acts, vec!["The Openers"]);
+
+    // Unchanged structured data is no change to the listing.
+    let syncing_result = venue_scraper.sync().await.unwrap();
+    assert_eq!(syncing_result.total_items_inserted, 0);
+    assert_eq!(syncing_result.total_items_updated, 0);
+}
+
+/// A venue without selectors reads the microdata of its listing.
+#[tokio::test]
+async fn test_sync_microdata() {
+    let test_fixtures = common::setup().await;
+    let venue_scraper = venue_scraper(
+        r#"
+        [[venue]]
+        id = "microdata_venue"
+        name = "Microdata venue"
+        agenda_url = "https://www.example.com/microdata/"
+        structured_data = true
+        "#,
+        &test_fixtures,
+    );
+
+    let syncing_result = venue_scraper.sync().await.unwrap();
+    assert_eq!(syncing_result.total_items, 1);
+    assert_eq!(syncing_result.total_items_inserted, 1);
+
+    let agenda = test_fixtures
+        .store
+        .get_agenda_by_url("https://www.example.com/programma/micro/")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(agenda.title, "Micro band");
+    assert_eq!(
+        agenda.starts_at.unwrap().to_rfc3339(),
+        "2030-04-02T21:00:00+02:00"
+    );
+    assert_eq!(agenda.price.as_deref(), Some("€ 12,50"));
+}
+
+#[test]
+fn test_selectors_required_without_structured_data() {
+    let venues = r#"
+        [[venue]]
+        id = "no_selectors"
+        name = "No selectors"
+        agenda_url = "https://www.example.com/programma/"
+    "#;
+    match parse_venue_definitions(venues) {
+        Err(ErrorKind::InvalidVenueDefinition { venue_id, .. }) => {
+            assert_eq!(venue_id, "no_selectors")
+        }
+        other => panic!(
+            "Expected an invalid venue definition, got {:?}",
+            other.map(|_| ())
+        ),
+    }
+}
@@ -1,6 +1,9 @@
 use mock_sender::MockSender;
-use std::rc::Rc;
+use std::path::Path;
+use std::sync::Arc;
+use venue_scraper_api::registry::DEFAULT_MAX_CONCURRENT_VENUES;
 use venue_scraper_api::sync_venues;
+use venue_scraper_api::venue_definition::load_venue_definitions;
 
 mod common;
 mod mock_sender;
@@ -10,12 +13,20 @@ mod mock_sender;
 async fn test_sync_all_venues() {
     let test_fixtures = common::setup().await;
 
-    let mock_sender = Rc::new(MockSender {
+    let mock_sender = Arc::new(MockSender {
         test_case: String::from("default-test-case"),
     });
 
     let client = reqwest::Client::new();
-    let result = sync_venues(&client, &test_fixtures.db, mock_sender).await;
+    let venue_definitions = load_venue_definitions(Path::new("venues.toml")).unwrap();
+    let result = sync_venues(
+        &client,
+        test_fixtures.store.clone(),
+        mock_sender,
+        &venue_definitions,
+        DEFAULT_MAX_CONCURRENT_VENUES,
+    )
+    .await;
     assert!(result.is_ok());
 
     let syncing_results = result.unwrap();
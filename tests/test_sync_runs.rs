@@ -0,0 +1,157 @@
+mod common;
+
+use chrono::{DateTime, Utc};
+use common::json_venue::{ListingSender, JSON_VENUE};
+use serde_json::json;
+use std::sync::Arc;
+use std::time::Duration;
+use venue_scraper_api::registry::{SyncJob, VenueRegistry};
+use venue_scraper_api::retry::{RetryPolicy, RetryingHttpSender};
+use venue_scraper_api::sync_run::daily_trends;
+use venue_scraper_api::venue_definition::parse_venue_definitions;
+
+const BROKEN_VENUE: &str = r#"
+[[venue]]
+id = "broken_venue"
+name = "Broken venue"
+agenda_url = "https://www.example.com/missing"
+
+[venue.json]
+items = "$[*]"
+
+[venue.json.fields]
+url = "$.link"
+title = "$.title"
+"#;
+
+/// Every registry run stores a sync run per venue, with the fetched urls and the errors, and the
+/// last successful run stays known while a venue fails.
+#[tokio::test]
+async fn test_sync_runs_are_stored() {
+    let test_fixtures = common::setup().await;
+    let store = test_fixtures.store.clone();
+    let sender = Arc::new(ListingSender::new(json!([
+        {"link": "https://www.example.com/events/kane/", "title": "Kane", "start": "2030-03-14T20:30:00+01:00"},
+    ])));
+    let mut definitions = parse_venue_definitions(JSON_VENUE).unwrap();
+    definitions.extend(parse_venue_definitions(BROKEN_VENUE).unwrap());
+    let registry = VenueRegistry::from_definitions(
+        &definitions,
+        sender.clone(),
+        &reqwest::Client::new(),
+        store.clone(),
+    )
+    .unwrap();
+    let since = DateTime::<Utc>::MIN_UTC;
+
+    registry.sync_all().await;
+    let sync_runs = store
+        .sync_runs_for_venue("json_venue", since)
+        .await
+        .unwrap();
+    assert_eq!(sync_runs.len(), 1);
+    let sync_run = &sync_runs[0];
+    assert_eq!(sync_run.job, SyncJob::Listing);
+    assert!(sync_run.successful);
+    assert!(sync_run.errors.is_empty());
+    assert!(sync_run.finished_at >= sync_run.started_at);
+    let result = sync_run.result.as_ref().unwrap();
+    assert_eq!(result.total_items_inserted, 1);
+    assert_eq!(result.fetched_urls.len(), 1);
+    assert_eq!(result.fetched_urls[0].url, "https://www.example.com/events");
+    assert_eq!(result.fetched_urls[0].status_code, Some(200));
+
+    let broken_runs = store
+        .sync_runs_for_venue("broken_venue", since)
+        .await
+        .unwrap();
+    assert_eq!(broken_runs.len(), 1);
+    assert!(!broken_runs[0].successful);
+    assert_eq!(broken_runs[0].errors.len(), 1);
+    assert!(broken_runs[0].errors[0].starts_with("StatusCodeFromUrl"));
+    let partial_result = broken_runs[0].result.as_ref().unwrap();
+    assert_eq!(partial_result.total_urls_unfetchable, 1);
+    assert_eq!(partial_result.fetched_urls[0].status_code, Some(404));
+    assert!(broken_runs[0].to_string().contains("failed"));
+    assert!(store
+        .last_successful_sync_run("broken_venue")
+        .await
+        .unwrap()
+        .is_none());
+
+    sender.set_failing(true);
+    registry.sync_all().await;
+    let sync_runs = store
+        .sync_runs_for_venue("json_venue", since)
+        .await
+        .unwrap();
+    assert_eq!(sync_runs.len(), 2);
+    assert!(!sync_runs[1].successful);
+    let failed_result = sync_runs[1].result.as_ref().unwrap();
+    assert_eq!(failed_result.fetched_urls[0].status_code, Some(503));
+    let last_successful = store
+        .last_successful_sync_run("json_venue")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(last_successful.started_at, sync_runs[0].started_at);
+    assert!(store
+        .sync_runs_for_venue(
+            "json_venue",
+            sync_runs[1].finished_at + chrono::Duration::days(1)
+        )
+        .await
+        .unwrap()
+        .is_empty());
+
+    let trends = daily_trends(&sync_runs);
+    assert_eq!(trends.len(), 1);
+    assert_eq!(trends[0].venue_id, "json_venue");
+    assert_eq!(trends[0].runs, 2);
+    assert_eq!(trends[0].successful_runs, 1);
+    assert_eq!(trends[0].total_items_inserted, 1);
+    assert_eq!(trends[0].total_urls_fetched, 2);
+}
+
+/// A venue whose listing cannot be fetched still stores what the sync did: the failed url and its
+/// retries.
+#[tokio::test]
+async fn test_failed_sync_run_keeps_partial_result() {
+    let test_fixtures = common::setup().await;
+    let store = test_fixtures.store.clone();
+    let sender = ListingSender::default();
+    sender.set_failing(true);
+    let retry_policy = RetryPolicy {
+        max_attempts: 3,
+        initial_backoff: Duration::ZERO,
+        jitter: 0.0,
+        ..RetryPolicy::default()
+    };
+    let registry = VenueRegistry::from_definitions(
+        &parse_venue_definitions(JSON_VENUE).unwrap(),
+        Arc::new(RetryingHttpSender::new(sender, retry_policy)),
+        &reqwest::Client::new(),
+        store.clone(),
+    )
+    .unwrap();
+
+    let sync_report = registry.sync_all().await;
+    let partial_result = sync_report.venue_reports[0]
+        .partial_result
+        .as_ref()
+        .unwrap();
+    assert_eq!(partial_result.total_retries, 2);
+
+    let sync_runs = store
+        .sync_runs_for_venue("json_venue", DateTime::<Utc>::MIN_UTC)
+        .await
+        .unwrap();
+    assert!(!sync_runs[0].successful);
+    assert!(sync_runs[0].errors[0].starts_with("RetriesExhausted"));
+    let result = sync_runs[0].result.as_ref().unwrap();
+    assert_eq!(result.total_urls_fetched, 1);
+    assert_eq!(result.total_urls_unfetchable, 1);
+    assert_eq!(result.total_retries, 2);
+    assert_eq!(result.fetched_urls[0].url, "https://www.example.com/events");
+    assert!(result.fetched_urls[0].error.is_some());
+}
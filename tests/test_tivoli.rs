@@ -8,7 +8,7 @@ async fn test_sync_tivoli() {
     let test_fixtures = common::setup().await;
 
     let tivoli_syncer =
-        tivoli_utrecht_with_mock_sender("default-test-case", test_fixtures.db.clone());
+        tivoli_utrecht_with_mock_sender("default-test-case", test_fixtures.store.clone());
     let result = tivoli_syncer.sync().await;
 
     assert!(result.is_ok());
@@ -17,6 +17,7 @@ async fn test_sync_tivoli() {
     assert_eq!(syncing_result.total_items, 591);
     assert_eq!(syncing_result.total_items_inserted, 591);
     assert_eq!(syncing_result.total_unparseable_items, 0);
+    assert_eq!(syncing_result.total_unparseable_dates, 0);
 
     ()
 }
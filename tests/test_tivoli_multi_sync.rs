@@ -11,7 +11,7 @@ use mock_sender::tivoli_utrecht_with_mock_sender;
 async fn test_sync_multi_sync() {
     let test_fixtures = common::setup().await;
     let tivoli_syncer =
-        tivoli_utrecht_with_mock_sender("multiple-fetch-run-1", test_fixtures.db.clone());
+        tivoli_utrecht_with_mock_sender("multiple-fetch-run-1", test_fixtures.store.clone());
 
     let result = tivoli_syncer.sync().await;
 
@@ -22,7 +22,7 @@ async fn test_sync_multi_sync() {
 
     // Run 2.
     let tivoli_syncer =
-        tivoli_utrecht_with_mock_sender("multiple-fetch-run-2", test_fixtures.db.clone());
+        tivoli_utrecht_with_mock_sender("multiple-fetch-run-2", test_fixtures.store.clone());
     let result = tivoli_syncer.sync().await;
     assert!(result.is_ok());
     let syncing_result = result.unwrap();
@@ -0,0 +1,171 @@
+mod common;
+
+use async_trait::async_trait;
+use reqwest::{RequestBuilder, Response, ResponseBuilderExt};
+use std::sync::Arc;
+use url::Url;
+use venue_scraper_api::errors::ErrorKind;
+use venue_scraper_api::http_sender::HttpSender;
+use venue_scraper_api::urls::{canonical_url, normalize_url};
+use venue_scraper_api::venue_definition::parse_venue_definitions;
+use venue_scraper_api::VenueScraper;
+
+const LISTING: &str = r#"
+<html><body><ul>
+  <li><a href="/programma/relative">Relative</a></li>
+  <li><a href="//www.example.com/programma/protocol-relative/">Protocol relative</a></li>
+  <li><a href="detail.html#tickets">Document relative</a></li>
+  <li><a href="https://www.example.com/programma/tracked/?utm_source=news&amp;utm_medium=mail&amp;id=7">Tracked</a></li>
+  <li><a href="https://www.example.com/programma/relative/#top">Duplicate</a></li>
+</ul></body></html>
+"#;
+
+const DETAILS: &str = r#"
+<html><body>
+  <a class="tickets" href="/tickets?id=7">Tickets</a>
+  <img class="poster" src="//cdn.example.com/poster.jpg">
+</body></html>
+"#;
+
+/// Sender answering the listing at /programma/ and the details of every agenda item.
+struct RelativeLinksSender;
+
+#[async_trait]
+impl HttpSender for RelativeLinksSender {
+    async fn send(&self, request: RequestBuilder) -> Result<Response, ErrorKind> {
+        let url = request.build().unwrap().url().clone();
+        let body = if url.path() == "/programma/" {
+            LISTING
+        } else {
+            DETAILS
+        };
+        let response = http::response::Builder::new()
+            .url(url)
+            .status(200)
+            .body(body)
+            .unwrap();
+        Ok(response.into())
+    }
+}
+
+#[test]
+fn test_canonical_url() {
+    let page_url = Url::parse("https://www.example.com/programma/?page=2").unwrap();
+    for (href, expected) in [
+        ("/programma/foo", "https://www.example.com/programma/foo/"),
+        ("//cdn.example.com/foo/", "https://cdn.example.com/foo/"),
+        ("foo/", "https://www.example.com/programma/foo/"),
+        ("foo.html", "https://www.example.com/programma/foo.html"),
+        (
+            " https://www.example.com/foo?utm_campaign=x&fbclid=y#top ",
+            "https://www.example.com/foo/",
+        ),
+        (
+            "https://www.example.com/foo/?id=7&UTM_SOURCE=x",
+            "https://www.example.com/foo/?id=7",
+        ),
+        ("HTTPS://WWW.EXAMPLE.COM", "https://www.example.com/"),
+    ] {
+        assert_eq!(
+            canonical_url(&page_url, href).unwrap(),
+            expected,
+            "{}",
+            href
+        );
+    }
+}
+
+#[test]
+fn test_normalize_url_is_idempotent() {
+    let url = normalize_url(Url::parse("https://www.example.com/a/b?utm_x=1&b=2#c").unwrap());
+    assert_eq!(url.as_str(), "https://www.example.com/a/b/?b=2");
+    assert_eq!(normalize_url(url.clone()), url);
+}
+
+/// Agenda items with relative links get canonical urls, duplicates are stored once, the details
+/// pages are fetched as linked and the details links are absolute.
+#[tokio::test]
+async fn test_sync_relative_links() {
+    let test_fixtures = common::setup().await;
+    let venues = r#"
+        [[venue]]
+        id = "relative_venue"
+        name = "Relative venue"
+        agenda_url = "https://www.example.com/programma/"
+        fetch_details = true
+
+        [venue.selectors]
+        agenda_item = "li"
+        url = "a"
+        title = "a"
+        description = "p"
+
+        [venue.detail_selectors]
+        ticket_url = "a.tickets"
+        image = "img.poster"
+    "#;
+    let venue_definition = parse_venue_definitions(venues).unwrap().remove(0);
+    let venue_scraper = VenueScraper::from_definition(
+        &venue_definition,
+        Arc::new(RelativeLinksSender),
+        reqwest::Client::new(),
+        test_fixtures.store.clone(),
+    )
+    .unwrap();
+
+    let syncing_result = venue_scraper.sync().await.unwrap();
+    assert_eq!(syncing_result.total_items, 5);
+    assert_eq!(syncing_result.total_items_inserted, 4);
+    let syncing_result = venue_scraper.sync_details().await.unwrap();
+    assert_eq!(syncing_result.total_urls_unfetchable, 0);
+    // The details pages are fetched at the links as listed, the last of the duplicates.
+    let mut fetched_urls: Vec<String> = syncing_result
+        .fetched_urls
+        .into_iter()
+        .map(|fetched_url| fetched_url.url)
+        .collect();
+    fetched_urls.sort();
+    assert_eq!(
+        fetched_urls,
+        vec![
+            "https://www.example.com/programma/detail.html#tickets",
+            "https://www.example.com/programma/protocol-relative/",
+            "https://www.example.com/programma/relative/#top",
+            "https://www.example.com/programma/tracked/?utm_source=news&utm_medium=mail&id=7",
+        ]
+    );
+
+    let mut urls: Vec<String> = test_fixtures
+        .store
+        .agenda_items_for_venue("relative_venue")
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|agenda| agenda.url)
+        .collect();
+    urls.sort();
+    assert_eq!(
+        urls,
+        vec![
+            "https://www.example.com/programma/detail.html",
+            "https://www.example.com/programma/protocol-relative/",
+            "https://www.example.com/programma/relative/",
+            "https://www.example.com/programma/tracked/?id=7",
+        ]
+    );
+
+    let agenda = test_fixtures
+        .store
+        .get_agenda_by_url("https://www.example.com/programma/relative/")
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        agenda.ticket_url.unwrap(),
+        "https://www.example.com/tickets?id=7"
+    );
+    assert_eq!(
+        agenda.image_url.unwrap(),
+        "https://cdn.example.com/poster.jpg"
+    );
+}
@@ -0,0 +1,81 @@
+use std::path::Path;
+use venue_scraper_api::errors::ErrorKind;
+use venue_scraper_api::venue_definition::{
+    load_venue_definitions, parse_venue_definitions, PaginationStrategy,
+};
+
+/// The venues file in the repository must be valid.
+#[test]
+fn test_load_venue_definitions() {
+    let venue_definitions = load_venue_definitions(Path::new("venues.toml")).unwrap();
+    assert_eq!(venue_definitions.len(), 2);
+
+    let tivoli = &venue_definitions[0];
+    assert_eq!(tivoli.id, "tivoli_utrecht");
+    let pagination = tivoli.pagination.as_ref().unwrap();
+    assert!(matches!(
+        &pagination.strategy,
+        PaginationStrategy::NextLink { selector } if selector == "link[rel=next], a[rel=next]"
+    ));
+    assert_eq!(pagination.max_pages, 40);
+    assert_eq!(tivoli.max_concurrent_details, 4);
+}
+
+const VALID_VENUE: &str = r#"
+    [[venue]]
+    id = "valid_venue"
+    name = "Valid venue"
+    agenda_url = "https://www.example.com/agenda/"
+
+    [venue.selectors]
+    agenda_item = "li"
+    url = "a"
+    title = "a"
+    description = "p"
+"#;
+
+const BROKEN_VENUE: &str = r#"
+    [[venue]]
+    id = "broken_venue"
+    name = "Broken venue"
+    agenda_url = "https://www.example.com/agenda/"
+
+    [venue.selectors]
+    agenda_item = "li["
+    url = "a"
+    title = "a"
+    description = "p"
+"#;
+
+/// Validation errors must point at the offending venue.
+#[test]
+fn test_invalid_venue_definitions() {
+    let venues = format!("{}{}", VALID_VENUE, BROKEN_VENUE);
+    match parse_venue_definitions(&venues) {
+        Err(ErrorKind::InvalidVenueDefinition { venue_id, message }) => {
+            assert_eq!(venue_id, "broken_venue");
+            assert!(message.contains("agenda_item"));
+        }
+        other => panic!("Expected InvalidVenueDefinition, got {:?}", other),
+    }
+
+    let duplicate_venues = format!("{}{}", VALID_VENUE, VALID_VENUE);
+    match parse_venue_definitions(&duplicate_venues) {
+        Err(ErrorKind::InvalidVenueDefinition { venue_id, message }) => {
+            assert_eq!(venue_id, "valid_venue");
+            assert!(message.contains("more than once"));
+        }
+        other => panic!("Expected InvalidVenueDefinition, got {:?}", other),
+    }
+
+    let sequential_venue = VALID_VENUE.replace(
+        "agenda_url = \"https://www.example.com/agenda/\"",
+        "agenda_url = \"https://www.example.com/agenda/\"\n    max_concurrent_details = 0",
+    );
+    match parse_venue_definitions(&sequential_venue) {
+        Err(ErrorKind::InvalidVenueDefinition { message, .. }) => {
+            assert!(message.contains("max_concurrent_details"));
+        }
+        other => panic!("Expected InvalidVenueDefinition, got {:?}", other),
+    }
+}